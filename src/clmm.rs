@@ -0,0 +1,241 @@
+use rust_decimal::{ prelude::FromPrimitive, Decimal, MathematicalOps };
+use rust_decimal_macros::dec;
+
+/// Initialized tick of a concentrated liquidity pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    pub index: i32,
+    pub liquidity_net: i128,
+}
+
+/// Concentrated liquidity pool (raydium clmm, orca whirlpool, uniswap v3) in raw token units,
+/// with its initialized ticks only known within `[lower_tick, upper_tick)`.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    pub sqrt_price: Decimal,
+    pub liquidity: u128,
+    pub tick_current: i32,
+    pub ticks: Vec<Tick>, // ordered by index
+    pub lower_tick: i32,
+    pub upper_tick: i32,
+}
+
+/// Outcome of an exact input swap, in raw token units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Swap {
+    pub amount_in: Decimal,
+    pub amount_out: Decimal,
+    pub ticks_crossed: usize,
+}
+
+impl Swap {
+    /// Effective price in output per input token.
+    pub fn price(&self) -> Decimal {
+        if self.amount_in.is_zero() {
+            return Decimal::ZERO;
+        }
+        self.amount_out / self.amount_in
+    }
+}
+
+pub fn sqrt_price_at_tick(tick: i32) -> Option<Decimal> {
+    dec!(1.0001).sqrt()?.checked_powi(i64::from(tick))
+}
+
+impl Pool {
+    /// Simulates swapping `amount_in` (token 0 when `zero_for_one`, token 1 otherwise) net
+    /// of `fee_rate`, crossing ticks as the price moves. `None` when the swap cannot be
+    /// filled within the liquidity known to the pool.
+    pub fn swap(&self, amount_in: Decimal, zero_for_one: bool, fee_rate: Decimal) -> Option<Swap> {
+        if amount_in <= Decimal::ZERO || self.sqrt_price <= Decimal::ZERO {
+            return None;
+        }
+
+        let mut remaining = amount_in.checked_mul(Decimal::ONE - fee_rate)?;
+        let mut amount_out = Decimal::ZERO;
+        let mut sqrt_price = self.sqrt_price;
+        // beyond 96 bits for the decimal
+        let mut liquidity = Decimal::from_u128(self.liquidity)?;
+        let mut ticks_crossed = 0;
+
+        // initialized ticks in the direction of the swap, bounded by the known range
+        let mut ticks: Box<dyn Iterator<Item = Tick>> = if zero_for_one {
+            let bound = Tick { index: self.lower_tick, liquidity_net: 0 };
+            Box::new(
+                self.ticks
+                    .iter()
+                    .rev()
+                    .filter(|tick| tick.index <= self.tick_current && tick.index > self.lower_tick)
+                    .copied()
+                    .chain([bound])
+            )
+        } else {
+            let bound = Tick { index: self.upper_tick, liquidity_net: 0 };
+            Box::new(
+                self.ticks
+                    .iter()
+                    .filter(|tick| tick.index > self.tick_current && tick.index < self.upper_tick)
+                    .copied()
+                    .chain([bound])
+            )
+        };
+
+        while !remaining.is_zero() {
+            let tick = ticks.next()?;
+            let sqrt_price_target = sqrt_price_at_tick(tick.index)?;
+            let is_bound = tick.index == self.lower_tick || tick.index == self.upper_tick;
+
+            // input needed to move the price to the target within the current range
+            let (amount_to_target, out_to_target) = if zero_for_one {
+                (
+                    liquidity.checked_mul(
+                        Decimal::ONE.checked_div(sqrt_price_target)? -
+                            Decimal::ONE.checked_div(sqrt_price)?
+                    )?,
+                    liquidity.checked_mul(sqrt_price - sqrt_price_target)?,
+                )
+            } else {
+                (
+                    liquidity.checked_mul(sqrt_price_target - sqrt_price)?,
+                    liquidity.checked_mul(
+                        Decimal::ONE.checked_div(sqrt_price)? -
+                            Decimal::ONE.checked_div(sqrt_price_target)?
+                    )?,
+                )
+            };
+
+            if remaining < amount_to_target {
+                let sqrt_price_next = if zero_for_one {
+                    Decimal::ONE.checked_div(
+                        Decimal::ONE.checked_div(sqrt_price)? + remaining.checked_div(liquidity)?
+                    )?
+                } else {
+                    sqrt_price + remaining.checked_div(liquidity)?
+                };
+
+                amount_out += if zero_for_one {
+                    liquidity.checked_mul(sqrt_price - sqrt_price_next)?
+                } else {
+                    liquidity.checked_mul(
+                        Decimal::ONE.checked_div(sqrt_price)? -
+                            Decimal::ONE.checked_div(sqrt_price_next)?
+                    )?
+                };
+                remaining = Decimal::ZERO;
+            } else {
+                // the known liquidity ends here
+                if is_bound {
+                    return None;
+                }
+
+                remaining -= amount_to_target;
+                amount_out += out_to_target;
+                sqrt_price = sqrt_price_target;
+
+                // crossing down removes the liquidity the tick adds when crossing up
+                let liquidity_net = Decimal::from_i128(tick.liquidity_net)?;
+                liquidity = if zero_for_one {
+                    liquidity.checked_sub(liquidity_net)?
+                } else {
+                    liquidity.checked_add(liquidity_net)?
+                };
+                ticks_crossed += 1;
+
+                if liquidity < Decimal::ZERO {
+                    return None;
+                }
+            }
+        }
+
+        Some(Swap { amount_in, amount_out, ticks_crossed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(ticks: Vec<Tick>) -> Pool {
+        Pool {
+            sqrt_price: sqrt_price_at_tick(0).unwrap(),
+            liquidity: 1_000_000,
+            tick_current: 0,
+            ticks,
+            lower_tick: -1000,
+            upper_tick: 1000,
+        }
+    }
+
+    #[test]
+    fn test_sqrt_price_at_tick() {
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), dec!(1));
+        assert_eq!(sqrt_price_at_tick(2).unwrap().round_dp(8), dec!(1.0001));
+        assert_eq!(sqrt_price_at_tick(-2).unwrap().round_dp(8), dec!(0.99990001));
+    }
+
+    #[test]
+    fn test_swap_within_range() {
+        let pool = pool(vec![]);
+
+        // x * y = L^2 within a single range
+        let swap = pool.swap(dec!(1000), true, dec!(0)).unwrap();
+        assert_eq!(swap.amount_out.round_dp(4), dec!(999.0010));
+        assert_eq!(swap.ticks_crossed, 0);
+        assert!(swap.price() < dec!(1));
+
+        let swap = pool.swap(dec!(1000), false, dec!(0)).unwrap();
+        assert_eq!(swap.amount_out.round_dp(4), dec!(999.0010));
+    }
+
+    #[test]
+    fn test_swap_fee() {
+        let pool = pool(vec![]);
+
+        let swap = pool.swap(dec!(1000), true, dec!(0.01)).unwrap();
+        assert_eq!(swap.amount_in, dec!(1000));
+        assert_eq!(swap.amount_out.round_dp(4), dec!(989.0209));
+    }
+
+    #[test]
+    fn test_swap_crossing_ticks() {
+        let shallow = pool(vec![]);
+        // liquidity halves below tick -10 and doubles above tick 10
+        let deep = pool(
+            vec![Tick { index: -10, liquidity_net: 500_000 }, Tick { index: 10, liquidity_net: 1_000_000 }]
+        );
+
+        let swap = deep.swap(dec!(5000), true, dec!(0)).unwrap();
+        assert_eq!(swap.ticks_crossed, 1);
+        assert!(swap.amount_out < shallow.swap(dec!(5000), true, dec!(0)).unwrap().amount_out);
+
+        let swap = deep.swap(dec!(5000), false, dec!(0)).unwrap();
+        assert_eq!(swap.ticks_crossed, 1);
+        assert!(swap.amount_out > shallow.swap(dec!(5000), false, dec!(0)).unwrap().amount_out);
+    }
+
+    #[test]
+    fn test_swap_beyond_known_ticks() {
+        let pool = Pool { lower_tick: -10, upper_tick: 10, ..pool(vec![]) };
+
+        assert!(pool.swap(dec!(100), true, dec!(0)).is_some());
+        assert!(pool.swap(dec!(5000), true, dec!(0)).is_none());
+        assert!(pool.swap(dec!(5000), false, dec!(0)).is_none());
+    }
+
+    #[test]
+    fn test_swap_no_liquidity() {
+        let pool = pool(vec![Tick { index: -10, liquidity_net: 1_000_000 }]);
+
+        assert!(pool.swap(dec!(5000), true, dec!(0)).is_none());
+    }
+
+    #[test]
+    fn test_swap_liquidity_out_of_range() {
+        // beyond what a decimal holds, rather than panicking
+        let deep = Pool { liquidity: u128::MAX, ..pool(vec![]) };
+        assert!(deep.swap(dec!(5000), true, dec!(0)).is_none());
+
+        let crossed = pool(vec![Tick { index: -10, liquidity_net: i128::MIN }]);
+        assert!(crossed.swap(dec!(5000), true, dec!(0)).is_none());
+    }
+}
@@ -0,0 +1,131 @@
+use std::{ collections::BTreeMap, env, fmt, str::FromStr };
+
+#[derive(Debug, Clone)]
+pub enum ConfigError {
+    Missing(&'static str),
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Missing(name) => write!(f, "missing configuration {name}"),
+            ConfigError::Invalid(name, reason) => write!(f, "invalid configuration {name}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Reads a comma separated list from the environment, `None` when the variable is not set.
+pub fn env_list(name: &'static str) -> Result<Option<Vec<String>>, ConfigError> {
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+
+    parse_list(name, &value).map(|list| Some(list.into_iter().map(String::from).collect()))
+}
+
+// the items of the variable `name`, at least one
+fn parse_list<'a>(name: &'static str, value: &'a str) -> Result<Vec<&'a str>, ConfigError> {
+    let list = value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect::<Vec<_>>();
+
+    if list.is_empty() {
+        return Err(ConfigError::Invalid(name, "empty list".to_string()));
+    }
+
+    Ok(list)
+}
+
+/// Parses a variable from the environment, `None` when the variable is not set.
+pub fn env_parse<T>(name: &'static str) -> Result<Option<T>, ConfigError> where T: FromStr, T::Err: fmt::Display {
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+
+    value
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|err: T::Err| ConfigError::Invalid(name, err.to_string()))
+}
+
+/// Reads `<asset>=<value>` items of a comma separated list, assets uppercased, `None` when
+/// the variable is not set.
+pub fn env_assets<T>(name: &'static str) -> Result<Option<BTreeMap<String, T>>, ConfigError>
+    where T: FromStr
+{
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+
+    parse_assets(name, &value).map(Some)
+}
+
+/// Parses the `<asset>=<value>` items of the variable `name` set to `value`.
+pub fn parse_assets<T>(name: &'static str, value: &str) -> Result<BTreeMap<String, T>, ConfigError>
+    where T: FromStr
+{
+    parse_list(name, value)?
+        .iter()
+        .map(|item| {
+            let invalid = || ConfigError::Invalid(name, format!("invalid item {item}"));
+
+            let (asset, value) = item.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim().parse::<T>().map_err(|_| invalid())?;
+
+            Ok((asset.trim().to_uppercase(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_list() {
+        env::set_var("ARBITRAGE_TEST_ENV_LIST", "wss://a, wss://b,,");
+        assert_eq!(
+            env_list("ARBITRAGE_TEST_ENV_LIST").unwrap(),
+            Some(vec!["wss://a".to_string(), "wss://b".to_string()])
+        );
+
+        env::set_var("ARBITRAGE_TEST_ENV_LIST", " , ");
+        assert!(env_list("ARBITRAGE_TEST_ENV_LIST").is_err());
+
+        env::remove_var("ARBITRAGE_TEST_ENV_LIST");
+        assert_eq!(env_list("ARBITRAGE_TEST_ENV_LIST").unwrap(), None);
+    }
+
+    #[test]
+    fn test_env_parse() {
+        env::set_var("ARBITRAGE_TEST_ENV_PARSE", " 3 ");
+        assert_eq!(env_parse::<usize>("ARBITRAGE_TEST_ENV_PARSE").unwrap(), Some(3));
+
+        env::set_var("ARBITRAGE_TEST_ENV_PARSE", "three");
+        assert!(env_parse::<usize>("ARBITRAGE_TEST_ENV_PARSE").is_err());
+
+        env::remove_var("ARBITRAGE_TEST_ENV_PARSE");
+        assert_eq!(env_parse::<usize>("ARBITRAGE_TEST_ENV_PARSE").unwrap(), None);
+    }
+
+    #[test]
+    fn test_env_assets() {
+        env::set_var("ARBITRAGE_TEST_ENV_ASSETS", "usdt=10000, SOL = 50");
+        assert_eq!(
+            env_assets::<u32>("ARBITRAGE_TEST_ENV_ASSETS").unwrap(),
+            Some(BTreeMap::from([("SOL".to_string(), 50), ("USDT".to_string(), 10000)]))
+        );
+
+        env::set_var("ARBITRAGE_TEST_ENV_ASSETS", "SOL:50");
+        assert!(env_assets::<u32>("ARBITRAGE_TEST_ENV_ASSETS").is_err());
+
+        env::remove_var("ARBITRAGE_TEST_ENV_ASSETS");
+        assert_eq!(env_assets::<u32>("ARBITRAGE_TEST_ENV_ASSETS").unwrap(), None);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{ collections::{ BTreeMap, BTreeSet, VecDeque }, fmt };
+
+use rust_decimal::Decimal;
+
+use crate::{ Instrument, Venue };
+
+/// How a venue price was expressed in the common quote, e.g. SOL/USDT in USD through the
+/// USDT/USD rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    // as quoted by the venue
+    pub native: Instrument,
+    // common quote per native quote
+    pub rate: Decimal,
+    // from the native quote to the common one
+    pub assets: Vec<String>,
+    // of the rates, one per step
+    pub venues: Vec<Venue>,
+}
+
+impl Conversion {
+    /// Instrument in the common quote.
+    pub fn instrument(&self) -> Instrument {
+        let quote = self.assets.last().unwrap_or(&self.native.quote);
+        Instrument { base: self.native.base.clone(), quote: quote.clone() }
+    }
+
+    /// Price in the native quote, rounded for an order.
+    pub fn to_native(&self, price: Decimal) -> Decimal {
+        (price / self.rate).round_dp(8)
+    }
+
+    pub fn to_common(&self, price: Decimal) -> Decimal {
+        price * self.rate
+    }
+}
+
+impl fmt::Display for Conversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let venues = self.venues.iter().map(Venue::to_string).collect::<Vec<_>>();
+        let rate = self.rate.round_dp(6).normalize();
+        write!(f, "{} {rate} {}", self.assets.join("→"), venues.join(" "))
+    }
+}
+
+/// Venue named with the conversion of its price, if any.
+pub fn label(venue: &Venue, conversion: Option<&Conversion>) -> String {
+    match conversion {
+        Some(conversion) => format!("{venue} ({conversion})"),
+        None => venue.to_string(),
+    }
+}
+
+/// Live rates between quote currencies, e.g. USDT/USD, to express every price in a common
+/// quote.
+pub struct CrossRates {
+    quote: String,
+    rates: BTreeMap<Instrument, (Decimal, Venue)>,
+}
+
+impl CrossRates {
+    pub fn new(quote: String) -> Self {
+        Self { quote, rates: BTreeMap::new() }
+    }
+
+    pub fn update(&mut self, instrument: Instrument, venue: Venue, price: Decimal) {
+        if price.is_zero() {
+            return;
+        }
+        self.rates.insert(instrument, (price, venue));
+    }
+
+    /// Converts the instrument to the common quote through the fewest rates, `None` when it
+    /// is quoted in it already. Errors when no rates lead to the common quote.
+    pub fn conversion(&self, instrument: &Instrument) -> Result<Option<Conversion>, String> {
+        if instrument.quote == self.quote {
+            return Ok(None);
+        }
+
+        let start = Conversion {
+            native: instrument.clone(),
+            rate: Decimal::ONE,
+            assets: vec![instrument.quote.clone()],
+            venues: vec![],
+        };
+
+        let mut visited = BTreeSet::from([instrument.quote.clone()]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(conversion) = queue.pop_front() {
+            let asset = conversion.assets.last().unwrap();
+            if *asset == self.quote {
+                return Ok(Some(conversion));
+            }
+
+            // both ways, a rate of base in quote also being the inverse of quote in base
+            for (rate_instrument, (price, venue)) in &self.rates {
+                let (next, rate) = if rate_instrument.base == *asset {
+                    (&rate_instrument.quote, *price)
+                } else if rate_instrument.quote == *asset {
+                    (&rate_instrument.base, Decimal::ONE / price)
+                } else {
+                    continue;
+                };
+
+                if !visited.insert(next.clone()) {
+                    continue;
+                }
+
+                let mut conversion = conversion.clone();
+                conversion.rate *= rate;
+                conversion.assets.push(next.clone());
+                conversion.venues.push(venue.clone());
+                queue.push_back(conversion);
+            }
+        }
+
+        Err(format!("no rate from {} to {}", instrument.quote, self.quote))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn kraken(market: &str) -> Venue {
+        Venue { exchange_id: "kraken", market: market.to_string() }
+    }
+
+    fn rates(quote: &str) -> CrossRates {
+        let mut rates = CrossRates::new(quote.to_string());
+        rates.update("USDT/USD".parse().unwrap(), kraken("USDT/USD"), dec!(1.0002));
+        rates.update("USDC/USD".parse().unwrap(), kraken("USDC/USD"), dec!(0.9999));
+        rates
+    }
+
+    #[test]
+    fn test_conversion() {
+        let rates = rates("USD");
+
+        assert_eq!(rates.conversion(&"SOL/USD".parse().unwrap()), Ok(None));
+
+        let conversion = rates.conversion(&"SOL/USDT".parse().unwrap()).unwrap().unwrap();
+        assert_eq!(conversion.rate, dec!(1.0002));
+        assert_eq!(conversion.instrument(), "SOL/USD".parse().unwrap());
+        assert_eq!(conversion.to_common(dec!(100)), dec!(100.02));
+        assert_eq!(conversion.to_native(dec!(100.02)), dec!(100));
+        assert_eq!(conversion.to_string(), "USDT→USD 1.0002 kraken:USDT/USD");
+
+        assert!(rates.conversion(&"SOL/EUR".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_conversion_through_rates() {
+        let rates = rates("USDT");
+
+        // USDC to USD, then USD to USDT with the inverse of USDT/USD
+        let conversion = rates.conversion(&"SOL/USDC".parse().unwrap()).unwrap().unwrap();
+        assert_eq!(conversion.assets, ["USDC", "USD", "USDT"]);
+        assert_eq!(conversion.rate, dec!(0.9999) / dec!(1.0002));
+        assert_eq!(
+            label(&kraken("SOL/USDC"), Some(&conversion)),
+            "kraken:SOL/USDC (USDC→USD→USDT 0.9997 kraken:USDC/USD kraken:USDT/USD)"
+        );
+    }
+}
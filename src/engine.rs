@@ -3,13 +3,18 @@ use core::hash::Hash;
 
 use rust_decimal::Decimal;
 
-#[derive(Default)]
 pub struct Engine<I, P = Decimal> {
     ids: HashMap<I, P>, // O(1)
     prices: BTreeMap<P, HashSet<I>>, // O(log(n) + 1) - ordered
     // space O(2n)
 }
 
+impl<I, P> Default for Engine<I, P> {
+    fn default() -> Self {
+        Self { ids: HashMap::new(), prices: BTreeMap::new() }
+    }
+}
+
 impl<I, P> Engine<I, P> where I: Hash + Ord + Clone, P: Ord + Clone {
     pub fn update(&mut self, exchange_id: I, price: P) {
         if
@@ -28,15 +33,34 @@ impl<I, P> Engine<I, P> where I: Hash + Ord + Clone, P: Ord + Clone {
             }
         }
 
-        let set = self.prices.entry(price).or_insert(HashSet::new()); // O(2log(n))
+        let set = self.prices.entry(price).or_default(); // O(2log(n))
         set.insert(exchange_id); // O(1)
     }
 
-    pub fn lowest_price(&mut self) -> Option<(&P, impl Iterator<Item = &I>)> {
+    pub fn remove(&mut self, id: &I) -> Option<P> {
+        let price = self.ids.remove(id)?; // O(1)
+
+        if
+            let Some(set) = self.prices.get_mut(&price) // O(log(n))
+        {
+            set.remove(id); // O(1)
+            if set.is_empty() {
+                self.prices.remove(&price); // O(log(n))
+            }
+        }
+
+        Some(price)
+    }
+
+    pub fn price(&self, id: &I) -> Option<&P> {
+        self.ids.get(id)
+    }
+
+    pub fn lowest_price(&self) -> Option<(&P, impl Iterator<Item = &I>)> {
         self.prices.first_key_value().map(|v| (v.0, v.1.iter()))
     }
 
-    pub fn highest_price(&mut self) -> Option<(&P, impl Iterator<Item = &I>)> {
+    pub fn highest_price(&self) -> Option<(&P, impl Iterator<Item = &I>)> {
         self.prices.last_key_value().map(|v| (v.0, v.1.iter()))
     }
 
@@ -44,11 +68,67 @@ impl<I, P> Engine<I, P> where I: Hash + Ord + Clone, P: Ord + Clone {
         self.prices.iter().map(|v| (v.0, v.1.iter()))
     }
 
+    // distinct prices, venues quoting alike sharing one
     pub fn len(&self) -> usize {
         self.prices.len()
     }
 }
 
+impl<I> Engine<I> where I: Hash + Ord + Clone {
+    /// Middle of the venue prices, which one venue off the market cannot move far.
+    pub fn median_price(&self) -> Option<Decimal> {
+        let prices = self.prices
+            .iter()
+            .flat_map(|(price, ids)| std::iter::repeat_n(*price, ids.len()))
+            .collect::<Vec<_>>();
+
+        let middle = prices.len() / 2;
+        match prices.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(prices[middle]),
+            _ => Some((prices[middle - 1] + prices[middle]) / Decimal::TWO),
+        }
+    }
+}
+
+/// Keeps the last sequence number (e.g. solana slot) seen per key, rejecting
+/// out-of-order and duplicate updates.
+pub struct Sequencer<K> {
+    sequences: HashMap<K, u64>,
+}
+
+impl<K> Default for Sequencer<K> {
+    fn default() -> Self {
+        Self { sequences: HashMap::new() }
+    }
+}
+
+impl<K> Sequencer<K> where K: Hash + Eq {
+    /// Returns `false` when `sequence` is not newer than the last one accepted for `key`.
+    pub fn advance(&mut self, key: K, sequence: u64) -> bool {
+        self.accept(key, sequence, false)
+    }
+
+    /// Like `advance` but also accepts the last sequence again, e.g. several swaps in a block.
+    pub fn advance_or_repeat(&mut self, key: K, sequence: u64) -> bool {
+        self.accept(key, sequence, true)
+    }
+
+    fn accept(&mut self, key: K, sequence: u64, repeat: bool) -> bool {
+        match self.sequences.get_mut(&key) {
+            Some(last) if *last > sequence || (*last == sequence && !repeat) => false,
+            Some(last) => {
+                *last = sequence;
+                true
+            }
+            None => {
+                self.sequences.insert(key, sequence);
+                true
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rust_decimal_macros::dec;
@@ -90,6 +170,23 @@ mod tests {
         assert_eq!(3, map.len());
     }
 
+    #[test]
+    fn remove() {
+        let mut map = Engine::<String>::default();
+
+        map.update("a".into(), dec!(1));
+        map.update("b".into(), dec!(2));
+        map.update("c".into(), dec!(2));
+
+        assert_eq!(Some(dec!(1)), map.remove(&"a".into()));
+        assert_eq!(Some(dec!(2)), map.remove(&"b".into()));
+        assert_eq!(None, map.remove(&"b".into()));
+
+        assert_eq!(1, map.len());
+        assert_eq!(None, map.price(&"a".into()));
+        assert_eq!(vec!["c"], map.lowest_price().unwrap().1.collect::<Vec<_>>());
+    }
+
     #[test]
     fn iter() {
         let mut map = Engine::<String>::default();
@@ -112,6 +209,17 @@ mod tests {
         assert_eq!(vec!["b", "c"], keys);
     }
 
+    #[test]
+    fn price() {
+        let mut map = Engine::<String>::default();
+
+        map.update("a".into(), dec!(1));
+        map.update("a".into(), dec!(2));
+
+        assert_eq!(Some(&dec!(2)), map.price(&"a".into()));
+        assert_eq!(None, map.price(&"b".into()));
+    }
+
     #[test]
     fn lowest_price() {
         let mut map = Engine::<String>::default();
@@ -135,4 +243,55 @@ mod tests {
 
         assert_eq!(dec!(5), *map.highest_price().unwrap().0);
     }
+
+    #[test]
+    fn median_price() {
+        let mut map = Engine::<String>::default();
+        assert_eq!(None, map.median_price());
+
+        map.update("a".into(), dec!(1));
+        map.update("b".into(), dec!(2));
+        map.update("c".into(), dec!(2));
+        map.update("d".into(), dec!(9));
+
+        assert_eq!(Some(dec!(2)), map.median_price());
+
+        map.update("d".into(), dec!(3));
+        map.update("e".into(), dec!(4));
+
+        assert_eq!(Some(dec!(2)), map.median_price());
+
+        map.update("a".into(), dec!(3));
+
+        assert_eq!(Some(dec!(3)), map.median_price());
+    }
+
+    #[test]
+    fn sequencer_advance() {
+        let mut sequencer = Sequencer::<&str>::default();
+
+        assert!(sequencer.advance("a", 10));
+        assert!(sequencer.advance("a", 11));
+        assert!(sequencer.advance("b", 5));
+    }
+
+    #[test]
+    fn sequencer_rejects_stale() {
+        let mut sequencer = Sequencer::<&str>::default();
+
+        assert!(sequencer.advance("a", 10));
+        assert!(!sequencer.advance("a", 10));
+        assert!(!sequencer.advance("a", 9));
+        assert!(sequencer.advance("a", 12));
+    }
+
+    #[test]
+    fn sequencer_advance_or_repeat() {
+        let mut sequencer = Sequencer::<&str>::default();
+
+        assert!(sequencer.advance_or_repeat("a", 10));
+        assert!(sequencer.advance_or_repeat("a", 10));
+        assert!(!sequencer.advance_or_repeat("a", 9));
+        assert!(sequencer.advance_or_repeat("a", 11));
+    }
 }
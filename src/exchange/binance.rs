@@ -1,41 +1,143 @@
+use std::{ collections::HashMap, time::{ Duration, UNIX_EPOCH } };
+
 use rust_decimal_macros::dec;
 use serde::Deserialize;
 
 use rust_decimal::Decimal;
 use serde_json::json;
 
-use crate::{ websocket::ExchangeWebSocketConfig, MarketPrice };
+use crate::{ config::ConfigError, websocket::ExchangeWebSocketConfig, Instrument, MarketPrice };
 
 pub struct Binance;
 
 impl ExchangeWebSocketConfig for Binance {
     const EXCHANGE_ID: &'static str = "binance";
+    // streams per connection, two per market, and incoming messages per second pings and
+    // pongs included
+    const MAX_MARKETS_PER_CONNECTION: usize = 512;
+    const MAX_MESSAGES_PER_SECOND: u32 = 5;
+
+    type Session = BinanceSession;
 
-    fn url() -> String {
-        "wss://stream.binance.com:9443/ws".to_string()
+    fn urls() -> Result<Vec<String>, ConfigError> {
+        Ok(vec!["wss://stream.binance.com:9443/ws".to_string()])
     }
 
-    fn get_subscribe_payload(markets: &[&str]) -> String {
-        json!({"id": 1, "method": "SUBSCRIBE", "params": markets
-                .as_ref()
-                .iter()
-                .map(|market| format!("{market}@bookTicker"))
-                .collect::<Vec<_>>()}).to_string()
+    // symbols as the tickers name them, streams being named in lowercase
+    fn normalize_market(market: &str) -> String {
+        market.to_uppercase()
     }
 
-    fn parse_incoming_payload(payload: String) -> Result<MarketPrice, std::io::Error> {
-        let tick = serde_json::from_str::<BinanceBookTicker>(&payload)?;
+    // the book ticker prices the market, the diff depth stream saying when the book changed
+    fn get_subscribe_payloads(_: &mut BinanceSession, markets: &[&str]) -> Vec<String> {
+        vec![json!({"id": 1, "method": "SUBSCRIBE", "params": streams(markets)}).to_string()]
+    }
 
-        Ok(MarketPrice {
-            exchange_id: Self::EXCHANGE_ID,
-            price: tick.price(),
-            market: tick.symbol,
-        })
+    fn get_unsubscribe_payloads(session: &mut BinanceSession, markets: &[&str]) -> Vec<String> {
+        for market in markets {
+            session.symbols.remove(&market.to_uppercase());
+        }
+
+        vec![json!({"id": 2, "method": "UNSUBSCRIBE", "params": streams(markets)}).to_string()]
+    }
+
+    // the price of every book ticker, and again once the depth update covering it says when
+    // it changed
+    fn parse_incoming_payload(
+        session: &mut BinanceSession,
+        payload: String
+    ) -> Result<Vec<MarketPrice>, std::io::Error> {
+        match serde_json::from_str::<BinanceMessage>(&payload)? {
+            BinanceMessage::Depth(update) => {
+                let symbol = session.symbols.entry(update.symbol).or_default();
+                symbol.depth = Some((update.last_update_id, update.event_time));
+
+                let tick = symbol.untimed.take_if(|tick| tick.update_id <= update.last_update_id);
+                match tick {
+                    Some(tick) => Ok(vec![tick.market_price(Some(update.event_time))?]),
+                    None => Ok(vec![]),
+                }
+            }
+            BinanceMessage::Ticker(tick) => {
+                let symbol = session.symbols.entry(tick.symbol.clone()).or_default();
+
+                // the depth update covering the change may be in already
+                let event_time = symbol.depth
+                    .filter(|(last_update_id, _)| *last_update_id >= tick.update_id)
+                    .map(|(_, event_time)| event_time);
+                symbol.untimed = event_time.is_none().then(|| tick.clone());
+
+                Ok(vec![tick.market_price(event_time)?])
+            }
+            BinanceMessage::Reply { id, result } => {
+                log::debug!("{} request {id}: {result}", Self::EXCHANGE_ID);
+                Ok(vec![])
+            }
+        }
     }
 }
 
+fn streams(markets: &[&str]) -> Vec<String> {
+    markets
+        .iter()
+        .flat_map(|market| {
+            let market = market.to_lowercase();
+            [format!("{market}@bookTicker"), format!("{market}@depth@100ms")]
+        })
+        .collect()
+}
+
+#[derive(Default, Clone)]
+pub struct BinanceSession {
+    symbols: HashMap<String, BinanceSymbol>,
+}
+
+/// Book ticker of a symbol not yet timed, and the last depth update seen.
+///
+/// A depth update is sent once per 100 millis for the changes in between, so its event time
+/// bounds the time of the book tickers it covers from above, their age being overstated
+/// rather than understated.
+#[derive(Default, Clone)]
+struct BinanceSymbol {
+    untimed: Option<BinanceBookTicker>,
+    depth: Option<(u64, u64)>, // last update id, event time in millis
+}
+
+// binance symbols concatenate the assets, so the quote is told apart by its suffix
+const QUOTE_ASSETS: [&str; 7] = ["USDT", "USDC", "FDUSD", "BTC", "ETH", "BNB", "EUR"];
+
+pub fn instrument(symbol: &str) -> Result<Instrument, std::io::Error> {
+    let symbol = symbol.to_uppercase();
+
+    QUOTE_ASSETS.iter()
+        .find_map(|quote| {
+            let base = symbol.strip_suffix(quote)?;
+            (!base.is_empty()).then(|| Instrument { base: base.to_string(), quote: quote.to_string() })
+        })
+        .ok_or(
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown quote asset {symbol}")
+            )
+        )
+}
+
 #[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum BinanceMessage {
+    Depth(BinanceDepthUpdate),
+    Ticker(BinanceBookTicker),
+    // to the subscribe and unsubscribe requests
+    Reply {
+        id: u64,
+        result: serde_json::Value,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
 struct BinanceBookTicker {
+    #[serde(rename = "u")]
+    update_id: u64,
     #[serde(rename = "s")]
     symbol: String,
     #[serde(rename = "b")]
@@ -45,11 +147,31 @@ struct BinanceBookTicker {
 }
 
 impl BinanceBookTicker {
-    pub fn price(&self) -> Decimal {
-        (self.bid + self.ask) / dec!(2)
+    fn market_price(&self, event_time: Option<u64>) -> Result<MarketPrice, std::io::Error> {
+        Ok(MarketPrice {
+            exchange_id: Binance::EXCHANGE_ID,
+            price: (self.bid + self.ask) / dec!(2),
+            instrument: instrument(&self.symbol)?,
+            market: self.symbol.clone(),
+            bid: Some(self.bid),
+            ask: Some(self.ask),
+            event_time: event_time.map(|event_time| UNIX_EPOCH + Duration::from_millis(event_time)),
+            ..Default::default()
+        })
     }
 }
 
+#[derive(Deserialize, Debug)]
+struct BinanceDepthUpdate {
+    // in millis
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "u")]
+    last_update_id: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use crate::websocket::run_websocket;
@@ -62,50 +184,103 @@ mod tests {
     async fn test_run() {
         env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
-        let (tx, _rx) = tokio::sync::watch::channel(MarketPrice::default());
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
 
-        run_websocket::<Binance>(tx, &["btcusdt"]).await;
+        run_websocket::<Binance>(tx, &["btcusdt"], control_rx).await;
     }
 
     #[test]
     fn test_get_subscribe_payload() {
-        let payload = Binance::get_subscribe_payload(&["btcusdt", "ethusdt"]);
+        let payload = Binance::get_subscribe_payloads(
+            &mut BinanceSession::default(),
+            &["btcusdt", "ethusdt"]
+        );
+        assert_eq!(
+            payload,
+            vec![
+                json!({"id": 1, "method": "SUBSCRIBE", "params": ["btcusdt@bookTicker", "btcusdt@depth@100ms", "ethusdt@bookTicker", "ethusdt@depth@100ms"]}).to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_unsubscribe_payload() {
+        let mut session = BinanceSession::default();
+        Binance::parse_incoming_payload(&mut session, book_ticker(400900217)).unwrap();
+
+        let payload = Binance::get_unsubscribe_payloads(&mut session, &["BTCUSDT", "BNBUSDT"]);
         assert_eq!(
             payload,
-            json!({"id": 1, "method": "SUBSCRIBE", "params": ["btcusdt@bookTicker", "ethusdt@bookTicker"]}).to_string()
+            vec![
+                json!({"id": 2, "method": "UNSUBSCRIBE", "params": ["btcusdt@bookTicker", "btcusdt@depth@100ms", "bnbusdt@bookTicker", "bnbusdt@depth@100ms"]}).to_string()
+            ]
         );
+        assert!(session.symbols.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_market() {
+        assert_eq!(Binance::normalize_market("solusdt"), "SOLUSDT");
+    }
+
+    fn book_ticker(update_id: u64) -> String {
+        json!({"u": update_id, "s": "BNBUSDT", "b": "25.35190000", "B": "31.21000000", "a": "25.36520000", "A": "40.66000000"}).to_string()
+    }
+
+    fn depth_update(first_update_id: u64, last_update_id: u64) -> String {
+        json!({"e": "depthUpdate", "E": 1672515782136_u64, "s": "BNBUSDT", "U": first_update_id, "u": last_update_id, "b": [["25.35190000", "31.21000000"]], "a": []}).to_string()
     }
 
     #[test]
     fn test_parse_incoming_payload() {
-        let payload =
-            r#"{
-                "e": "24hrTicker",  
-                "E": 1672515782136, 
-                "s": "BNBBTC",      
-                "p": "0.0015",      
-                "P": "250.00",      
-                "w": "0.0018",      
-                "x": "0.0009",      
-                "c": "0.0025",      
-                "Q": "10",          
-                "b": "0.0024",      
-                "B": "10",          
-                "a": "0.0026",      
-                "A": "100",         
-                "o": "0.0010",      
-                "h": "0.0025",      
-                "l": "0.0010",      
-                "v": "10000",       
-                "q": "18",          
-                "O": 0,             
-                "C": 86400000,      
-                "F": 0,             
-                "L": 18150,         
-                "n": 18151          
-            }"#;
-        let market_price = Binance::parse_incoming_payload(payload.to_string()).unwrap();
-        assert_eq!(market_price.market, "BNBBTC");
-        assert_eq!(market_price.price, dec!(0.0025));
+        let mut session = BinanceSession::default();
+        let event_time = Some(UNIX_EPOCH + Duration::from_millis(1_672_515_782_136));
+
+        // priced at once, not timed yet
+        let market_prices = Binance::parse_incoming_payload(&mut session, book_ticker(400900217))
+            .unwrap();
+        assert_eq!(market_prices.len(), 1);
+
+        let market_price = &market_prices[0];
+        assert_eq!(market_price.market, "BNBUSDT");
+        assert_eq!(market_price.instrument, "BNB/USDT".parse().unwrap());
+        assert_eq!(market_price.price, dec!(25.35855));
+        assert_eq!(market_price.bid, Some(dec!(25.3519)));
+        assert_eq!(market_price.ask, Some(dec!(25.3652)));
+        assert_eq!(market_price.event_time, None);
+
+        // a depth update before the change
+        let payload = depth_update(400900200, 400900210);
+        assert!(Binance::parse_incoming_payload(&mut session, payload).unwrap().is_empty());
+
+        // the one covering it times the price, once
+        let market_prices = Binance::parse_incoming_payload(
+            &mut session,
+            depth_update(400900211, 400900220)
+        ).unwrap();
+        assert_eq!(market_prices.len(), 1);
+        assert_eq!(market_prices[0].price, dec!(25.35855));
+        assert_eq!(market_prices[0].event_time, event_time);
+
+        let payload = depth_update(400900221, 400900230);
+        assert!(Binance::parse_incoming_payload(&mut session, payload).unwrap().is_empty());
+
+        // covered by the depth update already in
+        let market_prices = Binance::parse_incoming_payload(&mut session, book_ticker(400900225))
+            .unwrap();
+        assert_eq!(market_prices[0].event_time, event_time);
+
+        // subscribe acknowledgements carry no prices
+        let payload = json!({"result": null, "id": 1}).to_string();
+        assert!(Binance::parse_incoming_payload(&mut session, payload).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_instrument() {
+        assert_eq!(instrument("solusdt").unwrap(), "SOL/USDT".parse().unwrap());
+        assert_eq!(instrument("SOLFDUSD").unwrap(), "SOL/FDUSD".parse().unwrap());
+        assert!(instrument("USDT").is_err());
+        assert!(instrument("SOLXYZ").is_err());
     }
 }
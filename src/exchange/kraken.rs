@@ -1,44 +1,167 @@
+use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
 use rust_decimal_macros::dec;
 use serde::Deserialize;
 
 use rust_decimal::Decimal;
 use serde_json::json;
 
-use crate::{ websocket::ExchangeWebSocketConfig, MarketPrice };
+use crate::{ config::ConfigError, websocket::ExchangeWebSocketConfig, MarketPrice };
 
 pub struct Kraken;
 
 impl ExchangeWebSocketConfig for Kraken {
     const EXCHANGE_ID: &'static str = "kraken";
+    // symbols per subscribe request, and requests kept under the public rate limit
+    const MAX_MARKETS_PER_CONNECTION: usize = 100;
+    const MAX_MESSAGES_PER_SECOND: u32 = 10;
+
+    type Session = ();
+
+    fn urls() -> Result<Vec<String>, ConfigError> {
+        Ok(vec!["wss://ws.kraken.com/v2".to_string()])
+    }
 
-    fn url() -> String {
-        "wss://ws.kraken.com/v2".to_string()
+    fn normalize_market(market: &str) -> String {
+        market.to_uppercase()
     }
 
-    fn get_subscribe_payload(markets: &[&str]) -> String {
-        json!({"req_id": 1, "method": "subscribe", "params": {"channel": "ticker", "snapshot": false, "event_trigger": "bbo", "symbol": markets
+    fn get_subscribe_payloads(_: &mut (), markets: &[&str]) -> Vec<String> {
+        vec![json!({"req_id": 1, "method": "subscribe", "params": {"channel": "ticker", "snapshot": false, "event_trigger": "bbo", "symbol": markets
                 .as_ref()
                 .iter()
-                .collect::<Vec<_>>()}}).to_string()
+                .collect::<Vec<_>>()}}).to_string()]
     }
 
-    fn parse_incoming_payload(payload: String) -> Result<MarketPrice, std::io::Error> {
-        let envelope = serde_json::from_str::<KrakenBookEnvelope>(&payload)?;
-        let tick = envelope.data
-            .first()
-            .ok_or(std::io::Error::new(std::io::ErrorKind::InvalidData, "no book tick"))?;
+    fn get_unsubscribe_payloads(_: &mut (), markets: &[&str]) -> Vec<String> {
+        vec![json!({"req_id": 2, "method": "unsubscribe", "params": {"channel": "ticker", "event_trigger": "bbo", "symbol": markets}}).to_string()]
+    }
 
-        Ok(MarketPrice {
-            exchange_id: Self::EXCHANGE_ID,
-            price: tick.price(),
-            market: tick.symbol.clone(),
-        })
+    // a tick per symbol, several when they changed together
+    fn parse_incoming_payload(
+        _: &mut (),
+        payload: String
+    ) -> Result<Vec<MarketPrice>, std::io::Error> {
+        parse_ticks(Self::EXCHANGE_ID, &payload)
+    }
+}
+
+/// The kraken tickers of the cross rates between quote currencies, reported and measured
+/// apart from the traded markets.
+pub struct KrakenRates;
+
+impl ExchangeWebSocketConfig for KrakenRates {
+    const EXCHANGE_ID: &'static str = "kraken_rates";
+    const MAX_MARKETS_PER_CONNECTION: usize = Kraken::MAX_MARKETS_PER_CONNECTION;
+    const MAX_MESSAGES_PER_SECOND: u32 = Kraken::MAX_MESSAGES_PER_SECOND;
+
+    type Session = ();
+
+    fn urls() -> Result<Vec<String>, ConfigError> {
+        Kraken::urls()
+    }
+
+    fn normalize_market(market: &str) -> String {
+        Kraken::normalize_market(market)
     }
+
+    fn get_subscribe_payloads(session: &mut (), markets: &[&str]) -> Vec<String> {
+        Kraken::get_subscribe_payloads(session, markets)
+    }
+
+    fn get_unsubscribe_payloads(session: &mut (), markets: &[&str]) -> Vec<String> {
+        Kraken::get_unsubscribe_payloads(session, markets)
+    }
+
+    fn parse_incoming_payload(
+        _: &mut (),
+        payload: String
+    ) -> Result<Vec<MarketPrice>, std::io::Error> {
+        parse_ticks(Self::EXCHANGE_ID, &payload)
+    }
+}
+
+fn parse_ticks(
+    exchange_id: &'static str,
+    payload: &str
+) -> Result<Vec<MarketPrice>, std::io::Error> {
+    let ticks = match serde_json::from_str::<KrakenMessage>(payload)? {
+        KrakenMessage::Ticker { data } => data,
+        KrakenMessage::Channel { channel: KrakenChannel::Heartbeat | KrakenChannel::Status } => {
+            return Ok(vec![]);
+        }
+        KrakenMessage::Reply { method, success, error } => {
+            if !success {
+                log::warn!("{exchange_id} {method} failed: {}", error.unwrap_or_default());
+            }
+            return Ok(vec![]);
+        }
+    };
+
+    ticks
+        .into_iter()
+        .map(|tick| {
+            Ok(MarketPrice {
+                exchange_id,
+                price: tick.price(),
+                instrument: tick.symbol.parse()?,
+                event_time: tick.timestamp.as_deref().and_then(parse_timestamp),
+                market: tick.symbol,
+                bid: Some(tick.bid),
+                ask: Some(tick.ask),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Parses the rfc 3339 timestamps of kraken, in utc, e.g. `2024-09-22T10:33:05.709993Z`.
+pub fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+
+    // days since the epoch of the civil date
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146_097 + day_of_era - 719_468).ok()?;
+
+    let nanos = format!("{fraction:0<9}").get(..9)?.parse::<u32>().ok()?;
+    let seconds = days * 86_400 + hours * 3600 + minutes * 60 + seconds;
+
+    Some(UNIX_EPOCH + Duration::new(seconds, nanos))
 }
 
 #[derive(Deserialize, Debug)]
-struct KrakenBookEnvelope {
-    data: Vec<KrakenBookTicker>,
+#[serde(untagged)]
+enum KrakenMessage {
+    Ticker {
+        data: Vec<KrakenBookTicker>,
+    },
+    Channel {
+        channel: KrakenChannel,
+    },
+    // to the subscribe and unsubscribe requests
+    Reply {
+        method: String,
+        success: bool,
+        error: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum KrakenChannel {
+    Heartbeat,
+    Status,
 }
 
 #[derive(Deserialize, Debug)]
@@ -46,6 +169,8 @@ struct KrakenBookTicker {
     symbol: String,
     bid: Decimal,
     ask: Decimal,
+    // of the last book change
+    timestamp: Option<String>,
 }
 
 impl KrakenBookTicker {
@@ -66,17 +191,31 @@ mod tests {
     async fn test_run() {
         env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
-        let (tx, _rx) = tokio::sync::watch::channel(MarketPrice::default());
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
 
-        run_websocket::<Kraken>(tx, &["BTC/USDT"]).await;
+        run_websocket::<Kraken>(tx, &["BTC/USDT"], control_rx).await;
     }
 
     #[test]
     fn test_get_subscribe_payload() {
-        let payload = Kraken::get_subscribe_payload(&["BTC/USDT", "ETH/USDT"]);
+        let payload = Kraken::get_subscribe_payloads(&mut (), &["BTC/USDT", "ETH/USDT"]);
+        assert_eq!(
+            payload,
+            vec![
+                json!({"req_id": 1, "method": "subscribe", "params": {"channel": "ticker", "snapshot": false, "event_trigger": "bbo", "symbol": ["BTC/USDT", "ETH/USDT"]}}).to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_unsubscribe_payload() {
+        let payload = Kraken::get_unsubscribe_payloads(&mut (), &["BTC/USDT"]);
         assert_eq!(
             payload,
-            json!({"req_id": 1, "method": "subscribe", "params": {"channel": "ticker", "snapshot": false, "event_trigger": "bbo", "symbol": ["BTC/USDT", "ETH/USDT"]}}).to_string()
+            vec![
+                json!({"req_id": 2, "method": "unsubscribe", "params": {"channel": "ticker", "event_trigger": "bbo", "symbol": ["BTC/USDT"]}}).to_string()
+            ]
         );
     }
 
@@ -99,12 +238,67 @@ mod tests {
                         "low": 0.09979,
                         "high": 0.10285,
                         "change": -0.00017,
-                        "change_pct": -0.17
+                        "change_pct": -0.17,
+                        "timestamp": "2024-09-22T10:33:05.709993Z"
+                    },
+                    {
+                        "symbol": "SOL/USD",
+                        "bid": 140.5,
+                        "ask": 140.7
                     }
                 ]
             }"#;
-        let market_price = Kraken::parse_incoming_payload(payload.to_string()).unwrap();
+        let market_prices = Kraken::parse_incoming_payload(&mut (), payload.to_string()).unwrap();
+        assert_eq!(market_prices.len(), 2);
+
+        let market_price = &market_prices[0];
+        assert_eq!(market_price.exchange_id, "kraken");
         assert_eq!(market_price.market, "ALGO/USD");
+        assert_eq!(market_price.instrument, "ALGO/USD".parse().unwrap());
         assert_eq!(market_price.price, dec!(0.100305));
+        assert_eq!(
+            market_price.event_time,
+            Some(UNIX_EPOCH + Duration::from_micros(1_727_001_185_709_993))
+        );
+
+        // every symbol of the frame
+        assert_eq!(market_prices[1].market, "SOL/USD");
+        assert_eq!(market_prices[1].price, dec!(140.6));
+        assert_eq!(market_prices[1].event_time, None);
+
+        let empty = r#"{"channel": "ticker", "type": "update", "data": []}"#;
+        assert!(Kraken::parse_incoming_payload(&mut (), empty.to_string()).unwrap().is_empty());
+
+        // heartbeats, status frames and replies carry no prices
+        let frames = [
+            json!({"channel": "heartbeat"}),
+            json!({"channel": "status", "type": "update", "data": [{"api_version": "v2", "connection_id": 12393906104898154338u64, "system": "online", "version": "2.0.8"}]}),
+            json!({"method": "subscribe", "result": {"channel": "ticker", "event_trigger": "bbo", "snapshot": false, "symbol": "ALGO/USD"}, "success": true, "time_in": "2024-09-22T10:33:05.709993Z", "time_out": "2024-09-22T10:33:05.710012Z", "req_id": 1}),
+        ];
+        for frame in frames {
+            assert!(Kraken::parse_incoming_payload(&mut (), frame.to_string()).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_incoming_payload_rates() {
+        let payload = r#"{"channel": "ticker", "type": "update", "data": [{"symbol": "USDT/USD", "bid": 0.9998, "ask": 1.0002}]}"#;
+        let market_prices = KrakenRates::parse_incoming_payload(&mut (), payload.to_string());
+
+        // apart from the traded kraken markets
+        let market_price = &market_prices.unwrap()[0];
+        assert_eq!(market_price.exchange_id, "kraken_rates");
+        assert_eq!(market_price.price, dec!(1));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(UNIX_EPOCH));
+        assert_eq!(
+            parse_timestamp("2000-02-29T23:59:59.5Z"),
+            Some(UNIX_EPOCH + Duration::from_millis(951_868_799_500))
+        );
+        assert_eq!(parse_timestamp("2024-09-22 10:33:05Z"), None);
+        assert_eq!(parse_timestamp("2024-09-22T10:33:05+02:00"), None);
     }
 }
@@ -1,3 +1,4 @@
 pub mod binance;
-pub mod helius;
 pub mod kraken;
+pub mod solana;
+pub mod uniswap;
@@ -0,0 +1,1280 @@
+use borsh::BorshDeserialize;
+use rust_decimal::{ prelude::FromPrimitive, Decimal };
+use serde::{ Deserialize, Serialize };
+
+use base64::prelude::*;
+use serde_json::json;
+
+use crate::{
+    clmm::{ self, Tick },
+    config::{ env_list, env_parse, ConfigError },
+    websocket::ExchangeWebSocketConfig,
+    Instrument,
+    MarketPrice,
+    SizedQuote,
+};
+
+use std::{
+    collections::{ BTreeMap, HashMap, HashSet },
+    env,
+    fmt,
+    str::FromStr,
+    sync::{ Arc, Mutex },
+};
+
+pub struct Solana;
+
+impl ExchangeWebSocketConfig for Solana {
+    const EXCHANGE_ID: &'static str = "solana";
+    // up to several subscriptions a market, and helius requests per second on basic plans
+    const MAX_MARKETS_PER_CONNECTION: usize = 50;
+    const MAX_MESSAGES_PER_SECOND: u32 = 10;
+
+    type Session = SolanaSession;
+
+    // any solana rpc websocket endpoints (helius, triton, quicknode, solana-test-validator)
+    // in failover order, falling back to helius when only its api key is configured
+    fn urls() -> Result<Vec<String>, ConfigError> {
+        if let Some(urls) = env_list("SOLANA_WS_URLS")? {
+            return Ok(urls);
+        }
+
+        let api_key = env
+            ::var("HELIUS_API_KEY")
+            .map_err(|_| ConfigError::Missing("SOLANA_WS_URLS or HELIUS_API_KEY"))?;
+
+        Ok(vec![format!("wss://mainnet.helius-rpc.com/?api-key={api_key}")])
+    }
+
+    // pairs uppercased and the default commitment left out, pool addresses being base58
+    fn normalize_market(market: &str) -> String {
+        market
+            .parse::<SolanaMarket>()
+            .map_or(market.to_string(), |market| market.to_string())
+    }
+
+    fn session() -> Result<SolanaSession, ConfigError> {
+        Ok(SolanaSession { quote: SolanaQuote::from_env()?, ..Default::default() })
+    }
+
+    // accountSubscribe takes a single pubkey, so every pool gets its own request, while
+    // pairs are discovered with a programSubscribe per dex and mint order
+    fn get_subscribe_payloads(session: &mut SolanaSession, markets: &[&str]) -> Vec<String> {
+        let mut payloads = vec![];
+
+        for market in markets {
+            let subscriptions = match market.parse::<SolanaMarket>() {
+                Ok(SolanaMarket::Pool(pool)) => {
+                    // tick arrays are only needed to quote trade sizes
+                    if let Some(quote) = &session.quote {
+                        let (rpc_url, seeded) = (quote.rpc_url.clone(), session.seeded.clone());
+                        tokio::spawn(seed_tick_arrays(rpc_url, pool.clone(), seeded));
+                        vec![
+                            SolanaSubscription::TickArrays(pool.clone()),
+                            SolanaSubscription::Account(pool)
+                        ]
+                    } else {
+                        vec![SolanaSubscription::Account(pool)]
+                    }
+                }
+                Ok(SolanaMarket::Pair(pair)) => {
+                    let mut subscriptions = vec![];
+                    for dex in [Dex::Raydium, Dex::Orca] {
+                        for inverted in [false, true] {
+                            subscriptions.push(SolanaSubscription::Program {
+                                dex,
+                                pair: pair.clone(),
+                                inverted,
+                            });
+                        }
+                    }
+                    subscriptions
+                }
+                Err(err) => {
+                    log::error!("{} {err}", Self::EXCHANGE_ID);
+                    continue;
+                }
+            };
+
+            for subscription in subscriptions {
+                session.next_id += 1;
+
+                payloads.push(subscription.payload(session.next_id));
+                session.requests.insert(session.next_id, subscription);
+            }
+        }
+
+        payloads
+    }
+
+    // subscriptions are cancelled by the ids the server handed out, those still pending
+    // being forgotten
+    fn get_unsubscribe_payloads(session: &mut SolanaSession, markets: &[&str]) -> Vec<String> {
+        let mut payloads = vec![];
+
+        for market in markets {
+            let market = match market.parse::<SolanaMarket>() {
+                Ok(market) => market,
+                Err(err) => {
+                    log::error!("{} {err}", Self::EXCHANGE_ID);
+                    continue;
+                }
+            };
+
+            session.requests.retain(|_, subscription| !subscription.is_for(&market));
+
+            let ids = session.subscriptions
+                .iter()
+                .filter(|(_, subscription)| subscription.is_for(&market))
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+
+            for id in ids {
+                let subscription = session.subscriptions.remove(&id).unwrap();
+                session.next_id += 1;
+                payloads.push(subscription.unsubscribe_payload(session.next_id, id));
+            }
+
+            if let SolanaMarket::Pool(pool) = market {
+                session.tick_arrays.remove(&pool.address);
+                session.seeded.lock().unwrap().remove(&pool.address);
+            }
+        }
+
+        payloads
+    }
+
+    fn parse_incoming_payload(
+        session: &mut SolanaSession,
+        payload: String
+    ) -> Result<Vec<MarketPrice>, std::io::Error> {
+        let envelope = match serde_json::from_str::<SolanaMessage>(&payload)? {
+            SolanaMessage::Notification(envelope) => envelope,
+            SolanaMessage::Subscribed { id, result } => {
+                let subscription = session.requests
+                    .remove(&id)
+                    .ok_or(
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, "unknown request id")
+                    )?;
+                session.subscriptions.insert(result, subscription);
+
+                return Ok(vec![]);
+            }
+        };
+
+        let subscription = session.subscriptions
+            .get(&envelope.params.subscription)
+            .ok_or(std::io::Error::new(std::io::ErrorKind::InvalidData, "unknown subscription"))?;
+
+        let slot = envelope.params.result.context.slot;
+
+        let (market, subscription, instrument, price, commitment, quote) = match
+            (subscription, envelope.params.result.value)
+        {
+            (SolanaSubscription::Account(pool), SolanaValue::Account(account)) => {
+                let pool_state: PoolState = account.try_into()?;
+
+                // behind the tick arrays notified since
+                let seeded = session.seeded.lock().unwrap().remove(&pool.address);
+                if let Some(seeded) = seeded {
+                    let tick_arrays = session.tick_arrays.entry(pool.address.clone()).or_default();
+                    for (start_tick_index, ticks) in seeded {
+                        tick_arrays.entry(start_tick_index).or_insert(ticks);
+                    }
+                }
+
+                let quote = session.quote.as_ref().and_then(|quote| {
+                    let tick_arrays = session.tick_arrays.get(&pool.address)?;
+                    pool_state.quote(tick_arrays, quote.notional, quote.fee_rate)
+                });
+
+                (
+                    pool.address.clone(),
+                    SolanaMarket::Pool(pool.clone()).to_string(),
+                    pool_state.instrument(),
+                    pool_state.price()?,
+                    pool.commitment,
+                    quote,
+                )
+            }
+            (SolanaSubscription::TickArrays(pool), SolanaValue::Program(program)) => {
+                let tick_array: TickArrayState = program.account.try_into()?;
+
+                session.tick_arrays
+                    .entry(pool.address.clone())
+                    .or_default()
+                    .insert(tick_array.start_tick_index, tick_array.initialized_ticks());
+
+                return Ok(vec![]);
+            }
+            (
+                SolanaSubscription::Program { dex, pair, inverted },
+                SolanaValue::Program(SolanaProgramAccount { pubkey, account }),
+            ) => {
+                let (decimals_0, decimals_1) = if *inverted {
+                    (pair.quote.decimals, pair.base.decimals)
+                } else {
+                    (pair.base.decimals, pair.quote.decimals)
+                };
+
+                let price = match dex {
+                    Dex::Raydium => PoolState::try_from(account)?.price()?,
+                    Dex::Orca => WhirlpoolState::try_from(account)?.price(decimals_0, decimals_1)?,
+                };
+
+                if !session.discovered.contains(&pubkey) {
+                    log::info!(
+                        "{} discovered {dex:?} pool {pubkey} for {}",
+                        Self::EXCHANGE_ID,
+                        pair.instrument
+                    );
+                    session.discovered.insert(pubkey.clone());
+                }
+
+                let price = if *inverted {
+                    if price.is_zero() {
+                        return Err(
+                            std::io::Error::new(std::io::ErrorKind::InvalidData, "zero pool price")
+                        );
+                    }
+                    Decimal::ONE / price
+                } else {
+                    price
+                };
+
+                let subscription = SolanaMarket::Pair(pair.clone()).to_string();
+                (pubkey, subscription, pair.instrument.clone(), price, pair.commitment, None)
+            }
+            _ => {
+                return Err(
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected notification")
+                );
+            }
+        };
+
+        Ok(
+            vec![MarketPrice {
+                exchange_id: Self::EXCHANGE_ID,
+                price,
+                market,
+                subscription: Some(subscription),
+                instrument,
+                slot: Some(slot),
+                commitment: Some(commitment),
+                quote,
+                ..Default::default()
+            }]
+        )
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct SolanaSession {
+    next_id: u64,
+    requests: HashMap<u64, SolanaSubscription>, // request id -> subscription
+    subscriptions: HashMap<u64, SolanaSubscription>, // subscription id -> subscription
+    discovered: HashSet<String>, // discovered pool addresses
+    quote: Option<SolanaQuote>,
+    tick_arrays: HashMap<String, TickArrays>, // pool -> start tick -> ticks
+    // fetched when subscribing, programSubscribe only notifying the tick arrays that change
+    seeded: Arc<Mutex<HashMap<String, TickArrays>>>,
+}
+
+type TickArrays = BTreeMap<i32, Vec<Tick>>;
+
+/// Trade size the configured pools are quoted for, `SOLANA_QUOTE_NOTIONAL` in the quote
+/// token and an optional `SOLANA_QUOTE_FEE_RATE` of the pools, their tick arrays fetched
+/// from the rpc at `SOLANA_RPC_URL` or helius.
+///
+/// Only raydium clmm pools subscribed by address are sized; orca whirlpools and discovered
+/// pairs are priced at the pool price alone, their tick arrays not being followed.
+#[derive(Debug, Clone)]
+struct SolanaQuote {
+    notional: Decimal,
+    fee_rate: Decimal,
+    rpc_url: String,
+}
+
+impl SolanaQuote {
+    fn from_env() -> Result<Option<Self>, ConfigError> {
+        let Some(notional) = env_parse::<Decimal>("SOLANA_QUOTE_NOTIONAL")? else {
+            return Ok(None);
+        };
+        if notional <= Decimal::ZERO {
+            return Err(ConfigError::Invalid("SOLANA_QUOTE_NOTIONAL", "not positive".to_string()));
+        }
+
+        let fee_rate = env_parse("SOLANA_QUOTE_FEE_RATE")?.unwrap_or_default();
+
+        Ok(Some(SolanaQuote { notional, fee_rate, rpc_url: rpc_url()? }))
+    }
+}
+
+/// The http rpc at `SOLANA_RPC_URL`, or helius when only `HELIUS_API_KEY` is set.
+pub fn rpc_url() -> Result<String, ConfigError> {
+    match env_parse::<String>("SOLANA_RPC_URL")? {
+        Some(rpc_url) => Ok(rpc_url),
+        None =>
+            env_parse::<String>("HELIUS_API_KEY")?
+                .map(|api_key| format!("https://mainnet.helius-rpc.com/?api-key={api_key}"))
+                .ok_or(ConfigError::Missing("SOLANA_RPC_URL or HELIUS_API_KEY")),
+    }
+}
+
+// the tick arrays of the pool as they are, once
+async fn seed_tick_arrays(
+    rpc_url: String,
+    pool: SolanaPool,
+    seeded: Arc<Mutex<HashMap<String, TickArrays>>>
+) {
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": "getProgramAccounts", "params": [Dex::Raydium.program_id(), {"encoding": "base64", "commitment": pool.commitment, "filters": tick_array_filters(&pool)}]});
+
+    let response = reqwest::Client
+        ::new()
+        .post(&rpc_url)
+        .header("Content-Type", "application/json")
+        .body(request.to_string())
+        .send().await;
+    let body = match response {
+        Ok(response) => response.text().await,
+        Err(err) => Err(err),
+    };
+
+    let accounts = body
+        .map_err(|err| err.to_string())
+        .and_then(|body| {
+            serde_json
+                ::from_str::<SolanaRpcResponse>(&body)
+                .map_err(|err| err.to_string())
+        });
+    let accounts = match accounts {
+        Ok(response) => response.result,
+        Err(err) => {
+            let address = &pool.address;
+            log::warn!("{} tick arrays of {address} not fetched: {err}", Solana::EXCHANGE_ID);
+            return;
+        }
+    };
+
+    let mut tick_arrays = BTreeMap::new();
+    for account in accounts {
+        match TickArrayState::try_from(account.account) {
+            Ok(tick_array) => {
+                tick_arrays.insert(tick_array.start_tick_index, tick_array.initialized_ticks());
+            }
+            Err(err) => log::warn!("{} {} {err}", Solana::EXCHANGE_ID, account.pubkey),
+        }
+    }
+
+    let (count, address) = (tick_arrays.len(), &pool.address);
+    log::debug!("{} {count} tick arrays of {address}", Solana::EXCHANGE_ID);
+    seeded.lock().unwrap().insert(pool.address, tick_arrays);
+}
+
+fn tick_array_filters(pool: &SolanaPool) -> serde_json::Value {
+    json!([
+        {"dataSize": TICK_ARRAY_DATA_SIZE},
+        {"memcmp": {"offset": 8, "bytes": pool.address}}
+    ])
+}
+
+#[derive(Clone)]
+enum SolanaSubscription {
+    // a single raydium clmm pool
+    Account(SolanaPool),
+    // the tick arrays of a single raydium clmm pool
+    TickArrays(SolanaPool),
+    // every pool of a dex trading the pair, `inverted` when the pool mints are quote/base
+    Program {
+        dex: Dex,
+        pair: SolanaPair,
+        inverted: bool,
+    },
+}
+
+impl SolanaSubscription {
+    fn payload(&self, id: u64) -> String {
+        match self {
+            SolanaSubscription::Account(pool) =>
+                json!({"jsonrpc": "2.0", "id": id, "method": "accountSubscribe", "params": [pool.address, {"encoding": "base64", "commitment": pool.commitment}] }).to_string(),
+            SolanaSubscription::TickArrays(pool) =>
+                json!({"jsonrpc": "2.0", "id": id, "method": "programSubscribe", "params": [Dex::Raydium.program_id(), {"encoding": "base64", "commitment": pool.commitment, "filters": tick_array_filters(pool)}] }).to_string(),
+            SolanaSubscription::Program { dex, pair, inverted } => {
+                let (mint_0, mint_1) = if *inverted {
+                    (pair.quote.mint, pair.base.mint)
+                } else {
+                    (pair.base.mint, pair.quote.mint)
+                };
+                let (offset_0, offset_1) = dex.mint_offsets();
+
+                json!({"jsonrpc": "2.0", "id": id, "method": "programSubscribe", "params": [dex.program_id(), {"encoding": "base64", "commitment": pair.commitment, "filters": [
+                    {"dataSize": dex.data_size()},
+                    {"memcmp": {"offset": offset_0, "bytes": mint_0}},
+                    {"memcmp": {"offset": offset_1, "bytes": mint_1}}
+                ]}] }).to_string()
+            }
+        }
+    }
+
+    fn unsubscribe_payload(&self, id: u64, subscription: u64) -> String {
+        let method = match self {
+            SolanaSubscription::Account(_) => "accountUnsubscribe",
+            _ => "programUnsubscribe",
+        };
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": [subscription]}).to_string()
+    }
+
+    // whether subscribed to for the market
+    fn is_for(&self, market: &SolanaMarket) -> bool {
+        match (self, market) {
+            (
+                SolanaSubscription::Account(pool) | SolanaSubscription::TickArrays(pool),
+                SolanaMarket::Pool(market),
+            ) => pool.address == market.address && pool.commitment == market.commitment,
+            (SolanaSubscription::Program { pair, .. }, SolanaMarket::Pair(market)) => {
+                pair.instrument == market.instrument && pair.commitment == market.commitment
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Solana commitment level a pool is subscribed with.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Commitment {
+    Processed,
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Processed => write!(f, "processed"),
+            Self::Confirmed => write!(f, "confirmed"),
+            Self::Finalized => write!(f, "finalized"),
+        }
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "processed" => Ok(Self::Processed),
+            "confirmed" => Ok(Self::Confirmed),
+            "finalized" => Ok(Self::Finalized),
+            _ =>
+                Err(
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("invalid commitment {s}")
+                    )
+                ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dex {
+    Raydium,
+    Orca,
+}
+
+impl Dex {
+    pub fn program_id(&self) -> &'static str {
+        match self {
+            Dex::Raydium => "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK",
+            Dex::Orca => "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc",
+        }
+    }
+
+    fn data_size(&self) -> usize {
+        match self {
+            Dex::Raydium => 1544,
+            Dex::Orca => 653,
+        }
+    }
+
+    // offsets of the two mints in the pool account
+    fn mint_offsets(&self) -> (usize, usize) {
+        match self {
+            Dex::Raydium => (73, 105),
+            Dex::Orca => (101, 181),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token {
+    mint: &'static str,
+    decimals: u8,
+}
+
+const TOKENS: [(&str, Token); 3] = [
+    ("SOL", Token { mint: "So11111111111111111111111111111111111111112", decimals: 9 }),
+    ("USDC", Token { mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", decimals: 6 }),
+    ("USDT", Token { mint: "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB", decimals: 6 }),
+];
+
+// the symbol of known mints, the mint address otherwise
+pub fn symbol(mint: &[u8]) -> String {
+    let mint = bs58::encode(mint).into_string();
+
+    TOKENS.iter()
+        .find(|(_, token)| token.mint == mint)
+        .map(|(symbol, _)| symbol.to_string())
+        .unwrap_or(mint)
+}
+
+impl FromStr for Token {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TOKENS.iter()
+            .find(|(symbol, _)| *symbol == s)
+            .map(|(_, token)| *token)
+            .ok_or(
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("unknown token {s}"))
+            )
+    }
+}
+
+/// Pool market in the form `<address>` or `<address>@<commitment>`.
+#[derive(Debug, Clone)]
+struct SolanaPool {
+    address: String,
+    commitment: Commitment,
+}
+
+/// Pair market in the form `<base>/<quote>` or `<base>/<quote>@<commitment>`.
+#[derive(Debug, Clone)]
+struct SolanaPair {
+    instrument: Instrument,
+    base: Token,
+    quote: Token,
+    commitment: Commitment,
+}
+
+enum SolanaMarket {
+    Pool(SolanaPool),
+    Pair(SolanaPair),
+}
+
+impl FromStr for SolanaMarket {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (market, commitment) = match s.split_once('@') {
+            Some((market, commitment)) => (market, commitment.parse()?),
+            None => (s, Commitment::default()),
+        };
+
+        match market.split_once('/') {
+            Some((base, quote)) =>
+                Ok(
+                    SolanaMarket::Pair(SolanaPair {
+                        instrument: market.parse()?,
+                        base: base.to_uppercase().parse()?,
+                        quote: quote.to_uppercase().parse()?,
+                        commitment,
+                    })
+                ),
+            None => Ok(SolanaMarket::Pool(SolanaPool { address: market.to_string(), commitment })),
+        }
+    }
+}
+
+impl fmt::Display for SolanaMarket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let commitment = match self {
+            SolanaMarket::Pool(pool) => {
+                write!(f, "{}", pool.address)?;
+                pool.commitment
+            }
+            SolanaMarket::Pair(pair) => {
+                write!(f, "{}", pair.instrument)?;
+                pair.commitment
+            }
+        };
+
+        if commitment != Commitment::default() {
+            write!(f, "@{commitment}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum SolanaMessage {
+    Notification(SolanaEnvelope),
+    Subscribed {
+        id: u64,
+        result: u64,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct SolanaEnvelope {
+    params: SolanaParams,
+}
+
+#[derive(Deserialize, Debug)]
+struct SolanaParams {
+    subscription: u64,
+    result: SolanaResult,
+}
+
+#[derive(Deserialize, Debug)]
+struct SolanaResult {
+    context: SolanaContext,
+    value: SolanaValue,
+}
+
+#[derive(Deserialize, Debug)]
+struct SolanaContext {
+    slot: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum SolanaValue {
+    // programNotification
+    Program(SolanaProgramAccount),
+    // accountNotification
+    Account(SolanaAccount),
+}
+
+#[derive(Deserialize, Debug)]
+struct SolanaRpcResponse {
+    result: Vec<SolanaProgramAccount>,
+}
+
+// also listed by getProgramAccounts
+#[derive(Deserialize, Debug)]
+struct SolanaProgramAccount {
+    pubkey: String,
+    account: SolanaAccount,
+}
+
+#[derive(Deserialize, Debug)]
+struct SolanaAccount {
+    data: SolanaData,
+}
+
+#[derive(Deserialize, Debug)]
+struct SolanaData(Vec<String>);
+
+impl SolanaAccount {
+    fn decode<T: BorshDeserialize>(&self) -> Result<T, std::io::Error> {
+        let base64 = self.data.0
+            .first()
+            .ok_or(std::io::Error::new(std::io::ErrorKind::InvalidData, "no account data"))?;
+        let decoded = BASE64_STANDARD.decode(base64).map_err(|err|
+            std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+        )?;
+        T::try_from_slice(&decoded)
+    }
+}
+
+// price of token 0 in token 1 from a Q64.64 square root price, none beyond what a decimal
+// holds (2^96 and up)
+fn price_from_sqrt_price_x64(
+    sqrt_price_x64: u128,
+    decimals_0: u8,
+    decimals_1: u8
+) -> Option<Decimal> {
+    let sqrt_price = Decimal::from_u128(sqrt_price_x64)? / Decimal::from_u128((2_u128).pow(64))?;
+
+    let exponent = i32::from(decimals_0) - i32::from(decimals_1);
+    let scale = Decimal::from(10_u64.pow(exponent.unsigned_abs()));
+
+    let price = sqrt_price.checked_mul(sqrt_price)?;
+    if exponent >= 0 {
+        price.checked_mul(scale)
+    } else {
+        price.checked_div(scale)
+    }
+}
+
+fn price_out_of_range() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, "price out of range")
+}
+
+fn ten_pow(decimals: u8) -> Decimal {
+    Decimal::from(10_u64.pow(u32::from(decimals)))
+}
+
+/// Raydium CLMM pool account.
+#[repr(C)]
+#[derive(BorshDeserialize)]
+pub struct PoolState {
+    discriminator: [u8; 8],
+    bump: u8,
+    pub amm_config: [u8; 32],
+    owner: [u8; 32],
+    pub token_mint_0: [u8; 32],
+    pub token_mint_1: [u8; 32],
+    pub token_vault_0: [u8; 32],
+    pub token_vault_1: [u8; 32],
+    pub observation_key: [u8; 32],
+    pub mint_decimals_0: u8,
+    pub mint_decimals_1: u8,
+    pub tick_spacing: u16,
+    liquidity: u128,
+    sqrt_price_x64: u128,
+    pub tick_current: i32,
+    padding_after: [u8; 1271],
+}
+
+impl PoolState {
+    pub fn price(&self) -> Result<Decimal, std::io::Error> {
+        price_from_sqrt_price_x64(
+            self.sqrt_price_x64,
+            self.mint_decimals_0,
+            self.mint_decimals_1
+        ).ok_or_else(price_out_of_range)
+    }
+
+    // token 0 in token 1
+    pub fn instrument(&self) -> Instrument {
+        Instrument { base: symbol(&self.token_mint_0), quote: symbol(&self.token_mint_1) }
+    }
+
+    // the pool with the ticks of the contiguous tick arrays around the current tick
+    fn clmm_pool(&self, tick_arrays: &TickArrays) -> Option<clmm::Pool> {
+        let span = i32::from(self.tick_spacing) * TICK_ARRAY_SIZE;
+        if span == 0 {
+            return None;
+        }
+
+        let start = self.tick_current.div_euclid(span) * span;
+        if !tick_arrays.contains_key(&start) {
+            return None;
+        }
+
+        let mut lower_tick = start;
+        while tick_arrays.contains_key(&(lower_tick - span)) {
+            lower_tick -= span;
+        }
+
+        let mut upper_tick = start + span;
+        while tick_arrays.contains_key(&upper_tick) {
+            upper_tick += span;
+        }
+
+        Some(clmm::Pool {
+            sqrt_price: Decimal::from_u128(self.sqrt_price_x64)? /
+            Decimal::from_u128((2_u128).pow(64))?,
+            liquidity: self.liquidity,
+            tick_current: self.tick_current,
+            ticks: tick_arrays
+                .range(lower_tick..upper_tick)
+                .flat_map(|(_, ticks)| ticks.iter().copied())
+                .collect(),
+            lower_tick,
+            upper_tick,
+        })
+    }
+
+    /// Effective prices of token 0 in token 1 for selling and buying `notional` worth of
+    /// token 1, `None` when the trade goes beyond the known tick arrays.
+    pub fn quote(
+        &self,
+        tick_arrays: &TickArrays,
+        notional: Decimal,
+        fee_rate: Decimal
+    ) -> Option<SizedQuote> {
+        let pool = self.clmm_pool(tick_arrays)?;
+        let scale_0 = ten_pow(self.mint_decimals_0);
+        let scale_1 = ten_pow(self.mint_decimals_1);
+        // raw to token 0 in token 1 prices
+        let scale = scale_0 / scale_1;
+
+        let sell = pool.swap(notional.checked_div(self.price().ok()?)? * scale_0, true, fee_rate)?;
+        let buy = pool.swap(notional * scale_1, false, fee_rate)?;
+
+        Some(SizedQuote {
+            notional,
+            bid: sell.price() * scale,
+            ask: scale.checked_div(buy.price())?,
+        })
+    }
+}
+
+pub const TICK_ARRAY_SIZE: i32 = 60;
+const TICK_ARRAY_DATA_SIZE: usize = 10240;
+
+/// Raydium CLMM tick array account.
+#[repr(C)]
+#[derive(BorshDeserialize)]
+struct TickArrayState {
+    discriminator: [u8; 8],
+    pool_id: [u8; 32],
+    start_tick_index: i32,
+    ticks: [TickState; TICK_ARRAY_SIZE as usize],
+    padding: [u8; 116],
+}
+
+#[repr(C)]
+#[derive(BorshDeserialize, Clone, Copy)]
+struct TickState {
+    tick: i32,
+    liquidity_net: i128,
+    liquidity_gross: u128,
+    padding: [u8; 132],
+}
+
+impl TickArrayState {
+    fn initialized_ticks(&self) -> Vec<Tick> {
+        self.ticks
+            .iter()
+            .filter(|tick| tick.liquidity_gross != 0)
+            .map(|tick| Tick { index: tick.tick, liquidity_net: tick.liquidity_net })
+            .collect()
+    }
+}
+
+impl TryFrom<SolanaAccount> for TickArrayState {
+    type Error = std::io::Error;
+
+    fn try_from(account: SolanaAccount) -> Result<Self, Self::Error> {
+        account.decode()
+    }
+}
+
+impl TryFrom<SolanaAccount> for PoolState {
+    type Error = std::io::Error;
+
+    fn try_from(account: SolanaAccount) -> Result<Self, Self::Error> {
+        account.decode()
+    }
+}
+
+/// Orca whirlpool account, its mint decimals are not part of the account.
+#[repr(C)]
+#[derive(BorshDeserialize)]
+struct WhirlpoolState {
+    padding_before: [u8; 65],
+    sqrt_price: u128,
+    padding_after: [u8; 572],
+}
+
+impl WhirlpoolState {
+    pub fn price(&self, decimals_a: u8, decimals_b: u8) -> Result<Decimal, std::io::Error> {
+        price_from_sqrt_price_x64(self.sqrt_price, decimals_a, decimals_b).ok_or_else(
+            price_out_of_range
+        )
+    }
+}
+
+impl TryFrom<SolanaAccount> for WhirlpoolState {
+    type Error = std::io::Error;
+
+    fn try_from(account: SolanaAccount) -> Result<Self, Self::Error> {
+        account.decode()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Duration;
+
+    use axum::{ routing::post, Json, Router };
+    use rust_decimal_macros::dec;
+    use tokio::{ net::TcpListener, time::{ self, sleep } };
+
+    use crate::websocket::run_websocket;
+
+    use super::*;
+    use env_logger::Env;
+
+    #[ignore]
+    #[tokio::test]
+    async fn test_run() {
+        env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+        let markets = ["3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF"];
+        run_websocket::<Solana>(tx, &markets, control_rx).await;
+    }
+
+    #[test]
+    fn test_price() {
+        let pool = PoolState {
+            sqrt_price_x64: 6_990_823_775_062_275_942,
+            discriminator: [0; 8],
+            bump: 0,
+            amm_config: [0; 32],
+            owner: [0; 32],
+            token_mint_0: [0; 32],
+            token_mint_1: [0; 32],
+            token_vault_0: [0; 32],
+            token_vault_1: [0; 32],
+            observation_key: [0; 32],
+            mint_decimals_0: 9,
+            mint_decimals_1: 6,
+            tick_spacing: 1,
+            liquidity: 0,
+            tick_current: 0,
+            padding_after: [0; 1271],
+        };
+        assert_eq!(pool.price().unwrap().round_dp(2), dec!(143.62));
+    }
+
+    #[test]
+    fn test_decoding() {
+        let envelope = SolanaEnvelope {
+            params: SolanaParams {
+                subscription: 1,
+                result: SolanaResult {
+                    context: SolanaContext { slot: 1 },
+                    value: SolanaValue::Account(SolanaAccount {
+                        data: SolanaData(vec![POOL_DATA.to_string()]),
+                    }),
+                },
+            },
+        };
+
+        let SolanaValue::Account(account) = envelope.params.result.value else {
+            panic!("not an account notification");
+        };
+        let pool: PoolState = account.try_into().unwrap();
+
+        assert_eq!(pool.price().unwrap().round_dp(2), dec!(145.03));
+        assert_eq!(pool.instrument(), "SOL/USDT".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_incoming_payload() {
+        let mut session = SolanaSession::default();
+        Solana::get_subscribe_payloads(
+            &mut session,
+            &["3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF@finalized"]
+        );
+
+        let subscribed = json!({"jsonrpc": "2.0", "result": 23784, "id": 1}).to_string();
+        assert!(Solana::parse_incoming_payload(&mut session, subscribed).unwrap().is_empty());
+
+        let notification =
+            json!({"jsonrpc": "2.0", "method": "accountNotification", "params": {"result": {"context": {"slot": 5199307}, "value": {"data": [POOL_DATA, "base64"], "executable": false, "lamports": 33594, "owner": "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK", "rentEpoch": 635, "space": 1544}}, "subscription": 23784}}).to_string();
+        let market_price = Solana::parse_incoming_payload(&mut session, notification)
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(market_price.market, "3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF");
+        assert_eq!(market_price.slot, Some(5199307));
+        assert_eq!(market_price.commitment, Some(Commitment::Finalized));
+        assert_eq!(market_price.price.round_dp(2), dec!(145.03));
+    }
+
+    #[test]
+    fn test_parse_incoming_payload_unknown_subscription() {
+        let mut session = SolanaSession::default();
+
+        let notification =
+            json!({"jsonrpc": "2.0", "method": "accountNotification", "params": {"result": {"context": {"slot": 5199307}, "value": {"data": [POOL_DATA, "base64"]}}, "subscription": 1}}).to_string();
+
+        assert!(Solana::parse_incoming_payload(&mut session, notification).is_err());
+    }
+
+    #[test]
+    fn test_get_subscribe_payload() {
+        let payload = Solana::get_subscribe_payloads(
+            &mut SolanaSession::default(),
+            &["So11111111111111111111111111111111111111112", "123@processed"]
+        );
+        assert_eq!(
+            payload,
+            vec![
+                json!({"jsonrpc": "2.0", "id": 1, "method": "accountSubscribe", "params": ["So11111111111111111111111111111111111111112", {"encoding": "base64", "commitment": "confirmed"}] }).to_string(),
+                json!({"jsonrpc": "2.0", "id": 2, "method": "accountSubscribe", "params": ["123", {"encoding": "base64", "commitment": "processed"}] }).to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_unsubscribe_payload() {
+        let mut session = SolanaSession::default();
+        Solana::get_subscribe_payloads(&mut session, &["123", "456"]);
+
+        for (id, subscription) in [(1, 10), (2, 20)] {
+            let subscribed = json!({"jsonrpc": "2.0", "id": id, "result": subscription}).to_string();
+            assert!(Solana::parse_incoming_payload(&mut session, subscribed).unwrap().is_empty());
+        }
+
+        let payload = Solana::get_unsubscribe_payloads(&mut session, &["123"]);
+        assert_eq!(
+            payload,
+            vec![
+                json!({"jsonrpc": "2.0", "id": 3, "method": "accountUnsubscribe", "params": [10]}).to_string()
+            ]
+        );
+        assert_eq!(session.subscriptions.len(), 1);
+
+        // subscribed with another commitment
+        assert!(Solana::get_unsubscribe_payloads(&mut session, &["456@processed"]).is_empty());
+    }
+
+    #[test]
+    fn test_whirlpool_price() {
+        let pool = WhirlpoolState {
+            padding_before: [0; 65],
+            sqrt_price: 6_990_823_775_062_275_942,
+            padding_after: [0; 572],
+        };
+        assert_eq!(pool.price(9, 6).unwrap().round_dp(2), dec!(143.62));
+        assert_eq!(pool.price(6, 9).unwrap().round_dp(9), dec!(0.000143621));
+
+        // beyond a decimal, not a zero price
+        let pool = WhirlpoolState { sqrt_price: u128::MAX, ..pool };
+        assert!(pool.price(9, 6).is_err());
+    }
+
+    #[test]
+    fn test_get_subscribe_payload_pair() {
+        let payload = Solana::get_subscribe_payloads(
+            &mut SolanaSession::default(),
+            &["SOL/USDC@processed"]
+        );
+
+        let sol = "So11111111111111111111111111111111111111112";
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        let raydium = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
+        let orca = "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc";
+
+        assert_eq!(
+            payload,
+            vec![
+                json!({"jsonrpc": "2.0", "id": 1, "method": "programSubscribe", "params": [raydium, {"encoding": "base64", "commitment": "processed", "filters": [{"dataSize": 1544}, {"memcmp": {"offset": 73, "bytes": sol}}, {"memcmp": {"offset": 105, "bytes": usdc}}]}] }).to_string(),
+                json!({"jsonrpc": "2.0", "id": 2, "method": "programSubscribe", "params": [raydium, {"encoding": "base64", "commitment": "processed", "filters": [{"dataSize": 1544}, {"memcmp": {"offset": 73, "bytes": usdc}}, {"memcmp": {"offset": 105, "bytes": sol}}]}] }).to_string(),
+                json!({"jsonrpc": "2.0", "id": 3, "method": "programSubscribe", "params": [orca, {"encoding": "base64", "commitment": "processed", "filters": [{"dataSize": 653}, {"memcmp": {"offset": 101, "bytes": sol}}, {"memcmp": {"offset": 181, "bytes": usdc}}]}] }).to_string(),
+                json!({"jsonrpc": "2.0", "id": 4, "method": "programSubscribe", "params": [orca, {"encoding": "base64", "commitment": "processed", "filters": [{"dataSize": 653}, {"memcmp": {"offset": 101, "bytes": usdc}}, {"memcmp": {"offset": 181, "bytes": sol}}]}] }).to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_market() {
+        let pool = "3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF";
+        assert_eq!(Solana::normalize_market(pool), pool);
+        assert_eq!(Solana::normalize_market(&format!("{pool}@confirmed")), pool);
+        assert_eq!(Solana::normalize_market("sol/usdc@processed"), "SOL/USDC@processed");
+        assert_eq!(Solana::normalize_market("SOL/XYZ"), "SOL/XYZ");
+    }
+
+    #[test]
+    fn test_get_subscribe_payload_unknown_token() {
+        let payload = Solana::get_subscribe_payloads(&mut SolanaSession::default(), &["SOL/XYZ"]);
+        assert!(payload.is_empty());
+    }
+
+    // the fixture pool as its quote/base twin: mints, decimals and square root price swapped
+    fn inverted_pool_data() -> String {
+        let mut data = BASE64_STANDARD.decode(POOL_DATA).unwrap();
+
+        let (mint_0, mint_1) = (data[73..105].to_vec(), data[105..137].to_vec());
+        data[73..105].copy_from_slice(&mint_1);
+        data[105..137].copy_from_slice(&mint_0);
+        data.swap(233, 234);
+
+        let sqrt_price_x64 = u128::from_le_bytes(data[253..269].try_into().unwrap());
+        data[253..269].copy_from_slice(&(u128::MAX / sqrt_price_x64).to_le_bytes());
+
+        BASE64_STANDARD.encode(data)
+    }
+
+    #[test]
+    fn test_parse_incoming_payload_program() {
+        let account = |data: String| SolanaAccount { data: SolanaData(vec![data]) };
+        let pool = PoolState::try_from(account(POOL_DATA.to_string())).unwrap();
+        assert_eq!(pool.instrument(), "SOL/USDT".parse().unwrap());
+        let pool = PoolState::try_from(account(inverted_pool_data())).unwrap();
+        assert_eq!(pool.instrument(), "USDT/SOL".parse().unwrap());
+
+        let mut session = SolanaSession::default();
+        Solana::get_subscribe_payloads(&mut session, &["SOL/USDT"]);
+
+        // raydium base/quote and raydium quote/base subscriptions
+        for (id, subscription) in [(1, 100), (2, 200)] {
+            let subscribed = json!({"jsonrpc": "2.0", "result": subscription, "id": id}).to_string();
+            assert!(Solana::parse_incoming_payload(&mut session, subscribed).unwrap().is_empty());
+        }
+
+        let notification = |subscription: u64, data: String| {
+            json!({"jsonrpc": "2.0", "method": "programNotification", "params": {"result": {"context": {"slot": 5208469}, "value": {"pubkey": "3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF", "account": {"data": [data, "base64"], "executable": false, "lamports": 33594, "owner": "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK", "rentEpoch": 636, "space": 1544}}}, "subscription": subscription}}).to_string()
+        };
+
+        let market_price = Solana::parse_incoming_payload(
+            &mut session,
+            notification(100, POOL_DATA.to_string())
+        ).unwrap().remove(0);
+        assert_eq!(market_price.market, "3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF");
+        // found through the pair, unsubscribed with it
+        assert_eq!(market_price.subscription.as_deref(), Some("SOL/USDT"));
+        assert_eq!(market_price.instrument, "SOL/USDT".parse().unwrap());
+        assert_eq!(market_price.slot, Some(5208469));
+        assert_eq!(market_price.commitment, Some(Commitment::Confirmed));
+        assert_eq!(market_price.price.round_dp(2), dec!(145.03));
+
+        // a USDT/SOL pool found by the quote/base subscription, priced back as SOL in USDT
+        let market_price = Solana::parse_incoming_payload(
+            &mut session,
+            notification(200, inverted_pool_data())
+        ).unwrap().remove(0);
+        assert_eq!(market_price.instrument, "SOL/USDT".parse().unwrap());
+        assert_eq!(market_price.price.round_dp(2), dec!(145.03));
+    }
+
+    fn tick_array_data(start_tick_index: i32, ticks: &[(i32, i128)]) -> String {
+        let mut data = vec![0_u8; TICK_ARRAY_DATA_SIZE];
+        data[40..44].copy_from_slice(&start_tick_index.to_le_bytes());
+
+        for (tick, liquidity_net) in ticks {
+            let offset = 44 + ((tick - start_tick_index) as usize) * 168;
+            data[offset..offset + 4].copy_from_slice(&tick.to_le_bytes());
+            data[offset + 4..offset + 20].copy_from_slice(&liquidity_net.to_le_bytes());
+            data[offset + 20..offset + 36].copy_from_slice(&liquidity_net.unsigned_abs().to_le_bytes());
+        }
+
+        BASE64_STANDARD.encode(data)
+    }
+
+    #[test]
+    fn test_tick_array_decoding() {
+        let account = SolanaAccount {
+            data: SolanaData(vec![tick_array_data(-19320, &[(-19312, 1_000), (-19300, -1_000)])]),
+        };
+
+        let tick_array: TickArrayState = account.try_into().unwrap();
+
+        assert_eq!(tick_array.start_tick_index, -19320);
+        assert_eq!(
+            tick_array.initialized_ticks(),
+            vec![Tick { index: -19312, liquidity_net: 1_000 }, Tick { index: -19300, liquidity_net: -1_000 }]
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        let account = SolanaAccount { data: SolanaData(vec![POOL_DATA.to_string()]) };
+        let pool: PoolState = account.try_into().unwrap();
+        assert_eq!(pool.tick_current, -19310);
+
+        let mut tick_arrays = BTreeMap::new();
+        assert!(pool.quote(&tick_arrays, dec!(1000), dec!(0)).is_none());
+
+        tick_arrays.insert(-19320, vec![Tick { index: -19312, liquidity_net: 1_000_000_000_000 }]);
+        let quote = pool.quote(&tick_arrays, dec!(1000), dec!(0)).unwrap();
+
+        assert_eq!(quote.notional, dec!(1000));
+        let price = pool.price().unwrap();
+        assert!(quote.bid < price && price < quote.ask);
+        assert_eq!(quote.bid.round_dp(1), dec!(145.0));
+        assert_eq!(quote.ask.round_dp(1), dec!(145.1));
+
+        let with_fee = pool.quote(&tick_arrays, dec!(1000), dec!(0.0025)).unwrap();
+        assert!(with_fee.bid < quote.bid && with_fee.ask > quote.ask);
+
+        // far beyond the liquidity of the known tick array
+        assert!(pool.quote(&tick_arrays, dec!(100000000), dec!(0)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_parse_incoming_payload_quote() {
+        // without an rpc to fetch the tick arrays from
+        let rpc_url = "http://127.0.0.1:9".to_string();
+        let mut session = SolanaSession {
+            quote: Some(SolanaQuote { notional: dec!(1000), fee_rate: dec!(0), rpc_url }),
+            ..Default::default()
+        };
+
+        let payload = Solana::get_subscribe_payloads(
+            &mut session,
+            &["3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF"]
+        );
+        assert_eq!(
+            payload[0],
+            json!({"jsonrpc": "2.0", "id": 1, "method": "programSubscribe", "params": ["CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK", {"encoding": "base64", "commitment": "confirmed", "filters": [{"dataSize": 10240}, {"memcmp": {"offset": 8, "bytes": "3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF"}}]}] }).to_string()
+        );
+
+        for (id, subscription) in [(1, 100), (2, 200)] {
+            let subscribed = json!({"jsonrpc": "2.0", "result": subscription, "id": id}).to_string();
+            assert!(Solana::parse_incoming_payload(&mut session, subscribed).unwrap().is_empty());
+        }
+
+        let pool_notification =
+            json!({"jsonrpc": "2.0", "method": "accountNotification", "params": {"result": {"context": {"slot": 5199307}, "value": {"data": [POOL_DATA, "base64"]}}, "subscription": 200}}).to_string();
+
+        let market_price = Solana::parse_incoming_payload(
+            &mut session,
+            pool_notification.clone()
+        ).unwrap().remove(0);
+        assert_eq!(market_price.quote, None);
+
+        let tick_array_notification =
+            json!({"jsonrpc": "2.0", "method": "programNotification", "params": {"result": {"context": {"slot": 5199306}, "value": {"pubkey": "8a9pMQbqLdQ7Jp3iAqvWWDCkpEVrXSfcvnR2eFPVfbyN", "account": {"data": [tick_array_data(-19320, &[]), "base64"]}}}, "subscription": 100}}).to_string();
+        let market_prices = Solana::parse_incoming_payload(&mut session, tick_array_notification);
+        assert!(market_prices.unwrap().is_empty());
+
+        let market_price = Solana::parse_incoming_payload(&mut session, pool_notification)
+            .unwrap()
+            .remove(0);
+        let quote = market_price.quote.unwrap();
+        assert!(quote.bid < market_price.price && market_price.price < quote.ask);
+    }
+
+    #[tokio::test]
+    async fn test_seed_tick_arrays() {
+        // stand-in for a validator rpc, the tick arrays of the pool being there already
+        async fn handle(Json(request): Json<serde_json::Value>) -> Json<serde_json::Value> {
+            assert_eq!(request["method"], "getProgramAccounts");
+            assert_eq!(request["params"][1]["filters"][1]["memcmp"]["bytes"], POOL);
+
+            let data = tick_array_data(-19320, &[(-19312, 1_000_000_000_000)]);
+            Json(
+                json!({"jsonrpc": "2.0", "id": 1, "result": [{"pubkey": "8a9pMQbqLdQ7Jp3iAqvWWDCkpEVrXSfcvnR2eFPVfbyN", "account": {"data": [data, "base64"]}}]})
+            )
+        }
+
+        const POOL: &str = "3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF";
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new().route("/", post(handle));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut session = SolanaSession {
+            quote: Some(SolanaQuote { notional: dec!(1000), fee_rate: dec!(0), rpc_url }),
+            ..Default::default()
+        };
+        Solana::get_subscribe_payloads(&mut session, &[POOL]);
+
+        for (id, subscription) in [(1, 100), (2, 200)] {
+            let subscribed = json!({"jsonrpc": "2.0", "result": subscription, "id": id}).to_string();
+            assert!(Solana::parse_incoming_payload(&mut session, subscribed).unwrap().is_empty());
+        }
+
+        time::timeout(Duration::from_secs(1), async {
+            while session.seeded.lock().unwrap().is_empty() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+
+        // quoted without a tick array notified
+        let pool_notification =
+            json!({"jsonrpc": "2.0", "method": "accountNotification", "params": {"result": {"context": {"slot": 5199307}, "value": {"data": [POOL_DATA, "base64"]}}, "subscription": 200}}).to_string();
+        let market_price = Solana::parse_incoming_payload(&mut session, pool_notification)
+            .unwrap()
+            .remove(0);
+        assert!(market_price.quote.is_some());
+    }
+
+    pub const POOL_DATA: &str =
+        "9+3j9dfD3kb7gW5mYww7tyTcWeSfbMQwbmA6aqzKBvo+NOK0CtWXnY1LJZBs542fS5bm0kWx8ZP4xOiQk0ISjfuuV0pqSqpF3gabiFf+q4GE+2h/Y0YYwDXaxDncGus7VZig8AAAAAABzgEOYK/tsicXvWMZL1QUWj+WWjO7gtLHAp6yzh4ggmSOl4mMVq5GLrklfwryG1z8wflLif89xwgEtN4fI7mgaxppPIfVVn+bgJ/R8nlT1iGlw9fkLqkEqgzx6VHC9ftGr+LhfBxDzvjpEMkpDIWormlksnb9DvCnpeBye7wdhp4JBgEAQc08AKkOAAAAAAAAAAAAAHR+AfhI1n1hAAAAAAAAAACStP//AAAAAFiBU5VNPDYYAAAAAAAAAAClGBxAb7XyAwAAAAAAAAAAV4C/AQAAAAD7bT8AAAAAAJVH3ShRUBcAAAAAAAAAAADcys7885IDAAAAAAAAAAAA/kvA45yTAwAAAAAAAAAAAKCA0M0cVBcAAAAAAAAAAAAAAAAAAAAAAAK4hmlmAAAAACBq4WYAAAAAyHbQZgAAAAD4JYqiKIqiKLAJAAAAAAAA2Rpn5QMAAAA4+6SdAwAAADeZjMvy0EWLYVy8xrGjZ8R0np/vcwZiLhsbWJEBILyayARSkz4YqYFn0pA0SiNypKqAs5sKeIP8B8R/lglDZwoFbi5biuhaxy9JKpHBKlrVCfYFdU9E3Cnfqc2Lz1DJmFmTrjInvl4AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAASyWQbOeNn0uW5tJFsfGT+MTokJNCEo37rldKakqqRd4AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAEslkGznjZ9LlubSRbHxk/jE6JCTQhKN+65XSmpKqkXeAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAIAAACABABAADIKPr7P///////////33pCCsAgwAIgAEAAAJAgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA2+4TWIAAAAAotlajewAAAGCszLATAAAApTny+xIAAAD561EAAAAAAJQYCwAAAAAAAAAAAAAAAACXAgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+}
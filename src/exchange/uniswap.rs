@@ -0,0 +1,485 @@
+use rust_decimal::{ prelude::FromPrimitive, Decimal };
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    config::{ env_list, ConfigError },
+    websocket::ExchangeWebSocketConfig,
+    Instrument,
+    MarketPrice,
+};
+
+use std::{ collections::HashMap, str::FromStr };
+
+// keccak256("Swap(address,address,int256,int256,uint160,uint128,int24)")
+const SWAP_TOPIC: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+
+pub struct Uniswap;
+
+impl ExchangeWebSocketConfig for Uniswap {
+    const EXCHANGE_ID: &'static str = "uniswap";
+
+    type Session = UniswapSession;
+
+    // any ethereum json-rpc websocket endpoints in failover order
+    fn urls() -> Result<Vec<String>, ConfigError> {
+        env_list("ETHEREUM_WS_URLS")?.ok_or(ConfigError::Missing("ETHEREUM_WS_URLS"))
+    }
+
+    // addresses lowercased, as the logs name the pools
+    fn normalize_market(market: &str) -> String {
+        market.parse::<UniswapPool>().map_or(market.to_string(), |pool| pool.market())
+    }
+
+    // a single logs subscription covers the swaps of every pool
+    fn get_subscribe_payloads(session: &mut UniswapSession, markets: &[&str]) -> Vec<String> {
+        for market in markets {
+            match market.parse::<UniswapPool>() {
+                Ok(pool) => {
+                    session.pools.insert(pool.address.to_lowercase(), pool);
+                }
+                Err(err) => log::error!("{} {err}", Self::EXCHANGE_ID),
+            }
+        }
+
+        session.resubscribe()
+    }
+
+    fn get_unsubscribe_payloads(session: &mut UniswapSession, markets: &[&str]) -> Vec<String> {
+        for market in markets {
+            let address = market.split_once('@').map_or(*market, |(address, _)| address);
+            session.pools.remove(&address.to_lowercase());
+        }
+
+        session.resubscribe()
+    }
+
+    fn parse_incoming_payload(
+        session: &mut UniswapSession,
+        payload: String
+    ) -> Result<Vec<MarketPrice>, std::io::Error> {
+        let log = match serde_json::from_str::<UniswapMessage>(&payload)? {
+            UniswapMessage::Notification(envelope) => envelope.params.result,
+            UniswapMessage::Subscribed { result } => {
+                session.subscription = Some(result);
+                return Ok(vec![]);
+            }
+            UniswapMessage::Unsubscribed { result } => {
+                log::debug!("{} unsubscribed: {result}", Self::EXCHANGE_ID);
+                return Ok(vec![]);
+            }
+        };
+
+        // logs are re-sent with removed set when their block is reorganized out of the chain
+        if log.removed || log.topics.first().map(String::as_str) != Some(SWAP_TOPIC) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "not a swap"));
+        }
+
+        let pool = session.pools
+            .get(&log.address.to_lowercase())
+            .ok_or(std::io::Error::new(std::io::ErrorKind::InvalidData, "unknown pool"))?;
+
+        let swap = log.data.parse::<UniswapSwap>()?;
+
+        Ok(
+            vec![MarketPrice {
+                exchange_id: Self::EXCHANGE_ID,
+                price: pool.price(swap.sqrt_price_x96)?,
+                market: pool.address.clone(),
+                subscription: Some(pool.market()),
+                instrument: pool.instrument.clone(),
+                block_number: Some(parse_quantity(&log.block_number)?),
+                ..Default::default()
+            }]
+        )
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct UniswapSession {
+    pools: HashMap<String, UniswapPool>, // lowercase address -> pool
+    subscription: Option<String>, // id of the logs subscription
+}
+
+impl UniswapSession {
+    // the logs subscription is replaced by one over the pools, if any
+    fn resubscribe(&mut self) -> Vec<String> {
+        let mut payloads = vec![];
+
+        if let Some(subscription) = self.subscription.take() {
+            payloads.push(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "eth_unsubscribe", "params": [subscription]}).to_string()
+            );
+        }
+
+        if !self.pools.is_empty() {
+            let addresses = self.pools
+                .values()
+                .map(|pool| pool.address.as_str())
+                .collect::<Vec<_>>();
+
+            payloads.push(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "eth_subscribe", "params": ["logs", {"address": addresses, "topics": [SWAP_TOPIC]}]}).to_string()
+            );
+        }
+
+        payloads
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Token {
+    address: &'static str,
+    decimals: u8,
+}
+
+const TOKENS: [(&str, Token); 4] = [
+    ("WETH", Token { address: "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", decimals: 18 }),
+    ("USDC", Token { address: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", decimals: 6 }),
+    ("USDT", Token { address: "0xdAC17F958D2ee523a2206206994597C13D831ec7", decimals: 6 }),
+    ("WBTC", Token { address: "0x2260FAC5E5542a773Aa44fBCfeDf7193F9c59fC3", decimals: 8 }),
+];
+
+impl FromStr for Token {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TOKENS.iter()
+            .find(|(symbol, _)| *symbol == s)
+            .map(|(_, token)| *token)
+            .ok_or(
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("unknown token {s}"))
+            )
+    }
+}
+
+/// Pool market in the form `<address>@<base>/<quote>`, priced as base in quote.
+#[derive(Debug, Clone)]
+struct UniswapPool {
+    address: String,
+    instrument: Instrument,
+    base: Token,
+    quote: Token,
+}
+
+impl UniswapPool {
+    fn market(&self) -> String {
+        format!("{}@{}", self.address.to_lowercase(), self.instrument)
+    }
+
+    // uniswap orders the pool tokens by address, so token0 is not necessarily the base
+    fn inverted(&self) -> bool {
+        self.base.address.to_lowercase() > self.quote.address.to_lowercase()
+    }
+
+    fn price(&self, sqrt_price_x96: (u64, u128)) -> Result<Decimal, std::io::Error> {
+        let (decimals_0, decimals_1) = if self.inverted() {
+            (self.quote.decimals, self.base.decimals)
+        } else {
+            (self.base.decimals, self.quote.decimals)
+        };
+
+        let price = price_from_sqrt_price_x96(sqrt_price_x96, decimals_0, decimals_1).ok_or(
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "price out of range")
+        )?;
+
+        if !self.inverted() {
+            return Ok(price);
+        }
+
+        Decimal::ONE.checked_div(price).ok_or(
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "zero pool price")
+        )
+    }
+}
+
+impl FromStr for UniswapPool {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("invalid pool {s}"))
+        };
+
+        let (address, pair) = s.split_once('@').ok_or_else(invalid)?;
+        let (base, quote) = pair.split_once('/').ok_or_else(invalid)?;
+
+        Ok(UniswapPool {
+            address: address.to_string(),
+            instrument: pair.parse()?,
+            base: base.to_uppercase().parse()?,
+            quote: quote.to_uppercase().parse()?,
+        })
+    }
+}
+
+// price of token 0 in token 1 from a Q64.96 square root price split in its integer
+// (up to 64 bits) and fractional (96 bits) parts, as the latter alone fills a decimal
+fn price_from_sqrt_price_x96(
+    (integer, fraction): (u64, u128),
+    decimals_0: u8,
+    decimals_1: u8
+) -> Option<Decimal> {
+    let fraction =
+        Decimal::from_u128(fraction)? /
+        Decimal::from_u128((2_u128).pow(48))? /
+        Decimal::from_u128((2_u128).pow(48))?;
+    let sqrt_price = Decimal::from(integer) + fraction;
+
+    let exponent = i32::from(decimals_0) - i32::from(decimals_1);
+    let scale = Decimal::from(10_u64.pow(exponent.unsigned_abs()));
+
+    let price = sqrt_price.checked_mul(sqrt_price)?;
+
+    if exponent >= 0 {
+        price.checked_mul(scale)
+    } else {
+        price.checked_div(scale)
+    }
+}
+
+fn parse_quantity(quantity: &str) -> Result<u64, std::io::Error> {
+    u64::from_str_radix(quantity.trim_start_matches("0x"), 16).map_err(|err|
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    )
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum UniswapMessage {
+    Notification(UniswapEnvelope),
+    Subscribed {
+        result: String,
+    },
+    // the reply to eth_unsubscribe
+    Unsubscribed {
+        result: bool,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct UniswapEnvelope {
+    params: UniswapParams,
+}
+
+#[derive(Deserialize, Debug)]
+struct UniswapParams {
+    result: UniswapLog,
+}
+
+#[derive(Deserialize, Debug)]
+struct UniswapLog {
+    address: String,
+    topics: Vec<String>,
+    data: String,
+    #[serde(rename = "blockNumber")]
+    block_number: String,
+    #[serde(default)]
+    removed: bool,
+}
+
+/// ABI encoded `Swap` data: amount0, amount1, sqrtPriceX96, liquidity and tick words.
+struct UniswapSwap {
+    sqrt_price_x96: (u64, u128),
+}
+
+impl FromStr for UniswapSwap {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = s.trim_start_matches("0x");
+        if data.len() != 5 * 64 || !data.is_ascii() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid swap data"));
+        }
+
+        // uint160 in the last 40 hex digits of the third word
+        let word = &data[2 * 64..3 * 64];
+        if word[..24].bytes().any(|digit| digit != b'0') {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid uint160"));
+        }
+
+        let invalid = |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+
+        Ok(UniswapSwap {
+            sqrt_price_x96: (
+                u64::from_str_radix(&word[24..40], 16).map_err(invalid)?,
+                u128::from_str_radix(&word[40..], 16).map_err(invalid)?,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ sync::Mutex, time::Duration };
+
+    use async_tungstenite::tungstenite::Message;
+    use rust_decimal_macros::dec;
+    use tokio::{ join, time::timeout };
+    use ws_mock::{ matchers::StringContains, ws_mock_server::{ WsMock, WsMockServer } };
+
+    use crate::websocket::run_websocket;
+
+    use super::*;
+
+    const POOL: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
+
+    // USDC/WETH swap at 2487.31 USDC per WETH
+    const SWAP_DATA: &str =
+        "0xfffffffffffffffffffffffffffffffffffffffffffffffffffffffed5fa0e000000000000000000000000000000000000000000000000001bc16d674ec800000000000000000000000000000000000000004e52f43d904a2fcaeb8cdaed0a1600000000000000000000000000000000000000000000021e19e0c9bab2400000fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffcfa90";
+
+    fn notification(removed: bool) -> String {
+        json!({"jsonrpc": "2.0", "method": "eth_subscription", "params": {"subscription": "0xcd0c3e8af590364c09d0fa6a1210faf5", "result": {"address": POOL.to_lowercase(), "blockHash": "0x61cdb2a09ab99abf791d474f20c2ea89bf8de2923a2d42bb49944c8c993cbf04", "blockNumber": "0x12a05f2", "data": SWAP_DATA, "logIndex": "0x1b", "topics": [SWAP_TOPIC, "0x000000000000000000000000e592427a0aece92de3edee1f18e0157c05861564", "0x000000000000000000000000e592427a0aece92de3edee1f18e0157c05861564"], "transactionHash": "0x2a8b1c0b8e6d2c1e4a0d2b7d3f8b4a7e9c1d0f2e3b4a5c6d7e8f9a0b1c2d3e4f", "transactionIndex": "0x3", "removed": removed}}}).to_string()
+    }
+
+    #[test]
+    fn test_get_subscribe_payload() {
+        let payload = Uniswap::get_subscribe_payloads(
+            &mut UniswapSession::default(),
+            &[&format!("{POOL}@WETH/USDC"), "0x1@XYZ/USDC"]
+        );
+        assert_eq!(
+            payload,
+            vec![
+                json!({"jsonrpc": "2.0", "id": 1, "method": "eth_subscribe", "params": ["logs", {"address": [POOL], "topics": [SWAP_TOPIC]}]}).to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_market() {
+        assert_eq!(
+            Uniswap::normalize_market(&format!("{POOL}@weth/usdc")),
+            format!("{}@WETH/USDC", POOL.to_lowercase())
+        );
+    }
+
+    #[test]
+    fn test_get_unsubscribe_payload() {
+        let mut session = UniswapSession::default();
+        let other = "0x1111111111111111111111111111111111111111";
+        Uniswap::get_subscribe_payloads(
+            &mut session,
+            &[&format!("{POOL}@WETH/USDC"), &format!("{other}@WBTC/USDC")]
+        );
+
+        let subscribed = json!({"jsonrpc": "2.0", "id": 1, "result": "0xcd0c3e8af590364c09d0fa6a1210faf5"});
+        let market_prices = Uniswap::parse_incoming_payload(&mut session, subscribed.to_string());
+        assert!(market_prices.unwrap().is_empty());
+
+        // the subscription is replaced by one without the pool
+        let market = format!("{POOL}@WETH/USDC");
+        let payload = Uniswap::get_unsubscribe_payloads(&mut session, &[&market]);
+        assert_eq!(
+            payload,
+            vec![
+                json!({"jsonrpc": "2.0", "id": 2, "method": "eth_unsubscribe", "params": ["0xcd0c3e8af590364c09d0fa6a1210faf5"]}).to_string(),
+                json!({"jsonrpc": "2.0", "id": 1, "method": "eth_subscribe", "params": ["logs", {"address": [other], "topics": [SWAP_TOPIC]}]}).to_string()
+            ]
+        );
+
+        // logs of the pool still on their way are left out
+        assert!(Uniswap::parse_incoming_payload(&mut session, notification(false)).is_err());
+
+        let payload = json!({"jsonrpc": "2.0", "id": 2, "result": true}).to_string();
+        assert!(Uniswap::parse_incoming_payload(&mut session, payload).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_incoming_payload() {
+        let mut session = UniswapSession::default();
+        Uniswap::get_subscribe_payloads(&mut session, &[&format!("{POOL}@WETH/USDC")]);
+
+        let market_price = Uniswap::parse_incoming_payload(
+            &mut session,
+            notification(false)
+        ).unwrap().remove(0);
+        assert_eq!(market_price.market, POOL);
+        assert_eq!(market_price.subscription, Some(format!("{}@WETH/USDC", POOL.to_lowercase())));
+        assert_eq!(market_price.instrument, "WETH/USDC".parse().unwrap());
+        assert_eq!(market_price.block_number, Some(19_531_250));
+        assert_eq!(market_price.price.round_dp(2), dec!(2487.31));
+    }
+
+    #[test]
+    fn test_parse_incoming_payload_not_inverted() {
+        let mut session = UniswapSession::default();
+        Uniswap::get_subscribe_payloads(&mut session, &[&format!("{POOL}@USDC/WETH")]);
+
+        let market_price = Uniswap::parse_incoming_payload(
+            &mut session,
+            notification(false)
+        ).unwrap().remove(0);
+        assert_eq!(market_price.price.round_dp(8), dec!(0.00040204));
+    }
+
+    #[test]
+    fn test_parse_incoming_payload_removed() {
+        let mut session = UniswapSession::default();
+        Uniswap::get_subscribe_payloads(&mut session, &[&format!("{POOL}@WETH/USDC")]);
+
+        assert!(Uniswap::parse_incoming_payload(&mut session, notification(true)).is_err());
+    }
+
+    // endpoint of the mock server, ETHEREUM_WS_URLS left alone for the other tests
+    static MOCK_URLS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    // uniswap against the mock server
+    struct MockUniswap;
+
+    impl ExchangeWebSocketConfig for MockUniswap {
+        const EXCHANGE_ID: &'static str = Uniswap::EXCHANGE_ID;
+
+        type Session = UniswapSession;
+
+        fn urls() -> Result<Vec<String>, ConfigError> {
+            Ok(MOCK_URLS.lock().unwrap().clone())
+        }
+
+        fn get_subscribe_payloads(session: &mut UniswapSession, markets: &[&str]) -> Vec<String> {
+            Uniswap::get_subscribe_payloads(session, markets)
+        }
+
+        fn get_unsubscribe_payloads(
+            session: &mut UniswapSession,
+            markets: &[&str]
+        ) -> Vec<String> {
+            Uniswap::get_unsubscribe_payloads(session, markets)
+        }
+
+        fn parse_incoming_payload(
+            session: &mut UniswapSession,
+            payload: String
+        ) -> Result<Vec<MarketPrice>, std::io::Error> {
+            Uniswap::parse_incoming_payload(session, payload)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_websocket() {
+        let server = WsMockServer::start().await;
+
+        *MOCK_URLS.lock().unwrap() = vec![server.uri().await];
+
+        WsMock::new()
+            .matcher(StringContains::new("eth_subscribe"))
+            .respond_with(Message::Text(notification(false)))
+            .expect(1)
+            .mount(&server).await;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+        let market = format!("{POOL}@WETH/USDC");
+        let markets = [market.as_str()];
+
+        join!(run_websocket::<MockUniswap>(tx, &markets, control_rx), async move {
+            let market_prices = timeout(Duration::from_secs(5), rx.recv()).await.unwrap();
+
+            let market_price = market_prices.unwrap()[0].clone();
+            assert_eq!(market_price.exchange_id, "uniswap");
+            assert_eq!(market_price.price.round_dp(2), dec!(2487.31));
+        });
+
+        server.verify().await;
+    }
+}
@@ -0,0 +1,750 @@
+use std::{
+    collections::{ BTreeMap, HashMap },
+    sync::Arc,
+    time::{ Duration, SystemTime, UNIX_EPOCH },
+};
+
+use async_tungstenite::{ tokio::connect_async, tungstenite::Message };
+use futures::prelude::*;
+use hmac::{ Hmac, Mac };
+use reqwest::{ Method, Url };
+use rust_decimal::Decimal;
+use serde::{ de::DeserializeOwned, Deserialize };
+use sha2::Sha256;
+use tokio::{ select, sync::broadcast, task::JoinHandle, time::{ interval, sleep } };
+
+use crate::{ config::{ self, ConfigError }, exchange::binance::instrument };
+
+use super::{
+    ExecutionError,
+    ExecutionVenue,
+    Fill,
+    Increments,
+    OrderRequest,
+    OrderState,
+    OrderStatus,
+    Side,
+};
+
+const EXCHANGE_ID: &str = "binance";
+// how long a signed request stays valid on the server
+const RECV_WINDOW_MS: u64 = 5000;
+// listen keys expire after an hour without keepalive
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+pub struct BinanceConfig {
+    pub rest_url: String,
+    pub user_stream_url: String,
+    pub api_key: String,
+    pub secret_key: String,
+}
+
+impl BinanceConfig {
+    /// `BINANCE_API_KEY` and `BINANCE_SECRET_KEY`, with the endpoints overridable by
+    /// `BINANCE_REST_URL` and `BINANCE_USER_STREAM_URL`.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(BinanceConfig {
+            rest_url: config
+                ::env_parse("BINANCE_REST_URL")?
+                .unwrap_or("https://api.binance.com".to_string()),
+            user_stream_url: config
+                ::env_parse("BINANCE_USER_STREAM_URL")?
+                .unwrap_or("wss://stream.binance.com:9443/ws".to_string()),
+            api_key: config
+                ::env_parse("BINANCE_API_KEY")?
+                .ok_or(ConfigError::Missing("BINANCE_API_KEY"))?,
+            secret_key: config
+                ::env_parse("BINANCE_SECRET_KEY")?
+                .ok_or(ConfigError::Missing("BINANCE_SECRET_KEY"))?,
+        })
+    }
+}
+
+/// Spot orders through the signed REST api, fills from the user data stream.
+pub struct BinanceVenue {
+    client: Client,
+    // order id -> market, as binance wants both to cancel or query an order
+    markets: HashMap<String, String>,
+    // of the markets traded so far, from their exchange info
+    increments: HashMap<String, Increments>,
+    fills: broadcast::Sender<Fill>,
+    user_stream: JoinHandle<()>,
+}
+
+impl BinanceVenue {
+    pub fn new(config: BinanceConfig) -> Self {
+        let client = Client { http: reqwest::Client::new(), config: Arc::new(config) };
+        let fills = broadcast::channel(1024).0;
+        let user_stream = tokio::spawn(run_user_stream(client.clone(), fills.clone()));
+
+        Self { client, markets: HashMap::new(), increments: HashMap::new(), fills, user_stream }
+    }
+
+    fn market(&self, order_id: &str) -> Result<&str, ExecutionError> {
+        self.markets
+            .get(order_id)
+            .map(String::as_str)
+            .ok_or(ExecutionError::NotFound(order_id.to_string()))
+    }
+
+    // the price and lot size filters of the symbol, fetched once
+    async fn increments(&mut self, symbol: &str) -> Result<Increments, ExecutionError> {
+        if let Some(increments) = self.increments.get(symbol) {
+            return Ok(*increments);
+        }
+
+        let mut url = self.client.url("/api/v3/exchangeInfo")?;
+        url.query_pairs_mut().append_pair("symbol", symbol);
+        let response = self.client.send::<ExchangeInfoResponse>(self.client.http.get(url)).await?;
+
+        let filters = response.symbols
+            .into_iter()
+            .find(|info| info.symbol == symbol)
+            .ok_or(ExecutionError::NotFound(symbol.to_string()))?
+            .filters;
+
+        let mut increments = Increments { tick_size: Decimal::ZERO, step_size: Decimal::ZERO };
+        for filter in filters {
+            match filter {
+                SymbolFilter::Price { tick_size } => increments.tick_size = tick_size,
+                SymbolFilter::LotSize { step_size } => increments.step_size = step_size,
+                SymbolFilter::Other => {}
+            }
+        }
+
+        self.increments.insert(symbol.to_string(), increments);
+        Ok(increments)
+    }
+}
+
+impl Drop for BinanceVenue {
+    fn drop(&mut self) {
+        self.user_stream.abort();
+    }
+}
+
+impl ExecutionVenue for BinanceVenue {
+    async fn place_order(&mut self, order: OrderRequest) -> Result<String, ExecutionError> {
+        let symbol = order.market.to_uppercase();
+        let order = self.increments(&symbol).await?.round(order)?;
+
+        let mut params = vec![
+            ("symbol", symbol.clone()),
+            ("side", side_name(order.side).to_string()),
+            ("quantity", order.quantity.normalize().to_string()),
+            ("newClientOrderId", order.client_order_id.clone()),
+            ("newOrderRespType", "ACK".to_string())
+        ];
+
+        match order.limit_price {
+            Some(price) => {
+                params.push(("type", "LIMIT".to_string()));
+                params.push(("timeInForce", "IOC".to_string()));
+                params.push(("price", price.normalize().to_string()));
+            }
+            None => params.push(("type", "MARKET".to_string())),
+        }
+
+        let response = self.client.signed::<NewOrderResponse>(
+            Method::POST,
+            "/api/v3/order",
+            &params
+        ).await?;
+
+        let order_id = response.order_id.to_string();
+        self.markets.insert(order_id.clone(), symbol);
+
+        Ok(order_id)
+    }
+
+    async fn cancel_order(&mut self, order_id: &str) -> Result<(), ExecutionError> {
+        let params = [
+            ("symbol", self.market(order_id)?.to_string()),
+            ("orderId", order_id.to_string()),
+        ];
+
+        self.client.signed::<serde_json::Value>(Method::DELETE, "/api/v3/order", &params).await?;
+
+        Ok(())
+    }
+
+    async fn order_state(&mut self, order_id: &str) -> Result<OrderState, ExecutionError> {
+        let params = [
+            ("symbol", self.market(order_id)?.to_string()),
+            ("orderId", order_id.to_string()),
+        ];
+
+        let response = self.client.signed::<QueryOrderResponse>(
+            Method::GET,
+            "/api/v3/order",
+            &params
+        ).await?;
+
+        let status = match response.status.as_str() {
+            "NEW" | "PENDING_NEW" | "PARTIALLY_FILLED" => OrderStatus::Open,
+            "FILLED" => OrderStatus::Filled,
+            "CANCELED" | "PENDING_CANCEL" => OrderStatus::Canceled,
+            _ => OrderStatus::Expired,
+        };
+
+        Ok(OrderState { status, executed_quantity: response.executed_quantity })
+    }
+
+    async fn balances(&mut self) -> Result<BTreeMap<String, Decimal>, ExecutionError> {
+        let params = [("omitZeroBalances", "true".to_string())];
+
+        let response = self.client.signed::<AccountResponse>(
+            Method::GET,
+            "/api/v3/account",
+            &params
+        ).await?;
+
+        Ok(
+            response.balances
+                .into_iter()
+                .map(|balance| (balance.asset, balance.free))
+                .collect()
+        )
+    }
+
+    fn fills(&self) -> broadcast::Receiver<Fill> {
+        self.fills.subscribe()
+    }
+}
+
+#[derive(Clone)]
+struct Client {
+    http: reqwest::Client,
+    config: Arc<BinanceConfig>,
+}
+
+impl Client {
+    // sends the request with its query signed by the secret key
+    async fn signed<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        params: &[(&str, String)]
+    ) -> Result<T, ExecutionError> {
+        let mut url = self.url(path)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+
+        url.query_pairs_mut()
+            .extend_pairs(params)
+            .append_pair("recvWindow", &RECV_WINDOW_MS.to_string())
+            .append_pair("timestamp", &timestamp.to_string());
+
+        let signature = sign(&self.config.secret_key, url.query().unwrap_or_default());
+        url.query_pairs_mut().append_pair("signature", &signature);
+
+        self.send(self.http.request(method, url)).await
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder
+    ) -> Result<T, ExecutionError> {
+        let transport = |err: reqwest::Error| ExecutionError::Transport(err.to_string());
+
+        let response = request
+            .header("X-MBX-APIKEY", &self.config.api_key)
+            .send().await
+            .map_err(transport)?;
+
+        let status = response.status();
+        let body = response.text().await.map_err(transport)?;
+
+        if status.is_success() {
+            return serde_json
+                ::from_str(&body)
+                .map_err(|err| ExecutionError::Transport(format!("unexpected response {err}")));
+        }
+
+        match serde_json::from_str::<ErrorResponse>(&body) {
+            // unknown order, or one that cannot be canceled anymore
+            Ok(error) if error.code == -2011 || error.code == -2013 => {
+                Err(ExecutionError::NotFound(error.msg))
+            }
+            Ok(error) if status.is_client_error() => {
+                Err(ExecutionError::Rejected(format!("{} {}", error.code, error.msg)))
+            }
+            _ => Err(ExecutionError::Transport(format!("{status} {body}"))),
+        }
+    }
+
+    fn url(&self, path: &str) -> Result<Url, ExecutionError> {
+        Url::parse(&format!("{}{path}", self.config.rest_url)).map_err(|err| {
+            ExecutionError::Transport(err.to_string())
+        })
+    }
+
+    async fn listen_key(&self) -> Result<String, ExecutionError> {
+        let url = self.url("/api/v3/userDataStream")?;
+        let response = self.send::<ListenKeyResponse>(self.http.post(url)).await?;
+        Ok(response.listen_key)
+    }
+
+    async fn keepalive(&self, listen_key: &str) -> Result<(), ExecutionError> {
+        let mut url = self.url("/api/v3/userDataStream")?;
+        url.query_pairs_mut().append_pair("listenKey", listen_key);
+        self.send::<serde_json::Value>(self.http.put(url)).await?;
+        Ok(())
+    }
+}
+
+// forwards the trades of the account orders, reconnecting with a new listen key
+async fn run_user_stream(client: Client, fills: broadcast::Sender<Fill>) {
+    loop {
+        // sleeping to avoid max cpu usage in case of retry
+        sleep(Duration::from_secs(1)).await;
+
+        let listen_key = match client.listen_key().await {
+            Ok(listen_key) => listen_key,
+            Err(err) => {
+                log::warn!("{EXCHANGE_ID} cannot get a listen key {err}");
+                continue;
+            }
+        };
+
+        let url = format!("{}/{listen_key}", client.config.user_stream_url);
+        let Ok((mut conn, _)) = connect_async(url).await else {
+            log::warn!("{EXCHANGE_ID} cannot connect to the user data stream");
+            continue;
+        };
+
+        log::debug!("{EXCHANGE_ID} user data stream connected");
+
+        let mut keepalive = interval(KEEPALIVE_INTERVAL);
+        keepalive.tick().await;
+
+        loop {
+            select! {
+                _ = keepalive.tick() => {
+                    if let Err(err) = client.keepalive(&listen_key).await {
+                        log::warn!("{EXCHANGE_ID} cannot keep the listen key alive {err}");
+                    }
+                }
+
+                res = conn.next() => {
+                    match res {
+                        Some(Ok(Message::Text(payload))) => {
+                            match parse_execution_report(&payload) {
+                                Ok(Some(fill)) => {
+                                    let _ = fills.send(fill);
+                                }
+                                Ok(None) => {}
+                                Err(err) => log::debug!("{EXCHANGE_ID} user data {err}"),
+                            }
+                        }
+                        Some(Ok(Message::Ping(value))) => {
+                            let _ = conn.send(Message::Pong(value)).await;
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    }
+                }
+            }
+        }
+
+        log::debug!("{EXCHANGE_ID} user data stream disconnected");
+    }
+}
+
+// the fill of an execution report, `None` for other events and order updates
+fn parse_execution_report(payload: &str) -> Result<Option<Fill>, std::io::Error> {
+    let event = serde_json::from_str::<UserDataEvent>(payload)?;
+
+    let UserDataEvent::ExecutionReport(report) = event else {
+        return Ok(None);
+    };
+
+    if report.execution_type != "TRADE" {
+        return Ok(None);
+    }
+
+    let side = match report.side.as_str() {
+        "BUY" => Side::Buy,
+        "SELL" => Side::Sell,
+        side => {
+            return Err(
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("unknown side {side}"))
+            );
+        }
+    };
+
+    // commissions are charged in either asset, or in bnb which is left out
+    let instrument = instrument(&report.symbol)?;
+    let fee = match report.commission_asset {
+        Some(asset) if asset == instrument.quote => report.commission,
+        Some(asset) if asset == instrument.base => report.commission * report.last_price,
+        _ => Decimal::ZERO,
+    };
+
+    Ok(
+        Some(Fill {
+            exchange_id: EXCHANGE_ID,
+            order_id: report.order_id.to_string(),
+            client_order_id: report.client_order_id,
+            market: report.symbol,
+            side,
+            quantity: report.last_quantity,
+            price: report.last_price,
+            fee,
+            timestamp: UNIX_EPOCH + Duration::from_millis(report.transaction_time),
+        })
+    )
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "BUY",
+        Side::Sell => "SELL",
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of the query string.
+fn sign(secret_key: &str, query: &str) -> String {
+    let mut mac = Hmac::<Sha256>
+        ::new_from_slice(secret_key.as_bytes())
+        .expect("hmac takes keys of any size");
+    mac.update(query.as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    code: i64,
+    msg: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NewOrderResponse {
+    #[serde(rename = "orderId")]
+    order_id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct QueryOrderResponse {
+    status: String,
+    #[serde(rename = "executedQty")]
+    executed_quantity: Decimal,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExchangeInfoResponse {
+    symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SymbolInfo {
+    symbol: String,
+    filters: Vec<SymbolFilter>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "filterType")]
+enum SymbolFilter {
+    #[serde(rename = "PRICE_FILTER")]
+    Price {
+        #[serde(rename = "tickSize")]
+        tick_size: Decimal,
+    },
+    #[serde(rename = "LOT_SIZE")]
+    LotSize {
+        #[serde(rename = "stepSize")]
+        step_size: Decimal,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+struct AccountResponse {
+    balances: Vec<AccountBalance>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AccountBalance {
+    asset: String,
+    free: Decimal,
+}
+
+#[derive(Deserialize, Debug)]
+struct ListenKeyResponse {
+    #[serde(rename = "listenKey")]
+    listen_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "e")]
+enum UserDataEvent {
+    #[serde(rename = "executionReport")]
+    ExecutionReport(ExecutionReport),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExecutionReport {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "c")]
+    client_order_id: String,
+    #[serde(rename = "S")]
+    side: String,
+    #[serde(rename = "x")]
+    execution_type: String,
+    #[serde(rename = "i")]
+    order_id: u64,
+    #[serde(rename = "l")]
+    last_quantity: Decimal,
+    #[serde(rename = "L")]
+    last_price: Decimal,
+    #[serde(rename = "n")]
+    commission: Decimal,
+    #[serde(rename = "N")]
+    commission_asset: Option<String>,
+    #[serde(rename = "T")]
+    transaction_time: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use async_tungstenite::tokio::accept_async;
+    use axum::{
+        extract::RawQuery,
+        http::{ HeaderMap, StatusCode },
+        response::IntoResponse,
+        routing::post,
+        Json,
+        Router,
+    };
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+    use tokio::{ net::TcpListener, time::timeout };
+
+    use crate::Instrument;
+
+    use super::*;
+
+    const API_KEY: &str = "test-api-key";
+    const SECRET_KEY: &str = "test-secret-key";
+
+    // queries of the orders the mock api received
+    static ORDERS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(vec![]);
+
+    // stand-in for the spot api, checking every signed request
+    fn mock_api() -> Router {
+        async fn signed(headers: &HeaderMap, query: &Option<String>) -> Result<(), StatusCode> {
+            if headers.get("X-MBX-APIKEY").map(|key| key.as_bytes()) != Some(API_KEY.as_bytes()) {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+
+            let query = query.as_deref().unwrap_or_default();
+            let Some((payload, signature)) = query.rsplit_once("&signature=") else {
+                return Err(StatusCode::BAD_REQUEST);
+            };
+
+            if sign(SECRET_KEY, payload) != signature || !payload.contains("timestamp=") {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+
+            Ok(())
+        }
+
+        async fn new_order(headers: HeaderMap, RawQuery(query): RawQuery) -> impl IntoResponse {
+            if let Err(status) = signed(&headers, &query).await {
+                return (status, Json(json!({"code": -1022, "msg": "invalid signature"})));
+            }
+
+            let query = query.unwrap_or_default();
+            ORDERS.lock().unwrap().push(query.clone());
+
+            if query.contains("quantity=100") {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"code": -2010, "msg": "Account has insufficient balance"})),
+                );
+            }
+
+            (StatusCode::OK, Json(json!({"symbol": "SOLUSDT", "orderId": 28})))
+        }
+
+        async fn cancel_order(headers: HeaderMap, RawQuery(query): RawQuery) -> impl IntoResponse {
+            if let Err(status) = signed(&headers, &query).await {
+                return (status, Json(json!({"code": -1022, "msg": "invalid signature"})));
+            }
+
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"code": -2011, "msg": "Unknown order sent."})),
+            )
+        }
+
+        async fn query_order(headers: HeaderMap, RawQuery(query): RawQuery) -> impl IntoResponse {
+            if let Err(status) = signed(&headers, &query).await {
+                return (status, Json(json!({"code": -1022, "msg": "invalid signature"})));
+            }
+
+            (StatusCode::OK, Json(json!({"status": "FILLED", "executedQty": "1.00000000"})))
+        }
+
+        async fn account(headers: HeaderMap, RawQuery(query): RawQuery) -> impl IntoResponse {
+            if let Err(status) = signed(&headers, &query).await {
+                return (status, Json(json!({"code": -1022, "msg": "invalid signature"})));
+            }
+
+            let balances = json!([
+                {"asset": "SOL", "free": "2.50000000", "locked": "0.00000000"},
+                {"asset": "USDT", "free": "1000.00000000", "locked": "10.00000000"}
+            ]);
+            (StatusCode::OK, Json(json!({"balances": balances})))
+        }
+
+        Router::new()
+            .route("/api/v3/order", post(new_order).delete(cancel_order).get(query_order))
+            .route("/api/v3/account", axum::routing::get(account))
+            .route(
+                "/api/v3/exchangeInfo",
+                axum::routing::get(|| async {
+                    Json(
+                        json!({"symbols": [{"symbol": "SOLUSDT", "filters": [
+                            {"filterType": "PRICE_FILTER", "minPrice": "0.01000000", "maxPrice": "10000.00000000", "tickSize": "0.01000000"},
+                            {"filterType": "LOT_SIZE", "minQty": "0.00100000", "maxQty": "9000000.00000000", "stepSize": "0.00100000"},
+                            {"filterType": "MIN_NOTIONAL", "minNotional": "5.00000000"}
+                        ]}]})
+                    )
+                })
+            )
+            .route(
+                "/api/v3/userDataStream",
+                post(|| async { Json(json!({"listenKey": "test-listen-key"})) })
+            )
+    }
+
+    async fn start(secret_key: &str) -> BinanceVenue {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rest_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, mock_api()).await });
+
+        // sends the report to every user data stream connection
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let user_stream_url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut conn = accept_async(stream).await.unwrap();
+                conn.send(Message::Text(report().to_string())).await.unwrap();
+                tokio::spawn(async move { while conn.next().await.is_some() {} });
+            }
+        });
+
+        BinanceVenue::new(BinanceConfig {
+            rest_url,
+            user_stream_url,
+            api_key: API_KEY.to_string(),
+            secret_key: secret_key.to_string(),
+        })
+    }
+
+    fn order(quantity: Decimal) -> OrderRequest {
+        OrderRequest {
+            client_order_id: "arb-1-b".to_string(),
+            market: "solusdt".to_string(),
+            instrument: "SOL/USDT".parse::<Instrument>().unwrap(),
+            side: Side::Buy,
+            quantity,
+            limit_price: Some(dec!(100.50)),
+        }
+    }
+
+    fn report() -> serde_json::Value {
+        json!({
+            "e": "executionReport", "E": 1499405658658_u64, "s": "SOLUSDT", "c": "arb-1-b",
+            "S": "BUY", "o": "LIMIT", "f": "IOC", "q": "1.00000000", "p": "100.50000000",
+            "x": "TRADE", "X": "FILLED", "i": 28, "l": "1.00000000", "L": "100.40000000",
+            "n": "0.00100000", "N": "SOL", "T": 1499405658657_u64
+        })
+    }
+
+    #[test]
+    fn test_sign() {
+        // example of the binance api documentation
+        assert_eq!(
+            sign(
+                "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j",
+                "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559"
+            ),
+            "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_orders() {
+        let mut venue = start(SECRET_KEY).await;
+
+        let order_id = venue.place_order(order(dec!(1))).await.unwrap();
+        assert_eq!(order_id, "28");
+
+        // on the tick and step of the symbol, the buy limit rounded down
+        let unrounded = OrderRequest { limit_price: Some(dec!(100.567)), ..order(dec!(1.2345)) };
+        venue.place_order(unrounded).await.unwrap();
+        let query = ORDERS.lock().unwrap().last().cloned().unwrap();
+        assert!(query.contains("&quantity=1.234&") && query.contains("&price=100.56&"), "{query}");
+        let under_step = venue.place_order(order(dec!(0.0001))).await;
+        assert!(matches!(under_step, Err(ExecutionError::Rejected(_))));
+
+        assert_eq!(venue.order_state(&order_id).await.unwrap(), OrderState {
+            status: OrderStatus::Filled,
+            executed_quantity: dec!(1),
+        });
+        assert!(matches!(venue.cancel_order(&order_id).await, Err(ExecutionError::NotFound(_))));
+        assert!(matches!(venue.cancel_order("29").await, Err(ExecutionError::NotFound(_))));
+        assert!(
+            matches!(venue.place_order(order(dec!(100))).await, Err(ExecutionError::Rejected(_)))
+        );
+
+        let balances = venue.balances().await.unwrap();
+        assert_eq!(balances["SOL"], dec!(2.5));
+        assert_eq!(balances["USDT"], dec!(1000));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_signature() {
+        let mut venue = start("wrong-secret-key").await;
+
+        assert!(matches!(venue.balances().await, Err(ExecutionError::Rejected(_))));
+    }
+
+    #[tokio::test]
+    async fn test_user_stream() {
+        let venue = start(SECRET_KEY).await;
+        let mut fills = venue.fills();
+
+        let fill = timeout(Duration::from_secs(5), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.order_id, "28");
+        assert_eq!(fill.client_order_id, "arb-1-b");
+        assert_eq!(fill.side, Side::Buy);
+        assert_eq!(fill.quantity, dec!(1));
+        assert_eq!(fill.price, dec!(100.4));
+        assert_eq!(fill.fee, dec!(0.1004));
+    }
+
+    #[test]
+    fn test_parse_execution_report() {
+        let mut report = report();
+        report["x"] = json!("NEW");
+        assert_eq!(parse_execution_report(&report.to_string()).unwrap(), None);
+
+        let account = json!({"e": "outboundAccountPosition", "E": 1564034571105_u64});
+        assert_eq!(parse_execution_report(&account.to_string()).unwrap(), None);
+    }
+}
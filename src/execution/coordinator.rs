@@ -0,0 +1,821 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    time::{ Duration, SystemTime, UNIX_EPOCH },
+};
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use tokio::time::{ sleep, Instant };
+
+use crate::{
+    config::{ self, ConfigError },
+    journal::Journal,
+    markets::{ base36, Opportunity },
+    Venue,
+};
+
+use super::{
+    risk::Risk,
+    ExecutionError,
+    ExecutionVenue,
+    OrderRequest,
+    OrderState,
+    OrderStatus,
+    Side,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// given to a leg its venue cannot cancel, such as a swap, past the expiry of its blockhash
+const UNCANCELABLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What to do with the base left over when one leg filled more than the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegFailurePolicy {
+    // completes the short leg on its own venue
+    Hedge,
+    // reverses the excess on the venue of the leg that filled it
+    Unwind,
+    // keeps the position
+    Hold,
+}
+
+impl FromStr for LegFailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hedge" => Ok(LegFailurePolicy::Hedge),
+            "unwind" => Ok(LegFailurePolicy::Unwind),
+            "hold" => Ok(LegFailurePolicy::Hold),
+            _ => Err(format!("unknown policy {s}, expected hedge, unwind or hold")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CoordinatorConfig {
+    pub policy: LegFailurePolicy,
+    // time given to the legs before what is left of them is canceled
+    pub leg_timeout: Duration,
+    // accepted move of each leg from the price it was seen at
+    pub leg_slippage_bps: Decimal,
+    // left between the leg limits, the trade refused otherwise
+    pub min_profit_bps: Decimal,
+    // accepted loss on a hedge or unwind, from the price of the opportunity
+    pub slippage_bps: Decimal,
+}
+
+impl CoordinatorConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(CoordinatorConfig {
+            policy: config
+                ::env_parse("TRADE_LEG_FAILURE_POLICY")?
+                .unwrap_or(LegFailurePolicy::Hedge),
+            leg_timeout: Duration::from_millis(
+                config::env_parse("TRADE_LEG_TIMEOUT_MS")?.unwrap_or(5000)
+            ),
+            leg_slippage_bps: config::env_parse("TRADE_LEG_SLIPPAGE_BPS")?.unwrap_or(dec!(5)),
+            min_profit_bps: config::env_parse("TRADE_MIN_PROFIT_BPS")?.unwrap_or(dec!(0)),
+            slippage_bps: config::env_parse("TRADE_HEDGE_SLIPPAGE_BPS")?.unwrap_or(dec!(20)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArbitrageState {
+    Placing,
+    Working,
+    // base bought minus sold being closed on the other leg venue
+    Hedging(Decimal),
+    // or on the venue that filled it
+    Unwinding(Decimal),
+    // as much base bought as sold, none when both legs failed
+    Flat,
+    // base bought minus sold left open
+    Exposed(Decimal),
+}
+
+impl fmt::Display for ArbitrageState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArbitrageState::Placing => write!(f, "placing"),
+            ArbitrageState::Working => write!(f, "working"),
+            ArbitrageState::Hedging(position) => write!(f, "hedging {position}"),
+            ArbitrageState::Unwinding(position) => write!(f, "unwinding {position}"),
+            ArbitrageState::Flat => write!(f, "flat"),
+            ArbitrageState::Exposed(position) => write!(f, "exposed {position}"),
+        }
+    }
+}
+
+/// Order of one side of the trade, not placed when refused by its venue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leg {
+    pub exchange_id: &'static str,
+    pub order_id: Option<String>,
+    pub state: OrderState,
+}
+
+impl Leg {
+    fn new(exchange_id: &'static str, placed: Result<String, ExecutionError>) -> Self {
+        let order_id = placed
+            .inspect_err(|err| log::warn!("{exchange_id} leg not placed {err}"))
+            .ok();
+        let status = if order_id.is_some() { OrderStatus::Open } else { OrderStatus::Expired };
+
+        let state = OrderState { status, executed_quantity: Decimal::ZERO };
+        Leg { exchange_id, order_id, state }
+    }
+}
+
+/// Outcome of an opportunity traded, with the states it went through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Execution {
+    pub buy: Leg,
+    pub sell: Leg,
+    pub transitions: Vec<ArbitrageState>,
+}
+
+impl Execution {
+    pub fn state(&self) -> ArbitrageState {
+        *self.transitions.last().unwrap()
+    }
+}
+
+/// Trades opportunities on both their venues at once, then closes whatever one leg
+/// filled more than the other as set by the policy.
+pub struct Coordinator<V> {
+    venues: HashMap<&'static str, V>,
+    config: CoordinatorConfig,
+    risk: Risk,
+    journal: Journal,
+}
+
+impl<V: ExecutionVenue> Coordinator<V> {
+    pub fn new(
+        venues: HashMap<&'static str, V>,
+        config: CoordinatorConfig,
+        risk: Risk,
+        journal: Journal
+    ) -> Self {
+        Self { venues, config, risk, journal }
+    }
+
+    pub fn venues_mut(&mut self) -> &mut HashMap<&'static str, V> {
+        &mut self.venues
+    }
+
+    /// Buys `quantity` of the opportunity base on its cheapest venue and sells it on its
+    /// dearest, each limited to its own price give or take the leg slippage. Errors when
+    /// the limits leave less than the minimum profit, the legs fail the risk checks or
+    /// neither could be placed.
+    pub async fn execute(
+        &mut self,
+        opportunity: &Opportunity,
+        quantity: Decimal
+    ) -> Result<Execution, ExecutionError> {
+        let (id, trade_id) = (opportunity.id(), opportunity.trade_id());
+
+        // priced in the quote of the venue
+        let order = |side, venue: &Venue, limit_price| {
+            let (instrument, limit_price) = opportunity.native(venue, limit_price);
+            let leg = match side {
+                Side::Buy => "b",
+                Side::Sell => "s",
+            };
+            OrderRequest {
+                client_order_id: format!("{trade_id}-{leg}"),
+                market: venue.market.clone(),
+                instrument,
+                side,
+                quantity,
+                limit_price: Some(limit_price),
+            }
+        };
+
+        let leg_slippage = self.config.leg_slippage_bps / Decimal::from(10_000);
+        let buy_limit = opportunity.buy_price * (Decimal::ONE + leg_slippage);
+        let sell_limit = opportunity.sell_price * (Decimal::ONE - leg_slippage);
+
+        let profit_bps = ((sell_limit - buy_limit) / buy_limit) * Decimal::from(10_000);
+        if profit_bps < self.config.min_profit_bps {
+            return Err(
+                ExecutionError::Rejected(
+                    format!("limits {buy_limit} to {sell_limit} under the minimum profit")
+                )
+            );
+        }
+
+        let (buy, sell) = (&opportunity.buy_venue, &opportunity.sell_venue);
+        let buy_order = order(Side::Buy, buy, buy_limit);
+        let sell_order = order(Side::Sell, sell, sell_limit);
+
+        self.risk.check(&[(buy.exchange_id, &buy_order), (sell.exchange_id, &sell_order)])?;
+
+        let mut transitions = vec![];
+        transition(&id, &mut transitions, ArbitrageState::Placing);
+
+        let (buy_placed, sell_placed) = if buy.exchange_id == sell.exchange_id {
+            // two markets of one venue, which cannot take both at once
+            let venue = self.venue(buy.exchange_id)?;
+            (
+                venue.place_order(buy_order.clone()).await,
+                venue.place_order(sell_order.clone()).await,
+            )
+        } else {
+            let [Some(buy_venue), Some(sell_venue)] = self.venues.get_disjoint_mut([
+                buy.exchange_id,
+                sell.exchange_id,
+            ]) else {
+                let exchange_ids = format!("{} or {}", buy.exchange_id, sell.exchange_id);
+                return Err(ExecutionError::NotFound(exchange_ids));
+            };
+            tokio::join!(
+                buy_venue.place_order(buy_order.clone()),
+                sell_venue.place_order(sell_order.clone())
+            )
+        };
+
+        if let (Err(err), Err(_)) = (&buy_placed, &sell_placed) {
+            return Err(err.clone());
+        }
+
+        let mut buy_leg = Leg::new(buy.exchange_id, buy_placed);
+        let mut sell_leg = Leg::new(sell.exchange_id, sell_placed);
+
+        transition(&id, &mut transitions, ArbitrageState::Working);
+
+        // a leg alone is canceled right away, its fill only to be closed
+        let deadline = if buy_leg.order_id.is_none() || sell_leg.order_id.is_none() {
+            Instant::now()
+        } else {
+            Instant::now() + self.config.leg_timeout
+        };
+        self.settle(&mut buy_leg, deadline).await;
+        self.settle(&mut sell_leg, deadline).await;
+        self.journal.order(&buy_order, &buy_leg);
+        self.journal.order(&sell_order, &sell_leg);
+
+        let position = buy_leg.state.executed_quantity - sell_leg.state.executed_quantity;
+        if position.is_zero() {
+            transition(&id, &mut transitions, ArbitrageState::Flat);
+            return Ok(Execution { buy: buy_leg, sell: sell_leg, transitions });
+        }
+
+        let policy = self.config.policy;
+        let (side, venue) = match (position.is_sign_positive(), policy) {
+            (_, LegFailurePolicy::Hold) => {
+                transition(&id, &mut transitions, ArbitrageState::Exposed(position));
+                return Ok(Execution { buy: buy_leg, sell: sell_leg, transitions });
+            }
+            (true, LegFailurePolicy::Hedge) => (Side::Sell, sell),
+            (true, LegFailurePolicy::Unwind) => (Side::Sell, buy),
+            (false, LegFailurePolicy::Hedge) => (Side::Buy, buy),
+            (false, LegFailurePolicy::Unwind) => (Side::Buy, sell),
+        };
+
+        // closed for what the base was bought or sold at, give or take the slippage
+        let slippage = self.config.slippage_bps / Decimal::from(10_000);
+        let limit_price = match side {
+            Side::Buy => opportunity.sell_price * (Decimal::ONE + slippage),
+            Side::Sell => opportunity.buy_price * (Decimal::ONE - slippage),
+        };
+
+        let closing = if policy == LegFailurePolicy::Hedge {
+            ArbitrageState::Hedging(position)
+        } else {
+            ArbitrageState::Unwinding(position)
+        };
+        transition(&id, &mut transitions, closing);
+
+        // not checked, it only takes risk off
+        let close_order = OrderRequest {
+            client_order_id: format!("{trade_id}-c"),
+            quantity: position.abs(),
+            ..order(side, venue, limit_price)
+        };
+        let placed = self.venue(venue.exchange_id)?.place_order(close_order.clone()).await;
+
+        let mut close_leg = Leg::new(venue.exchange_id, placed);
+        self.settle(&mut close_leg, Instant::now() + self.config.leg_timeout).await;
+        self.journal.order(&close_order, &close_leg);
+
+        let closed = close_leg.state.executed_quantity;
+        let position = match side {
+            Side::Buy => position + closed,
+            Side::Sell => position - closed,
+        };
+
+        let state = if position.is_zero() {
+            ArbitrageState::Flat
+        } else {
+            ArbitrageState::Exposed(position)
+        };
+        transition(&id, &mut transitions, state);
+
+        Ok(Execution { buy: buy_leg, sell: sell_leg, transitions })
+    }
+
+    /// Closes the positions left by the trades at about the consensus price, give or take
+    /// the slippage.
+    pub async fn flatten(&mut self) {
+        let slippage = self.config.slippage_bps / Decimal::from(10_000);
+        let flattened_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+
+        let positions = self.risk.positions().into_iter().enumerate();
+        for (index, (venue, instrument, position)) in positions {
+            let Some(price) = self.risk.consensus_price(&venue) else {
+                log::warn!("{venue} cannot flatten {position} without a price");
+                continue;
+            };
+
+            let (side, limit_price) = if position.is_sign_positive() {
+                (Side::Sell, price * (Decimal::ONE - slippage))
+            } else {
+                (Side::Buy, price * (Decimal::ONE + slippage))
+            };
+
+            let order = OrderRequest {
+                // within the 18 characters kraken takes
+                client_order_id: format!(
+                    "flatten-{}-{}",
+                    base36(flattened_at_ms, 8),
+                    base36((index % 36) as u128, 1)
+                ),
+                market: venue.market.clone(),
+                instrument,
+                side,
+                quantity: position.abs(),
+                limit_price: Some(limit_price),
+            };
+
+            let placed = match self.venue(venue.exchange_id) {
+                Ok(trading_venue) => trading_venue.place_order(order.clone()).await,
+                Err(err) => Err(err),
+            };
+
+            let mut leg = Leg::new(venue.exchange_id, placed);
+            self.settle(&mut leg, Instant::now() + self.config.leg_timeout).await;
+            self.journal.order(&order, &leg);
+
+            log::info!("{venue} flattened {} of {position}", leg.state.executed_quantity);
+        }
+    }
+
+    fn venue(&mut self, exchange_id: &str) -> Result<&mut V, ExecutionError> {
+        self.venues.get_mut(exchange_id).ok_or(ExecutionError::NotFound(exchange_id.to_string()))
+    }
+
+    // follows the leg until it is done, what is left of it canceled after the deadline or,
+    // when it cannot be, followed until it is done anyway
+    async fn settle(&mut self, leg: &mut Leg, deadline: Instant) {
+        let Some(order_id) = leg.order_id.clone() else {
+            return;
+        };
+        let Ok(venue) = self.venue(leg.exchange_id) else {
+            return;
+        };
+
+        let mut canceled = false;
+        let mut uncancelable_deadline = None;
+        loop {
+            match venue.order_state(&order_id).await {
+                Ok(state) => {
+                    leg.state = state;
+                }
+                Err(err) => log::debug!("{} {order_id} {err}", leg.exchange_id),
+            }
+
+            if leg.state.status != OrderStatus::Open || canceled {
+                return;
+            }
+
+            match uncancelable_deadline {
+                None if Instant::now() >= deadline => {
+                    match venue.cancel_order(&order_id).await {
+                        // read once more for what filled before the cancel
+                        Ok(()) => {
+                            canceled = true;
+                            continue;
+                        }
+                        Err(err) => {
+                            log::warn!("{} cannot cancel {order_id} {err}", leg.exchange_id);
+                            uncancelable_deadline = Some(Instant::now() + UNCANCELABLE_TIMEOUT);
+                        }
+                    }
+                }
+                Some(uncancelable_deadline) if Instant::now() >= uncancelable_deadline => {
+                    log::error!("{} {order_id} still open, taken as is", leg.exchange_id);
+                    return;
+                }
+                _ => {}
+            }
+
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+fn transition(id: &str, transitions: &mut Vec<ArbitrageState>, state: ArbitrageState) {
+    match transitions.last() {
+        Some(previous) => log::info!("{id} {previous} -> {state}"),
+        None => log::info!("{id} {state}"),
+    }
+    transitions.push(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ collections::BTreeMap, sync::{ atomic::{ AtomicBool, Ordering }, Arc, RwLock } };
+
+    use mockall::{ mock, predicate::eq };
+    use tokio::sync::broadcast;
+
+    use crate::{
+        execution::{ paper::{ PaperConfig, PaperVenue }, Fill },
+        markets::{ Markets, SharedMarkets },
+        Instrument,
+        Venue,
+    };
+
+    use super::{ super::risk::{ KillSwitch, RiskLimits }, * };
+
+    mock! {
+        Venue {}
+        impl ExecutionVenue for Venue {
+            async fn place_order(&mut self, order: OrderRequest) -> Result<String, ExecutionError>;
+            async fn cancel_order(&mut self, order_id: &str) -> Result<(), ExecutionError>;
+            async fn order_state(&mut self, order_id: &str) -> Result<OrderState, ExecutionError>;
+            async fn balances(&mut self) -> Result<BTreeMap<String, Decimal>, ExecutionError>;
+            fn fills(&self) -> broadcast::Receiver<Fill>;
+        }
+    }
+
+    // bought on binance at 100 and sold on kraken at 101
+    fn markets() -> Markets {
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+        let binance = Venue { exchange_id: "binance", market: "SOLUSDT".to_string() };
+        let kraken = Venue { exchange_id: "kraken", market: "SOL/USDT".to_string() };
+
+        let mut markets = Markets::new(dec!(10));
+        markets.update(sol.clone(), binance, dec!(100));
+        markets.update(sol, kraken, dec!(101));
+        markets
+    }
+
+    fn opportunity() -> Opportunity {
+        markets().opportunities().next().cloned().unwrap()
+    }
+
+    fn coordinator(
+        binance: MockVenue,
+        kraken: MockVenue,
+        policy: LegFailurePolicy
+    ) -> Coordinator<MockVenue> {
+        let config = CoordinatorConfig {
+            policy,
+            leg_timeout: Duration::from_millis(200),
+            leg_slippage_bps: dec!(10),
+            min_profit_bps: dec!(50),
+            slippage_bps: dec!(50),
+        };
+        let venues = HashMap::from([("binance", binance), ("kraken", kraken)]);
+        Coordinator::new(venues, config, risk(KillSwitch::default()), Journal::default())
+    }
+
+    fn risk(kill_switch: KillSwitch) -> Risk {
+        Risk::new(RiskLimits::default(), Arc::new(RwLock::new(markets())), kill_switch)
+    }
+
+    fn filled(executed_quantity: Decimal) -> Result<OrderState, ExecutionError> {
+        Ok(OrderState { status: OrderStatus::Filled, executed_quantity })
+    }
+
+    fn expired(executed_quantity: Decimal) -> Result<OrderState, ExecutionError> {
+        Ok(OrderState { status: OrderStatus::Expired, executed_quantity })
+    }
+
+    #[tokio::test]
+    async fn test_flat() {
+        // client order ids within the 18 characters kraken takes
+        let opportunity = opportunity();
+        let trade_id = opportunity.trade_id();
+        assert!(trade_id.starts_with("arb-") && trade_id.len() == 15, "{trade_id}");
+
+        let buy_id = format!("{trade_id}-b");
+        let mut binance = MockVenue::new();
+        binance
+            .expect_place_order()
+            .withf(move |order| {
+                order.side == Side::Buy &&
+                    order.limit_price == Some(dec!(100.1)) &&
+                    order.client_order_id == buy_id
+            })
+            .once()
+            .returning(|_| Ok("buy".to_string()));
+        binance.expect_order_state().with(eq("buy")).returning(|_| filled(dec!(1)));
+
+        let sell_id = format!("{trade_id}-s");
+        let mut kraken = MockVenue::new();
+        kraken
+            .expect_place_order()
+            .withf(move |order| {
+                order.side == Side::Sell &&
+                    order.limit_price == Some(dec!(100.899)) &&
+                    order.client_order_id == sell_id
+            })
+            .once()
+            .returning(|_| Ok("sell".to_string()));
+        kraken.expect_order_state().with(eq("sell")).returning(|_| filled(dec!(1)));
+
+        let mut coordinator = coordinator(binance, kraken, LegFailurePolicy::Hedge);
+        let execution = coordinator.execute(&opportunity, dec!(1)).await.unwrap();
+
+        use ArbitrageState::*;
+        assert_eq!(execution.transitions, vec![Placing, Working, Flat]);
+        assert_eq!(execution.sell.order_id.as_deref(), Some("sell"));
+    }
+
+    #[tokio::test]
+    async fn test_under_min_profit() {
+        // 100.3 to 100.697 once each leg may move 30 bps, under the 50 bps minimum
+        let policy = LegFailurePolicy::Hedge;
+        let mut coordinator = coordinator(MockVenue::new(), MockVenue::new(), policy);
+        coordinator.config.leg_slippage_bps = dec!(30);
+
+        let result = coordinator.execute(&opportunity(), dec!(1)).await;
+        assert!(matches!(result, Err(ExecutionError::Rejected(_))));
+    }
+
+    #[tokio::test]
+    async fn test_hedge_partial_fill() {
+        let mut binance = MockVenue::new();
+        binance.expect_place_order().once().returning(|_| Ok("buy".to_string()));
+        binance.expect_order_state().returning(|_| filled(dec!(1)));
+
+        // the sell partially filled, the rest sold again at most 50 bps under 100
+        let mut kraken = MockVenue::new();
+        kraken
+            .expect_place_order()
+            .withf(|order| order.client_order_id.ends_with("-s"))
+            .once()
+            .returning(|_| Ok("sell".to_string()));
+        kraken.expect_order_state().with(eq("sell")).returning(|_| expired(dec!(0.4)));
+        kraken
+            .expect_place_order()
+            .withf(|order| {
+                order.side == Side::Sell &&
+                    order.quantity == dec!(0.6) &&
+                    order.limit_price == Some(dec!(99.5))
+            })
+            .once()
+            .returning(|_| Ok("hedge".to_string()));
+        kraken.expect_order_state().with(eq("hedge")).returning(|_| filled(dec!(0.6)));
+
+        let mut coordinator = coordinator(binance, kraken, LegFailurePolicy::Hedge);
+        let execution = coordinator.execute(&opportunity(), dec!(1)).await.unwrap();
+
+        use ArbitrageState::*;
+        assert_eq!(execution.transitions, vec![Placing, Working, Hedging(dec!(0.6)), Flat]);
+        assert_eq!(execution.sell.state.executed_quantity, dec!(0.4));
+    }
+
+    #[tokio::test]
+    async fn test_unwind_rejected_leg() {
+        // the buy filled before it could be canceled, then sold back on binance
+        let mut binance = MockVenue::new();
+        binance
+            .expect_place_order()
+            .withf(|order| order.side == Side::Buy)
+            .once()
+            .returning(|_| Ok("buy".to_string()));
+        binance.expect_order_state().with(eq("buy")).returning(|_| filled(dec!(1)));
+        binance
+            .expect_place_order()
+            .withf(|order| order.side == Side::Sell && order.limit_price == Some(dec!(99.5)))
+            .once()
+            .returning(|_| Ok("unwind".to_string()));
+        binance.expect_order_state().with(eq("unwind")).returning(|_| expired(dec!(0.7)));
+
+        let mut kraken = MockVenue::new();
+        kraken
+            .expect_place_order()
+            .once()
+            .returning(|_| Err(ExecutionError::Rejected("insufficient funds".to_string())));
+
+        let mut coordinator = coordinator(binance, kraken, LegFailurePolicy::Unwind);
+        let execution = coordinator.execute(&opportunity(), dec!(1)).await.unwrap();
+
+        use ArbitrageState::*;
+        assert_eq!(
+            execution.transitions,
+            vec![Placing, Working, Unwinding(dec!(1)), Exposed(dec!(0.3))]
+        );
+        assert_eq!(execution.sell.order_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_hold_after_timeout() {
+        // the buy rests until canceled at the timeout, half filled
+        let mut binance = MockVenue::new();
+        binance.expect_place_order().once().returning(|_| Ok("buy".to_string()));
+
+        let canceled = Arc::new(AtomicBool::new(false));
+        let cancel = canceled.clone();
+        binance
+            .expect_cancel_order()
+            .with(eq("buy"))
+            .once()
+            .returning(move |_| {
+                cancel.store(true, Ordering::SeqCst);
+                Ok(())
+            });
+        binance.expect_order_state().returning(move |_| {
+            let status = if canceled.load(Ordering::SeqCst) {
+                OrderStatus::Canceled
+            } else {
+                OrderStatus::Open
+            };
+            Ok(OrderState { status, executed_quantity: dec!(0.5) })
+        });
+
+        let mut kraken = MockVenue::new();
+        kraken.expect_place_order().once().returning(|_| Ok("sell".to_string()));
+        kraken.expect_order_state().returning(|_| filled(dec!(1)));
+
+        let mut coordinator = coordinator(binance, kraken, LegFailurePolicy::Hold);
+        let execution = coordinator.execute(&opportunity(), dec!(1)).await.unwrap();
+
+        use ArbitrageState::*;
+        assert_eq!(execution.transitions, vec![Placing, Working, Exposed(dec!(-0.5))]);
+        assert_eq!(execution.buy.state.status, OrderStatus::Canceled);
+    }
+
+    #[tokio::test]
+    async fn test_uncancelable_leg() {
+        let mut binance = MockVenue::new();
+        binance.expect_place_order().once().returning(|_| Ok("buy".to_string()));
+        binance.expect_order_state().returning(|_| filled(dec!(1)));
+
+        // the sell, as a swap would, lands after the timeout and cannot be canceled
+        let landed_at = Instant::now() + Duration::from_millis(500);
+        let mut kraken = MockVenue::new();
+        kraken.expect_place_order().once().returning(|_| Ok("sell".to_string()));
+        kraken
+            .expect_cancel_order()
+            .returning(|_| Err(ExecutionError::Rejected("cannot be canceled".to_string())));
+        kraken.expect_order_state().returning(move |_| {
+            if Instant::now() < landed_at {
+                Ok(OrderState { status: OrderStatus::Open, executed_quantity: dec!(0) })
+            } else {
+                filled(dec!(1))
+            }
+        });
+
+        let mut coordinator = coordinator(binance, kraken, LegFailurePolicy::Hedge);
+        let execution = coordinator.execute(&opportunity(), dec!(1)).await.unwrap();
+
+        // not hedged as if the sell never filled
+        use ArbitrageState::*;
+        assert_eq!(execution.transitions, vec![Placing, Working, Flat]);
+        assert_eq!(execution.sell.state.status, OrderStatus::Filled);
+    }
+
+    #[tokio::test]
+    async fn test_both_legs_rejected() {
+        let mut binance = MockVenue::new();
+        binance
+            .expect_place_order()
+            .returning(|_| Err(ExecutionError::Transport("timeout".to_string())));
+
+        let mut kraken = MockVenue::new();
+        kraken
+            .expect_place_order()
+            .returning(|_| Err(ExecutionError::Rejected("insufficient funds".to_string())));
+
+        let mut coordinator = coordinator(binance, kraken, LegFailurePolicy::Hedge);
+        assert_eq!(
+            coordinator.execute(&opportunity(), dec!(1)).await,
+            Err(ExecutionError::Transport("timeout".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch() {
+        // neither venue expects an order
+        let policy = LegFailurePolicy::Hedge;
+        let mut coordinator = coordinator(MockVenue::new(), MockVenue::new(), policy);
+        let kill_switch = KillSwitch::default();
+        coordinator.risk = risk(kill_switch.clone());
+        kill_switch.engage("test");
+
+        assert_eq!(
+            coordinator.execute(&opportunity(), dec!(1)).await,
+            Err(ExecutionError::Rejected("kill switch engaged".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flatten() {
+        // sold back at the consensus of 100.5 less 50 bps
+        let mut binance = MockVenue::new();
+        binance
+            .expect_place_order()
+            .withf(|order| {
+                order.side == Side::Sell &&
+                    order.quantity == dec!(2) &&
+                    order.limit_price == Some(dec!(99.9975)) &&
+                    order.client_order_id.starts_with("flatten-") &&
+                    order.client_order_id.len() == 18
+            })
+            .once()
+            .returning(|_| Ok("flatten".to_string()));
+        binance.expect_order_state().returning(|_| filled(dec!(2)));
+
+        let mut coordinator = coordinator(binance, MockVenue::new(), LegFailurePolicy::Hedge);
+        coordinator.risk.record_fill(&Fill {
+            exchange_id: "binance",
+            order_id: "buy".to_string(),
+            client_order_id: "arb-1-b".to_string(),
+            market: "SOLUSDT".to_string(),
+            side: Side::Buy,
+            quantity: dec!(2),
+            price: dec!(100),
+            fee: dec!(0),
+            timestamp: std::time::SystemTime::now(),
+        });
+
+        coordinator.flatten().await;
+    }
+
+    #[tokio::test]
+    async fn test_paper_venues() {
+        let markets: SharedMarkets = Arc::new(RwLock::new(markets()));
+        let opportunity = opportunity();
+
+        let venue = |exchange_id, balances: &[(&str, Decimal)]| {
+            let config = PaperConfig {
+                latency: Duration::from_millis(50),
+                slippage_bps: dec!(0),
+                fee_rate: dec!(0),
+                balances: balances
+                    .iter()
+                    .map(|(asset, amount)| (asset.to_string(), *amount))
+                    .collect(),
+            };
+            PaperVenue::new(exchange_id, markets.clone(), config)
+        };
+        let config = CoordinatorConfig {
+            policy: LegFailurePolicy::Hedge,
+            leg_timeout: Duration::from_millis(500),
+            leg_slippage_bps: dec!(0),
+            min_profit_bps: dec!(0),
+            slippage_bps: dec!(0),
+        };
+
+        let risk = Risk::new(RiskLimits::default(), markets.clone(), KillSwitch::default());
+
+        // no SOL to sell on kraken, so the buy is canceled before it fills
+        let mut coordinator = Coordinator::new(
+            HashMap::from([
+                ("binance", venue("binance", &[("USDT", dec!(1000))])),
+                ("kraken", venue("kraken", &[])),
+            ]),
+            config.clone(),
+            risk.clone(),
+            Journal::default()
+        );
+
+        let execution = coordinator.execute(&opportunity, dec!(1)).await.unwrap();
+        assert_eq!(execution.state(), ArbitrageState::Flat);
+        assert_eq!(execution.buy.state.status, OrderStatus::Canceled);
+
+        let mut coordinator = Coordinator::new(
+            HashMap::from([
+                ("binance", venue("binance", &[("USDT", dec!(1000))])),
+                ("kraken", venue("kraken", &[("SOL", dec!(1))])),
+            ]),
+            config,
+            risk,
+            Journal::default()
+        );
+
+        let execution = coordinator.execute(&opportunity, dec!(1)).await.unwrap();
+        assert_eq!(execution.state(), ArbitrageState::Flat);
+        assert_eq!(execution.sell.state.executed_quantity, dec!(1));
+
+        let kraken = coordinator.venues_mut().get_mut("kraken").unwrap();
+        assert_eq!(kraken.balances().await.unwrap()["USDT"], dec!(101));
+    }
+
+    #[test]
+    fn test_policy() {
+        assert_eq!("unwind".parse(), Ok(LegFailurePolicy::Unwind));
+        assert!("close".parse::<LegFailurePolicy>().is_err());
+    }
+}
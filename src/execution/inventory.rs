@@ -0,0 +1,224 @@
+use std::{ collections::{ BTreeMap, BTreeSet }, sync::{ Arc, Mutex } };
+
+use rust_decimal::Decimal;
+
+use crate::{ markets::{ Opportunity, SharedMarkets }, metrics, Venue };
+
+use super::{ Fill, Side };
+
+/// Balances of every venue per asset, moved by the fills in between reconciliations with
+/// what the venues report.
+#[derive(Clone)]
+pub struct Inventory {
+    markets: SharedMarkets,
+    state: Arc<Mutex<InventoryState>>,
+}
+
+#[derive(Default)]
+struct InventoryState {
+    balances: BTreeMap<&'static str, BTreeMap<String, Decimal>>,
+    // venues and assets short for the trade quantity
+    short: BTreeSet<(&'static str, String)>,
+}
+
+impl Inventory {
+    pub fn new(markets: SharedMarkets) -> Self {
+        Self { markets, state: Default::default() }
+    }
+
+    pub fn record_fill(&self, fill: &Fill) {
+        let venue = Venue { exchange_id: fill.exchange_id, market: fill.market.clone() };
+        let instrument = self.markets.read().unwrap().native_instrument(&venue).cloned();
+        let Some(instrument) = instrument else {
+            log::warn!("{venue} fill {} of an unknown market", fill.order_id);
+            return;
+        };
+
+        let (base, quote) = match fill.side {
+            Side::Buy => (fill.quantity, -fill.quantity * fill.price),
+            Side::Sell => (-fill.quantity, fill.quantity * fill.price),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let balances = state.balances.entry(fill.exchange_id).or_default();
+        for (asset, change) in [(instrument.base, base), (instrument.quote, quote - fill.fee)] {
+            let balance = balances.entry(asset.clone()).or_default();
+            *balance += change;
+            metrics::inventory(fill.exchange_id, &asset, *balance);
+        }
+    }
+
+    /// Replaces what was tracked of the venue by what it reports.
+    pub fn reconcile(&self, exchange_id: &'static str, reported: BTreeMap<String, Decimal>) {
+        let mut state = self.state.lock().unwrap();
+        let tracked = state.balances.insert(exchange_id, reported.clone()).unwrap_or_default();
+
+        // missed or misread fills, or transfers
+        let assets = tracked.keys().chain(reported.keys()).collect::<BTreeSet<_>>();
+        for asset in assets {
+            let (tracked, reported) = (
+                tracked.get(asset).copied().unwrap_or_default(),
+                reported.get(asset).copied().unwrap_or_default(),
+            );
+
+            if tracked != reported {
+                log::info!("{exchange_id} {asset} tracked {tracked} reported {reported}");
+            }
+            metrics::inventory(exchange_id, asset, reported);
+        }
+    }
+
+    pub fn balance(&self, exchange_id: &str, asset: &str) -> Decimal {
+        let state = self.state.lock().unwrap();
+        state.balances
+            .get(exchange_id)
+            .and_then(|balances| balances.get(asset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The most base the opportunity can trade, bought with the quote held on its buy venue
+    /// and sold from the base held on its sell venue.
+    pub fn tradable_quantity(&self, opportunity: &Opportunity) -> Decimal {
+        let (instrument, limit) = opportunity
+            .native(&opportunity.buy_venue, opportunity.sell_price);
+
+        let quote = self.balance(opportunity.buy_venue.exchange_id, &instrument.quote);
+        let base = self.balance(opportunity.sell_venue.exchange_id, &instrument.base);
+
+        (quote / limit).min(base).max(Decimal::ZERO)
+    }
+
+    /// Flags the venues of the opportunity holding too little to trade `quantity`, returning
+    /// those newly short.
+    pub fn flag_rebalancing(
+        &self,
+        opportunity: &Opportunity,
+        quantity: Decimal
+    ) -> Vec<(&'static str, String)> {
+        let (instrument, limit) = opportunity
+            .native(&opportunity.buy_venue, opportunity.sell_price);
+        let (buy, sell) = (opportunity.buy_venue.exchange_id, opportunity.sell_venue.exchange_id);
+
+        let needs = [
+            (buy, &instrument.quote, quantity * limit),
+            (sell, &instrument.base, quantity),
+        ];
+        let needs = needs.map(|(exchange_id, asset, need)| {
+            (exchange_id, asset.clone(), self.balance(exchange_id, asset) < need)
+        });
+
+        let mut state = self.state.lock().unwrap();
+        let mut newly_short = vec![];
+
+        for (exchange_id, asset, short) in needs {
+            metrics::rebalance_needed(exchange_id, &asset, short);
+
+            let key = (exchange_id, asset);
+            if !short {
+                state.short.remove(&key);
+            } else if state.short.insert(key.clone()) {
+                newly_short.push(key);
+            }
+        }
+
+        newly_short
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ sync::RwLock, time::SystemTime };
+
+    use rust_decimal_macros::dec;
+
+    use crate::{ markets::Markets, Instrument };
+
+    use super::*;
+
+    fn venue(exchange_id: &'static str) -> Venue {
+        Venue { exchange_id, market: "SOL/USDT".to_string() }
+    }
+
+    // bought on binance at 100 and sold on kraken at 101
+    fn inventory() -> (Inventory, Opportunity) {
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+
+        let mut markets = Markets::new(dec!(10));
+        markets.update(sol.clone(), venue("binance"), dec!(100));
+        markets.update(sol, venue("kraken"), dec!(101));
+        let opportunity = markets.opportunities().next().cloned().unwrap();
+
+        (Inventory::new(Arc::new(RwLock::new(markets))), opportunity)
+    }
+
+    fn fill(side: Side, quantity: Decimal, price: Decimal) -> Fill {
+        Fill {
+            exchange_id: "binance",
+            order_id: "1".to_string(),
+            client_order_id: "arb-1-b".to_string(),
+            market: "SOL/USDT".to_string(),
+            side,
+            quantity,
+            price,
+            fee: dec!(0.1),
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_fills_and_reconcile() {
+        let (inventory, _) = inventory();
+        inventory.reconcile("binance", BTreeMap::from([("USDT".to_string(), dec!(1000))]));
+
+        inventory.record_fill(&fill(Side::Buy, dec!(2), dec!(100)));
+        assert_eq!(inventory.balance("binance", "SOL"), dec!(2));
+        assert_eq!(inventory.balance("binance", "USDT"), dec!(799.9));
+
+        inventory.record_fill(&fill(Side::Sell, dec!(1), dec!(101)));
+        assert_eq!(inventory.balance("binance", "SOL"), dec!(1));
+        assert_eq!(inventory.balance("binance", "USDT"), dec!(900.8));
+
+        // the venue knows better
+        inventory.reconcile("binance", BTreeMap::from([("SOL".to_string(), dec!(1.5))]));
+        assert_eq!(inventory.balance("binance", "SOL"), dec!(1.5));
+        assert_eq!(inventory.balance("binance", "USDT"), dec!(0));
+    }
+
+    #[test]
+    fn test_tradable_quantity() {
+        let (inventory, opportunity) = inventory();
+        assert_eq!(inventory.tradable_quantity(&opportunity), dec!(0));
+
+        // the quote buys 5 at the 101 limit, 3 can be sold
+        inventory.reconcile("binance", BTreeMap::from([("USDT".to_string(), dec!(505))]));
+        inventory.reconcile("kraken", BTreeMap::from([("SOL".to_string(), dec!(3))]));
+        assert_eq!(inventory.tradable_quantity(&opportunity), dec!(3));
+
+        inventory.reconcile("kraken", BTreeMap::from([("SOL".to_string(), dec!(30))]));
+        assert_eq!(inventory.tradable_quantity(&opportunity), dec!(5));
+    }
+
+    #[test]
+    fn test_flag_rebalancing() {
+        let (inventory, opportunity) = inventory();
+        inventory.reconcile("binance", BTreeMap::from([("USDT".to_string(), dec!(1000))]));
+        inventory.reconcile("kraken", BTreeMap::from([("SOL".to_string(), dec!(1))]));
+
+        assert_eq!(
+            inventory.flag_rebalancing(&opportunity, dec!(2)),
+            vec![("kraken", "SOL".to_string())]
+        );
+        // flagged once
+        assert_eq!(inventory.flag_rebalancing(&opportunity, dec!(2)), vec![]);
+
+        inventory.reconcile("kraken", BTreeMap::from([("SOL".to_string(), dec!(2))]));
+        assert_eq!(inventory.flag_rebalancing(&opportunity, dec!(2)), vec![]);
+
+        inventory.reconcile("kraken", BTreeMap::from([("SOL".to_string(), dec!(1))]));
+        assert_eq!(
+            inventory.flag_rebalancing(&opportunity, dec!(20)),
+            vec![("binance", "USDT".to_string()), ("kraken", "SOL".to_string())]
+        );
+    }
+}
@@ -0,0 +1,807 @@
+use std::{
+    collections::{ BTreeMap, HashMap },
+    sync::{ Arc, Mutex },
+    time::{ Duration, SystemTime, UNIX_EPOCH },
+};
+
+use async_tungstenite::{ tokio::connect_async, tungstenite::Message };
+use base64::{ prelude::BASE64_STANDARD, Engine as _ };
+use futures::prelude::*;
+use hmac::{ Hmac, Mac };
+use reqwest::Url;
+use rust_decimal::Decimal;
+use serde::{ de::DeserializeOwned, Deserialize };
+use serde_json::{ json, Value };
+use sha2::{ Digest, Sha256, Sha512 };
+use tokio::{
+    select,
+    sync::{ broadcast, mpsc, oneshot },
+    task::JoinHandle,
+    time::{ sleep, timeout },
+};
+
+use crate::{ config::{ self, ConfigError }, exchange::kraken::parse_timestamp, Instrument };
+
+use super::{
+    ExecutionError,
+    ExecutionVenue,
+    Fill,
+    Increments,
+    OrderRequest,
+    OrderState,
+    OrderStatus,
+    Side,
+};
+
+const EXCHANGE_ID: &str = "kraken";
+// for the websocket answer to an order request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct KrakenConfig {
+    pub rest_url: String,
+    pub ws_url: String,
+    pub api_key: String,
+    pub secret_key: Vec<u8>, // decoded from base64
+}
+
+impl KrakenConfig {
+    /// `KRAKEN_API_KEY` and the base64 `KRAKEN_SECRET_KEY`, with the endpoints overridable
+    /// by `KRAKEN_REST_URL` and `KRAKEN_WS_AUTH_URL`.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let secret_key = config
+            ::env_parse::<String>("KRAKEN_SECRET_KEY")?
+            .ok_or(ConfigError::Missing("KRAKEN_SECRET_KEY"))?;
+
+        Ok(KrakenConfig {
+            rest_url: config
+                ::env_parse("KRAKEN_REST_URL")?
+                .unwrap_or("https://api.kraken.com".to_string()),
+            ws_url: config
+                ::env_parse("KRAKEN_WS_AUTH_URL")?
+                .unwrap_or("wss://ws-auth.kraken.com/v2".to_string()),
+            api_key: config
+                ::env_parse("KRAKEN_API_KEY")?
+                .ok_or(ConfigError::Missing("KRAKEN_API_KEY"))?,
+            secret_key: BASE64_STANDARD
+                .decode(secret_key)
+                .map_err(|err| ConfigError::Invalid("KRAKEN_SECRET_KEY", err.to_string()))?,
+        })
+    }
+}
+
+/// Orders and fills over the authenticated v2 websocket, with the token and balances from
+/// the signed REST api.
+pub struct KrakenVenue {
+    client: Client,
+    requests: mpsc::Sender<WsRequest>,
+    // kept up to date by the executions channel
+    orders: Arc<Mutex<HashMap<String, OrderState>>>,
+    // by websocket name, e.g. SOL/USDT, from the asset pairs fetched once
+    increments: HashMap<String, Increments>,
+    fills: broadcast::Sender<Fill>,
+    connection: JoinHandle<()>,
+}
+
+struct WsRequest {
+    method: &'static str,
+    params: Value,
+    reply: oneshot::Sender<Result<Value, ExecutionError>>,
+}
+
+impl KrakenVenue {
+    pub fn new(config: KrakenConfig) -> Self {
+        let client = Client { http: reqwest::Client::new(), config: Arc::new(config) };
+        let (requests, requests_rx) = mpsc::channel(64);
+        let orders = Arc::new(Mutex::new(HashMap::new()));
+        let fills = broadcast::channel(1024).0;
+
+        let connection = tokio::spawn(
+            run_connection(client.clone(), requests_rx, orders.clone(), fills.clone())
+        );
+
+        Self { client, requests, orders, increments: HashMap::new(), fills, connection }
+    }
+
+    async fn increments(&mut self, market: &str) -> Result<Increments, ExecutionError> {
+        if self.increments.is_empty() {
+            let pairs = self.client
+                .public::<HashMap<String, AssetPair>>("/0/public/AssetPairs").await?;
+
+            // the tick size, or the price decimals of the pairs listing none
+            self.increments = pairs
+                .into_values()
+                .filter_map(|pair| {
+                    let increments = Increments {
+                        tick_size: match pair.tick_size {
+                            Some(tick_size) => tick_size,
+                            None => Decimal::try_new(1, pair.pair_decimals).ok()?,
+                        },
+                        step_size: Decimal::try_new(1, pair.lot_decimals).ok()?,
+                    };
+                    Some((pair.wsname?, increments))
+                })
+                .collect();
+        }
+
+        self.increments.get(market).copied().ok_or(ExecutionError::NotFound(market.to_string()))
+    }
+
+    async fn request(&self, method: &'static str, params: Value) -> Result<Value, ExecutionError> {
+        let (reply, reply_rx) = oneshot::channel();
+        let closed = || ExecutionError::Transport("connection closed".to_string());
+
+        self.requests.send(WsRequest { method, params, reply }).await.map_err(|_| closed())?;
+
+        match timeout(REQUEST_TIMEOUT, reply_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(closed()),
+            Err(_) => Err(ExecutionError::Transport(format!("{method} timed out"))),
+        }
+    }
+}
+
+impl Drop for KrakenVenue {
+    fn drop(&mut self) {
+        self.connection.abort();
+    }
+}
+
+impl ExecutionVenue for KrakenVenue {
+    async fn place_order(&mut self, order: OrderRequest) -> Result<String, ExecutionError> {
+        let order = self.increments(&order.market).await?.round(order)?;
+
+        let mut params =
+            json!({
+                "symbol": order.market,
+                "side": side_name(order.side),
+                "order_qty": number(order.quantity),
+                "cl_ord_id": order.client_order_id,
+                "order_type": "market",
+            });
+
+        if let Some(price) = order.limit_price {
+            params["order_type"] = json!("limit");
+            params["limit_price"] = number(price);
+            params["time_in_force"] = json!("ioc");
+        }
+
+        let result = self.request("add_order", params).await?;
+        let order_id = result["order_id"]
+            .as_str()
+            .ok_or(ExecutionError::Transport("no order id".to_string()))?
+            .to_string();
+
+        // fills may have arrived before the answer
+        self.orders.lock().unwrap().entry(order_id.clone()).or_insert(OrderState {
+            status: OrderStatus::Open,
+            executed_quantity: Decimal::ZERO,
+        });
+
+        Ok(order_id)
+    }
+
+    async fn cancel_order(&mut self, order_id: &str) -> Result<(), ExecutionError> {
+        self.request("cancel_order", json!({"order_id": [order_id]})).await?;
+        Ok(())
+    }
+
+    async fn order_state(&mut self, order_id: &str) -> Result<OrderState, ExecutionError> {
+        self.orders
+            .lock()
+            .unwrap()
+            .get(order_id)
+            .copied()
+            .ok_or(ExecutionError::NotFound(order_id.to_string()))
+    }
+
+    async fn balances(&mut self) -> Result<BTreeMap<String, Decimal>, ExecutionError> {
+        let balances = self.client.private::<HashMap<String, Decimal>>("/0/private/Balance").await?;
+
+        Ok(
+            balances
+                .into_iter()
+                // earn and staking balances, e.g. SOL.F, cannot be traded
+                .filter(|(asset, _)| !asset.contains('.'))
+                .map(|(asset, amount)| (asset_name(&asset), amount))
+                .collect()
+        )
+    }
+
+    fn fills(&self) -> broadcast::Receiver<Fill> {
+        self.fills.subscribe()
+    }
+}
+
+#[derive(Clone)]
+struct Client {
+    http: reqwest::Client,
+    config: Arc<KrakenConfig>,
+}
+
+impl Client {
+    // posts to a private endpoint with the body signed by the secret key
+    async fn private<T: DeserializeOwned>(&self, path: &str) -> Result<T, ExecutionError> {
+        let transport = |err: reqwest::Error| ExecutionError::Transport(err.to_string());
+
+        let url = Url::parse(&format!("{}{path}", self.config.rest_url)).map_err(|err| {
+            ExecutionError::Transport(err.to_string())
+        })?;
+
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_micros())
+            .unwrap_or_default()
+            .to_string();
+        let body = format!("nonce={nonce}");
+
+        let response = self.http
+            .post(url)
+            .header("API-Key", &self.config.api_key)
+            .header("API-Sign", sign(&self.config.secret_key, path, &nonce, &body))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send().await
+            .map_err(transport)?;
+
+        let status = response.status();
+        let body = response.text().await.map_err(transport)?;
+
+        let Ok(response) = serde_json::from_str::<RestResponse<T>>(&body) else {
+            return Err(ExecutionError::Transport(format!("{status} {body}")));
+        };
+
+        match response.result {
+            Some(result) if response.error.is_empty() => Ok(result),
+            _ => Err(ExecutionError::Rejected(response.error.join(", "))),
+        }
+    }
+
+    async fn public<T: DeserializeOwned>(&self, path: &str) -> Result<T, ExecutionError> {
+        let transport = |err: reqwest::Error| ExecutionError::Transport(err.to_string());
+
+        let body = self.http
+            .get(format!("{}{path}", self.config.rest_url))
+            .send().await
+            .map_err(transport)?
+            .text().await
+            .map_err(transport)?;
+
+        let Ok(response) = serde_json::from_str::<RestResponse<T>>(&body) else {
+            return Err(ExecutionError::Transport(format!("{path} {body}")));
+        };
+
+        match response.result {
+            Some(result) if response.error.is_empty() => Ok(result),
+            _ => Err(ExecutionError::Rejected(response.error.join(", "))),
+        }
+    }
+
+    async fn websocket_token(&self) -> Result<String, ExecutionError> {
+        let response = self.private::<TokenResult>("/0/private/GetWebSocketsToken").await?;
+        Ok(response.token)
+    }
+}
+
+// keeps an authenticated connection subscribed to the executions, sending the order
+// requests over it until the venue is dropped
+async fn run_connection(
+    client: Client,
+    mut requests: mpsc::Receiver<WsRequest>,
+    orders: Arc<Mutex<HashMap<String, OrderState>>>,
+    fills: broadcast::Sender<Fill>
+) {
+    let mut req_id = 0_u64;
+
+    loop {
+        let token = match client.websocket_token().await {
+            Ok(token) => token,
+            Err(err) => {
+                log::warn!("{EXCHANGE_ID} cannot get a websocket token {err}");
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        let Ok((mut conn, _)) = connect_async(client.config.ws_url.as_str()).await else {
+            log::warn!("{EXCHANGE_ID} cannot connect to the authenticated websocket");
+            sleep(Duration::from_secs(1)).await;
+            continue;
+        };
+
+        let subscribe =
+            json!({
+                "method": "subscribe",
+                "params": {
+                    "channel": "executions",
+                    "token": token,
+                    "snap_orders": false,
+                    "snap_trades": false,
+                },
+            });
+        if conn.send(Message::Text(subscribe.to_string())).await.is_err() {
+            continue;
+        }
+
+        log::debug!("{EXCHANGE_ID} authenticated websocket connected");
+
+        // requests sent, waiting for their answer
+        let mut pending = HashMap::<u64, oneshot::Sender<Result<Value, ExecutionError>>>::new();
+
+        loop {
+            select! {
+                request = requests.recv() => {
+                    let Some(WsRequest { method, mut params, reply }) = request else {
+                        // the venue was dropped
+                        return;
+                    };
+
+                    req_id += 1;
+                    params["token"] = json!(token);
+
+                    let payload = json!({"method": method, "params": params, "req_id": req_id});
+                    if conn.send(Message::Text(payload.to_string())).await.is_err() {
+                        let _ = reply.send(
+                            Err(ExecutionError::Transport("cannot send request".to_string()))
+                        );
+                        break;
+                    }
+                    pending.insert(req_id, reply);
+                }
+
+                res = conn.next() => {
+                    match res {
+                        Some(Ok(Message::Text(payload))) => {
+                            handle_message(&payload, &mut pending, &orders, &fills);
+                        }
+                        Some(Ok(Message::Ping(value))) => {
+                            let _ = conn.send(Message::Pong(value)).await;
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    }
+                }
+            }
+        }
+
+        // dropping the pending replies fails their requests
+        log::debug!("{EXCHANGE_ID} authenticated websocket disconnected");
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+fn handle_message(
+    payload: &str,
+    pending: &mut HashMap<u64, oneshot::Sender<Result<Value, ExecutionError>>>,
+    orders: &Mutex<HashMap<String, OrderState>>,
+    fills: &broadcast::Sender<Fill>
+) {
+    let message = match serde_json::from_str::<WsMessage>(payload) {
+        Ok(message) => message,
+        Err(err) => {
+            log::debug!("{EXCHANGE_ID} {err} {payload}");
+            return;
+        }
+    };
+
+    if let Some(reply) = message.req_id.and_then(|req_id| pending.remove(&req_id)) {
+        let result = match (message.success, message.error) {
+            (Some(true), _) => Ok(message.result.unwrap_or_default()),
+            (_, Some(error)) if error.contains("Unknown order") => {
+                Err(ExecutionError::NotFound(error))
+            }
+            (_, error) => Err(ExecutionError::Rejected(error.unwrap_or_default())),
+        };
+        let _ = reply.send(result);
+        return;
+    }
+
+    if message.channel.as_deref() != Some("executions") {
+        return;
+    }
+
+    let executions = message.data
+        .map(serde_json::from_value::<Vec<Execution>>)
+        .transpose();
+
+    let executions = match executions {
+        Ok(executions) => executions.unwrap_or_default(),
+        Err(err) => {
+            log::debug!("{EXCHANGE_ID} executions {err}");
+            return;
+        }
+    };
+
+    for execution in executions {
+        if let Some(status) = execution.order_status.as_deref() {
+            let status = match status {
+                "filled" => OrderStatus::Filled,
+                "canceled" => OrderStatus::Canceled,
+                "expired" => OrderStatus::Expired,
+                _ => OrderStatus::Open,
+            };
+
+            let mut orders = orders.lock().unwrap();
+            let order = orders.entry(execution.order_id.clone()).or_insert(OrderState {
+                status,
+                executed_quantity: Decimal::ZERO,
+            });
+            order.status = status;
+            if let Some(cum_qty) = execution.cum_qty {
+                order.executed_quantity = cum_qty;
+            }
+        }
+
+        if let Some(fill) = execution.fill() {
+            let _ = fills.send(fill);
+        }
+    }
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+}
+
+// kraken wants numbers where serde writes decimals as strings
+fn number(value: Decimal) -> Value {
+    serde_json::from_str(&value.normalize().to_string()).unwrap_or_default()
+}
+
+// the REST api prefixes legacy asset names, e.g. XXBT or ZUSD
+fn asset_name(asset: &str) -> String {
+    let asset = match asset.strip_prefix(['X', 'Z']) {
+        Some(name) if asset.len() == 4 => name,
+        _ => asset,
+    };
+
+    match asset {
+        "XBT" => "BTC".to_string(),
+        "XDG" => "DOGE".to_string(),
+        asset => asset.to_string(),
+    }
+}
+
+/// Base64 HMAC-SHA512 of the path and the SHA256 of the nonce and body, keyed by the
+/// decoded secret.
+fn sign(secret_key: &[u8], path: &str, nonce: &str, body: &str) -> String {
+    let digest = Sha256::digest(format!("{nonce}{body}"));
+
+    let mut mac = Hmac::<Sha512>::new_from_slice(secret_key).expect("hmac takes keys of any size");
+    mac.update(path.as_bytes());
+    mac.update(&digest);
+
+    BASE64_STANDARD.encode(mac.finalize().into_bytes())
+}
+
+#[derive(Deserialize, Debug)]
+struct RestResponse<T> {
+    #[serde(default)]
+    error: Vec<String>,
+    result: Option<T>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AssetPair {
+    // none for the pairs not on the websocket api
+    wsname: Option<String>,
+    tick_size: Option<Decimal>,
+    pair_decimals: u32,
+    lot_decimals: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResult {
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct WsMessage {
+    req_id: Option<u64>,
+    success: Option<bool>,
+    error: Option<String>,
+    result: Option<Value>,
+    channel: Option<String>,
+    data: Option<Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Execution {
+    order_id: String,
+    exec_type: String,
+    order_status: Option<String>,
+    cl_ord_id: Option<String>,
+    symbol: Option<String>,
+    side: Option<String>,
+    last_qty: Option<Decimal>,
+    last_price: Option<Decimal>,
+    cum_qty: Option<Decimal>,
+    #[serde(default)]
+    fees: Vec<ExecutionFee>,
+    timestamp: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExecutionFee {
+    asset: String,
+    qty: Decimal,
+}
+
+impl Execution {
+    // the trade of a `trade` execution
+    fn fill(&self) -> Option<Fill> {
+        if self.exec_type != "trade" {
+            return None;
+        }
+
+        let side = match self.side.as_deref()? {
+            "buy" => Side::Buy,
+            "sell" => Side::Sell,
+            _ => {
+                return None;
+            }
+        };
+
+        let market = self.symbol.clone()?;
+        let (quantity, price) = (self.last_qty?, self.last_price?);
+
+        // fees charged in neither asset of the market are left out
+        let instrument = market.parse::<Instrument>().ok()?;
+        let fee = self.fees
+            .iter()
+            .map(|fee| {
+                if asset_name(&fee.asset) == instrument.quote {
+                    fee.qty
+                } else if asset_name(&fee.asset) == instrument.base {
+                    fee.qty * price
+                } else {
+                    Decimal::ZERO
+                }
+            })
+            .sum();
+
+        Some(Fill {
+            exchange_id: EXCHANGE_ID,
+            order_id: self.order_id.clone(),
+            client_order_id: self.cl_ord_id.clone().unwrap_or_default(),
+            market,
+            side,
+            quantity,
+            price,
+            fee,
+            timestamp: self.timestamp
+                .as_deref()
+                .and_then(parse_timestamp)
+                .unwrap_or_else(SystemTime::now),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_tungstenite::tokio::accept_async;
+    use axum::{ http::{ HeaderMap, StatusCode }, routing::post, Json, Router };
+    use rust_decimal_macros::dec;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const API_KEY: &str = "test-api-key";
+    const TOKEN: &str = "test-token";
+
+    // params of the orders the mock websocket received
+    static ORDERS: Mutex<Vec<Value>> = Mutex::new(vec![]);
+
+    fn secret_key() -> Vec<u8> {
+        b"test-secret-key".to_vec()
+    }
+
+    // stand-in for the private REST api, checking every signature
+    fn mock_api() -> Router {
+        fn verify(path: &str, headers: &HeaderMap, body: &str) -> bool {
+            let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+            let Some(nonce) = body.strip_prefix("nonce=") else {
+                return false;
+            };
+
+            header("API-Key") == Some(API_KEY) &&
+                header("API-Sign") == Some(&sign(&secret_key(), path, nonce, body))
+        }
+
+        Router::new()
+            .route(
+                "/0/public/AssetPairs",
+                axum::routing::get(|| async {
+                    Json(
+                        json!({"error": [], "result": {
+                            "SOLUSDT": {"altname": "SOLUSDT", "wsname": "SOL/USDT", "pair_decimals": 2, "lot_decimals": 2, "tick_size": "0.01"},
+                            "XXBTZUSD": {"altname": "XBTUSD", "wsname": "XBT/USD", "pair_decimals": 1, "lot_decimals": 8}
+                        }})
+                    )
+                })
+            )
+            .route(
+                "/0/private/GetWebSocketsToken",
+                post(|headers: HeaderMap, body: String| async move {
+                    if !verify("/0/private/GetWebSocketsToken", &headers, &body) {
+                        return (StatusCode::OK, Json(json!({"error": ["EAPI:Invalid signature"]})));
+                    }
+                    (StatusCode::OK, Json(json!({"error": [], "result": {"token": TOKEN}})))
+                })
+            )
+            .route(
+                "/0/private/Balance",
+                post(|headers: HeaderMap, body: String| async move {
+                    if !verify("/0/private/Balance", &headers, &body) {
+                        return Json(json!({"error": ["EAPI:Invalid signature"]}));
+                    }
+                    Json(
+                        json!({"error": [], "result": {
+                            "ZUSD": "171.6158", "XXBT": "0.5", "USDT": "100", "SOL.F": "1"
+                        }})
+                    )
+                })
+            )
+    }
+
+    // stand-in for the authenticated websocket, filling every order in full
+    async fn mock_ws(listener: TcpListener) {
+        while let Ok((stream, _)) = listener.accept().await {
+            let mut conn = accept_async(stream).await.unwrap();
+
+            while let Some(Ok(Message::Text(payload))) = conn.next().await {
+                let request = serde_json::from_str::<Value>(&payload).unwrap();
+                assert_eq!(request["params"]["token"], TOKEN);
+
+                let (method, req_id) = (request["method"].clone(), request["req_id"].clone());
+                let params = &request["params"];
+
+                if method == "add_order" {
+                    ORDERS.lock().unwrap().push(params.clone());
+                }
+
+                let mut replies = vec![];
+                match method.as_str().unwrap() {
+                    "subscribe" => assert_eq!(params["channel"], "executions"),
+                    "add_order" if params["order_qty"].as_f64() > Some(10.0) => {
+                        replies.push(
+                            json!({"method": method, "req_id": req_id, "success": false,
+                                "error": "EOrder:Insufficient funds"})
+                        );
+                    }
+                    "add_order" => {
+                        replies.push(
+                            json!({"method": method, "req_id": req_id, "success": true,
+                                "result": {"order_id": "OABC-1", "cl_ord_id": params["cl_ord_id"]}})
+                        );
+                        replies.push(
+                            json!({"channel": "executions", "type": "update", "data": [{
+                                "exec_type": "trade", "order_id": "OABC-1",
+                                "cl_ord_id": params["cl_ord_id"], "symbol": params["symbol"],
+                                "side": params["side"], "last_qty": params["order_qty"],
+                                "last_price": 100.4, "cum_qty": params["order_qty"],
+                                "order_status": "filled",
+                                "fees": [{"asset": "USDT", "qty": 0.26}],
+                                "timestamp": "2024-09-22T10:33:05.709993Z"
+                            }]})
+                        );
+                    }
+                    "cancel_order" => {
+                        replies.push(
+                            json!({"method": method, "req_id": req_id, "success": false,
+                                "error": "EOrder:Unknown order"})
+                        );
+                    }
+                    method => panic!("unexpected {method}"),
+                }
+
+                for reply in replies {
+                    conn.send(Message::Text(reply.to_string())).await.unwrap();
+                }
+            }
+        }
+    }
+
+    async fn start() -> KrakenVenue {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rest_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, mock_api()).await });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(mock_ws(listener));
+
+        KrakenVenue::new(KrakenConfig {
+            rest_url,
+            ws_url,
+            api_key: API_KEY.to_string(),
+            secret_key: secret_key(),
+        })
+    }
+
+    fn order(quantity: Decimal) -> OrderRequest {
+        OrderRequest {
+            client_order_id: "arb-1-s".to_string(),
+            market: "SOL/USDT".to_string(),
+            instrument: "SOL/USDT".parse().unwrap(),
+            side: Side::Sell,
+            quantity,
+            limit_price: Some(dec!(100.25)),
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        // example of the kraken api documentation
+        let secret_key = BASE64_STANDARD.decode(
+            "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg=="
+        ).unwrap();
+
+        assert_eq!(
+            sign(
+                &secret_key,
+                "/0/private/AddOrder",
+                "1616492376594",
+                "nonce=1616492376594&ordertype=limit&pair=XBTUSD&price=37500&type=buy&volume=1.25"
+            ),
+            "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ=="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_orders() {
+        let mut venue = start().await;
+        let mut fills = venue.fills();
+
+        // on the tick and step of the pair, the sell limit rounded up
+        let unrounded = OrderRequest { limit_price: Some(dec!(100.251)), ..order(dec!(1.505)) };
+        let order_id = venue.place_order(unrounded).await.unwrap();
+        assert_eq!(order_id, "OABC-1");
+
+        let params = ORDERS.lock().unwrap().last().cloned().unwrap();
+        assert_eq!((&params["order_qty"], &params["limit_price"]), (&json!(1.5), &json!(100.26)));
+
+        let fill = timeout(Duration::from_secs(5), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.order_id, "OABC-1");
+        assert_eq!(fill.client_order_id, "arb-1-s");
+        assert_eq!(fill.side, Side::Sell);
+        assert_eq!((fill.quantity, fill.price, fill.fee), (dec!(1.5), dec!(100.4), dec!(0.26)));
+        assert_eq!(fill.timestamp, parse_timestamp("2024-09-22T10:33:05.709993Z").unwrap());
+
+        assert_eq!(venue.order_state(&order_id).await.unwrap(), OrderState {
+            status: OrderStatus::Filled,
+            executed_quantity: dec!(1.5),
+        });
+        assert!(matches!(venue.cancel_order(&order_id).await, Err(ExecutionError::NotFound(_))));
+        assert!(
+            matches!(venue.place_order(order(dec!(100))).await, Err(ExecutionError::Rejected(_)))
+        );
+
+        let unlisted = OrderRequest { market: "SOL/EUR".to_string(), ..order(dec!(1)) };
+        assert!(matches!(venue.place_order(unlisted).await, Err(ExecutionError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_balances() {
+        let mut venue = start().await;
+
+        let balances = venue.balances().await.unwrap();
+        assert_eq!(
+            balances,
+            BTreeMap::from([
+                ("BTC".to_string(), dec!(0.5)),
+                ("USD".to_string(), dec!(171.6158)),
+                ("USDT".to_string(), dec!(100)),
+            ])
+        );
+
+        venue.client.config = Arc::new(KrakenConfig {
+            rest_url: venue.client.config.rest_url.clone(),
+            ws_url: venue.client.config.ws_url.clone(),
+            api_key: API_KEY.to_string(),
+            secret_key: b"wrong-secret-key".to_vec(),
+        });
+        assert!(matches!(venue.balances().await, Err(ExecutionError::Rejected(_))));
+    }
+}
@@ -0,0 +1,185 @@
+use std::{ collections::BTreeMap, fmt, time::SystemTime };
+
+use rust_decimal::Decimal;
+use tokio::sync::broadcast;
+
+use crate::Instrument;
+
+pub mod binance;
+pub mod coordinator;
+pub mod inventory;
+pub mod kraken;
+pub mod paper;
+pub mod raydium;
+pub mod risk;
+pub mod transaction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Immediate-or-cancel order for `quantity` of the instrument base.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    pub client_order_id: String,
+    pub market: String, // as named by the venue
+    pub instrument: Instrument,
+    pub side: Side,
+    pub quantity: Decimal,
+    // market order when not set
+    pub limit_price: Option<Decimal>,
+}
+
+/// Price and quantity increments the orders of a market are taken in, none when zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Increments {
+    pub tick_size: Decimal,
+    pub step_size: Decimal,
+}
+
+impl Increments {
+    /// The order with its quantity rounded down to the step and its limit price to the
+    /// tick, down for a buy and up for a sell so it never pays more than asked. Errors when
+    /// the quantity is under a step.
+    pub fn round(&self, order: OrderRequest) -> Result<OrderRequest, ExecutionError> {
+        let quantity = round_to(order.quantity, self.step_size, false);
+        if quantity.is_zero() {
+            return Err(
+                ExecutionError::Rejected(
+                    format!("quantity {} under the step {}", order.quantity, self.step_size)
+                )
+            );
+        }
+
+        let limit_price = order.limit_price.map(|price| {
+            round_to(price, self.tick_size, order.side == Side::Sell)
+        });
+
+        Ok(OrderRequest { quantity, limit_price, ..order })
+    }
+}
+
+fn round_to(value: Decimal, increment: Decimal, up: bool) -> Decimal {
+    if increment <= Decimal::ZERO {
+        return value;
+    }
+
+    let increments = value / increment;
+    let increments = if up { increments.ceil() } else { increments.floor() };
+    (increments * increment).normalize()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Open,
+    Filled,
+    Canceled,
+    // left unfilled, or partially filled, once its limit was out of reach
+    Expired,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderState {
+    pub status: OrderStatus,
+    pub executed_quantity: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub exchange_id: &'static str,
+    pub order_id: String,
+    pub client_order_id: String,
+    pub market: String,
+    pub side: Side,
+    pub quantity: Decimal,
+    pub price: Decimal,
+    pub fee: Decimal, // in the quote
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionError {
+    Rejected(String),
+    NotFound(String),
+    Transport(String),
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Rejected(reason) => write!(f, "order rejected: {reason}"),
+            ExecutionError::NotFound(order_id) => write!(f, "order not found {order_id}"),
+            ExecutionError::Transport(reason) => write!(f, "transport error: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// Exchange, or simulation of one, orders can be sent to.
+pub trait ExecutionVenue {
+    /// Sends the order, returning the order id assigned by the venue.
+    async fn place_order(&mut self, order: OrderRequest) -> Result<String, ExecutionError>;
+    async fn cancel_order(&mut self, order_id: &str) -> Result<(), ExecutionError>;
+    async fn order_state(&mut self, order_id: &str) -> Result<OrderState, ExecutionError>;
+    async fn balances(&mut self) -> Result<BTreeMap<String, Decimal>, ExecutionError>;
+    /// Fills of the orders placed from now on.
+    fn fills(&self) -> broadcast::Receiver<Fill>;
+}
+
+/// Venue a leg is traded on, simulated or live.
+pub enum TradingVenue {
+    Paper(paper::PaperVenue),
+    Binance(binance::BinanceVenue),
+    Kraken(kraken::KrakenVenue),
+    Raydium(raydium::RaydiumVenue),
+}
+
+impl ExecutionVenue for TradingVenue {
+    async fn place_order(&mut self, order: OrderRequest) -> Result<String, ExecutionError> {
+        match self {
+            TradingVenue::Paper(venue) => venue.place_order(order).await,
+            TradingVenue::Binance(venue) => venue.place_order(order).await,
+            TradingVenue::Kraken(venue) => venue.place_order(order).await,
+            TradingVenue::Raydium(venue) => venue.place_order(order).await,
+        }
+    }
+
+    async fn cancel_order(&mut self, order_id: &str) -> Result<(), ExecutionError> {
+        match self {
+            TradingVenue::Paper(venue) => venue.cancel_order(order_id).await,
+            TradingVenue::Binance(venue) => venue.cancel_order(order_id).await,
+            TradingVenue::Kraken(venue) => venue.cancel_order(order_id).await,
+            TradingVenue::Raydium(venue) => venue.cancel_order(order_id).await,
+        }
+    }
+
+    async fn order_state(&mut self, order_id: &str) -> Result<OrderState, ExecutionError> {
+        match self {
+            TradingVenue::Paper(venue) => venue.order_state(order_id).await,
+            TradingVenue::Binance(venue) => venue.order_state(order_id).await,
+            TradingVenue::Kraken(venue) => venue.order_state(order_id).await,
+            TradingVenue::Raydium(venue) => venue.order_state(order_id).await,
+        }
+    }
+
+    async fn balances(&mut self) -> Result<BTreeMap<String, Decimal>, ExecutionError> {
+        match self {
+            TradingVenue::Paper(venue) => venue.balances().await,
+            TradingVenue::Binance(venue) => venue.balances().await,
+            TradingVenue::Kraken(venue) => venue.balances().await,
+            TradingVenue::Raydium(venue) => venue.balances().await,
+        }
+    }
+
+    fn fills(&self) -> broadcast::Receiver<Fill> {
+        match self {
+            TradingVenue::Paper(venue) => venue.fills(),
+            TradingVenue::Binance(venue) => venue.fills(),
+            TradingVenue::Kraken(venue) => venue.fills(),
+            TradingVenue::Raydium(venue) => venue.fills(),
+        }
+    }
+}
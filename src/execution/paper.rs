@@ -0,0 +1,417 @@
+use std::{
+    collections::{ BTreeMap, HashMap },
+    sync::{ Arc, Mutex },
+    time::{ Duration, SystemTime },
+};
+
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use tokio::{ sync::broadcast, time::sleep };
+
+use crate::{ config::{ self, ConfigError }, markets::SharedMarkets, Venue };
+
+use super::{
+    ExecutionError,
+    ExecutionVenue,
+    Fill,
+    OrderRequest,
+    OrderState,
+    OrderStatus,
+    Side,
+};
+
+/// Simulation parameters, the same for every paper venue.
+#[derive(Debug, Clone)]
+pub struct PaperConfig {
+    // from placing an order to it being filled
+    pub latency: Duration,
+    // adverse price move on every fill
+    pub slippage_bps: Decimal,
+    pub fee_rate: Decimal,
+    pub balances: BTreeMap<String, Decimal>,
+}
+
+impl PaperConfig {
+    /// `PAPER_LATENCY_MS`, `PAPER_SLIPPAGE_BPS`, `PAPER_FEE_RATE` and the initial
+    /// `PAPER_BALANCES` of every venue as `<asset>=<amount>` items, e.g. `USDT=10000,SOL=50`.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(PaperConfig {
+            latency: Duration::from_millis(config::env_parse("PAPER_LATENCY_MS")?.unwrap_or(50)),
+            slippage_bps: config::env_parse("PAPER_SLIPPAGE_BPS")?.unwrap_or(dec!(1)),
+            fee_rate: config::env_parse("PAPER_FEE_RATE")?.unwrap_or(dec!(0.001)),
+            balances: config::env_assets("PAPER_BALANCES")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// Fills orders against the venue price in the engine once the configured latency has
+/// passed, so the price may have moved in the meantime.
+pub struct PaperVenue {
+    exchange_id: &'static str,
+    markets: SharedMarkets,
+    config: PaperConfig,
+    state: Arc<Mutex<PaperState>>,
+    fills: broadcast::Sender<Fill>,
+}
+
+struct PaperState {
+    next_id: u64,
+    balances: BTreeMap<String, Decimal>,
+    orders: HashMap<String, OrderState>,
+}
+
+impl PaperVenue {
+    pub fn new(exchange_id: &'static str, markets: SharedMarkets, config: PaperConfig) -> Self {
+        let state = PaperState {
+            next_id: 0,
+            balances: config.balances.clone(),
+            orders: HashMap::new(),
+        };
+
+        Self {
+            exchange_id,
+            markets,
+            config,
+            state: Arc::new(Mutex::new(state)),
+            fills: broadcast::channel(1024).0,
+        }
+    }
+}
+
+impl ExecutionVenue for PaperVenue {
+    async fn place_order(&mut self, order: OrderRequest) -> Result<String, ExecutionError> {
+        if order.quantity <= Decimal::ZERO {
+            return Err(ExecutionError::Rejected("quantity must be positive".to_string()));
+        }
+
+        let simulation = Simulation {
+            exchange_id: self.exchange_id,
+            markets: self.markets.clone(),
+            config: self.config.clone(),
+            state: self.state.clone(),
+            fills: self.fills.clone(),
+        };
+
+        // checked upfront as well, as a venue would
+        let price = simulation.fill_price(&order)?;
+        simulation.check_balances(&simulation.state.lock().unwrap().balances, &order, price)?;
+
+        let order_id = {
+            let mut state = self.state.lock().unwrap();
+            state.next_id += 1;
+
+            let order_id = format!("paper-{}-{}", self.exchange_id, state.next_id);
+            state.orders.insert(order_id.clone(), OrderState {
+                status: OrderStatus::Open,
+                executed_quantity: Decimal::ZERO,
+            });
+            order_id
+        };
+
+        tokio::spawn(simulation.fill(order_id.clone(), order));
+
+        Ok(order_id)
+    }
+
+    async fn cancel_order(&mut self, order_id: &str) -> Result<(), ExecutionError> {
+        match self.state.lock().unwrap().orders.get_mut(order_id) {
+            Some(order) if order.status == OrderStatus::Open => {
+                order.status = OrderStatus::Canceled;
+                Ok(())
+            }
+            Some(order) => Err(ExecutionError::Rejected(format!("order {:?}", order.status))),
+            None => Err(ExecutionError::NotFound(order_id.to_string())),
+        }
+    }
+
+    async fn order_state(&mut self, order_id: &str) -> Result<OrderState, ExecutionError> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders.get(order_id)
+            .copied()
+            .ok_or(ExecutionError::NotFound(order_id.to_string()))
+    }
+
+    async fn balances(&mut self) -> Result<BTreeMap<String, Decimal>, ExecutionError> {
+        Ok(self.state.lock().unwrap().balances.clone())
+    }
+
+    fn fills(&self) -> broadcast::Receiver<Fill> {
+        self.fills.subscribe()
+    }
+}
+
+// what a pending order needs to be filled on its own task
+struct Simulation {
+    exchange_id: &'static str,
+    markets: SharedMarkets,
+    config: PaperConfig,
+    state: Arc<Mutex<PaperState>>,
+    fills: broadcast::Sender<Fill>,
+}
+
+impl Simulation {
+    async fn fill(self, order_id: String, order: OrderRequest) {
+        sleep(self.config.latency).await;
+
+        let price = self.fill_price(&order);
+
+        let mut state = self.state.lock().unwrap();
+
+        // canceled while in flight
+        if state.orders.get(&order_id).map(|order| order.status) != Some(OrderStatus::Open) {
+            return;
+        }
+
+        let fill = price.and_then(|price| {
+            self.check_balances(&state.balances, &order, price)?;
+            Ok(price)
+        });
+
+        let price = match fill {
+            Ok(price) => price,
+            Err(err) => {
+                log::debug!("{} {order_id} expired {err}", self.exchange_id);
+                state.orders.insert(order_id, OrderState {
+                    status: OrderStatus::Expired,
+                    executed_quantity: Decimal::ZERO,
+                });
+                return;
+            }
+        };
+
+        let notional = order.quantity * price;
+        let fee = notional * self.config.fee_rate;
+        let (base, quote) = (&order.instrument.base, &order.instrument.quote);
+
+        let (base_change, quote_change) = match order.side {
+            Side::Buy => (order.quantity, -notional - fee),
+            Side::Sell => (-order.quantity, notional - fee),
+        };
+        *state.balances.entry(base.clone()).or_default() += base_change;
+        *state.balances.entry(quote.clone()).or_default() += quote_change;
+        state.orders.insert(order_id.clone(), OrderState {
+            status: OrderStatus::Filled,
+            executed_quantity: order.quantity,
+        });
+
+        drop(state);
+
+        let _ = self.fills.send(Fill {
+            exchange_id: self.exchange_id,
+            order_id,
+            client_order_id: order.client_order_id,
+            market: order.market,
+            side: order.side,
+            quantity: order.quantity,
+            price,
+            fee,
+            timestamp: SystemTime::now(),
+        });
+    }
+
+    // buys at the ask and sells at the bid, at the venue price when its book lacks the side,
+    // moved against the order by the slippage, if within its limit
+    fn fill_price(&self, order: &OrderRequest) -> Result<Decimal, ExecutionError> {
+        let venue = Venue { exchange_id: self.exchange_id, market: order.market.clone() };
+
+        let price = {
+            let markets = self.markets.read().unwrap();
+            let side = match order.side {
+                Side::Buy => markets.native_ask(&venue),
+                Side::Sell => markets.native_bid(&venue),
+            };
+            side.or_else(|| markets.native_price(&venue))
+        };
+        let price = price.ok_or(ExecutionError::Rejected(format!("no price for {venue}")))?;
+
+        let slippage = self.config.slippage_bps / dec!(10_000);
+        let price = match order.side {
+            Side::Buy => price * (Decimal::ONE + slippage),
+            Side::Sell => price * (Decimal::ONE - slippage),
+        };
+
+        match (order.side, order.limit_price) {
+            (Side::Buy, Some(limit)) if price > limit => {
+                Err(ExecutionError::Rejected(format!("price {price} above limit {limit}")))
+            }
+            (Side::Sell, Some(limit)) if price < limit => {
+                Err(ExecutionError::Rejected(format!("price {price} below limit {limit}")))
+            }
+            _ => Ok(price),
+        }
+    }
+
+    fn check_balances(
+        &self,
+        balances: &BTreeMap<String, Decimal>,
+        order: &OrderRequest,
+        price: Decimal
+    ) -> Result<(), ExecutionError> {
+        let (asset, needed) = match order.side {
+            Side::Buy => {
+                let notional = order.quantity * price;
+                (&order.instrument.quote, notional + notional * self.config.fee_rate)
+            }
+            Side::Sell => (&order.instrument.base, order.quantity),
+        };
+
+        let available = balances.get(asset).copied().unwrap_or_default();
+        if available < needed {
+            return Err(
+                ExecutionError::Rejected(format!("insufficient {asset} {available} < {needed}"))
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use tokio::time::timeout;
+
+    use crate::{ markets::Markets, Instrument };
+
+    use super::*;
+
+    fn venue(latency: Duration) -> (PaperVenue, SharedMarkets) {
+        let markets: SharedMarkets = Arc::new(RwLock::new(Markets::new(dec!(10))));
+        markets
+            .write()
+            .unwrap()
+            .update(
+                "SOL/USDT".parse().unwrap(),
+                Venue { exchange_id: "binance", market: "SOLUSDT".to_string() },
+                dec!(100)
+            );
+
+        let config = PaperConfig {
+            latency,
+            slippage_bps: dec!(10),
+            fee_rate: dec!(0.001),
+            balances: BTreeMap::from([("USDT".to_string(), dec!(1000))]),
+        };
+
+        (PaperVenue::new("binance", markets.clone(), config), markets)
+    }
+
+    fn order(side: Side, quantity: Decimal, limit_price: Option<Decimal>) -> OrderRequest {
+        OrderRequest {
+            client_order_id: "test".to_string(),
+            market: "SOLUSDT".to_string(),
+            instrument: "SOL/USDT".parse::<Instrument>().unwrap(),
+            side,
+            quantity,
+            limit_price,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fill() {
+        let (mut venue, _) = venue(Duration::ZERO);
+        let mut fills = venue.fills();
+
+        let order_id = venue.place_order(order(Side::Buy, dec!(2), None)).await.unwrap();
+
+        let fill = timeout(Duration::from_secs(1), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.order_id, order_id);
+        assert_eq!(venue.order_state(&order_id).await.unwrap(), OrderState {
+            status: OrderStatus::Filled,
+            executed_quantity: dec!(2),
+        });
+        assert_eq!(fill.price, dec!(100.1));
+        assert_eq!(fill.fee, dec!(0.2002));
+
+        let balances = venue.balances().await.unwrap();
+        assert_eq!(balances["SOL"], dec!(2));
+        assert_eq!(balances["USDT"], dec!(1000) - dec!(200.2) - dec!(0.2002));
+
+        venue.place_order(order(Side::Sell, dec!(2), Some(dec!(99)))).await.unwrap();
+
+        let fill = timeout(Duration::from_secs(1), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.price, dec!(99.9));
+        assert_eq!(venue.balances().await.unwrap()["SOL"], dec!(0));
+    }
+
+    #[tokio::test]
+    async fn test_fill_book() {
+        let (mut venue, markets) = venue(Duration::ZERO);
+        let mut fills = venue.fills();
+
+        // a wide book around the 100 mid
+        let binance = Venue { exchange_id: "binance", market: "SOLUSDT".to_string() };
+        markets.write().unwrap().book(binance.clone(), Some(dec!(95)), Some(dec!(105)));
+
+        venue.place_order(order(Side::Buy, dec!(2), None)).await.unwrap();
+        let fill = timeout(Duration::from_secs(1), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.price, dec!(105.105));
+
+        venue.place_order(order(Side::Sell, dec!(1), None)).await.unwrap();
+        let fill = timeout(Duration::from_secs(1), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.price, dec!(94.905));
+
+        // the mid where the book side is missing
+        markets.write().unwrap().book(binance, Some(dec!(95)), None);
+        venue.place_order(order(Side::Buy, dec!(1), None)).await.unwrap();
+        let fill = timeout(Duration::from_secs(1), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.price, dec!(100.1));
+    }
+
+    #[tokio::test]
+    async fn test_rejected() {
+        let (mut venue, _) = venue(Duration::ZERO);
+
+        // no base to sell, not enough quote to buy
+        assert!(venue.place_order(order(Side::Sell, dec!(1), None)).await.is_err());
+        assert!(venue.place_order(order(Side::Buy, dec!(100), None)).await.is_err());
+        assert!(venue.place_order(order(Side::Buy, dec!(1), Some(dec!(100)))).await.is_err());
+        assert!(venue.place_order(order(Side::Buy, dec!(0), None)).await.is_err());
+
+        let mut unknown = order(Side::Buy, dec!(1), None);
+        unknown.market = "ETHUSDT".to_string();
+        assert!(venue.place_order(unknown).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_latency() {
+        let (mut venue, markets) = venue(Duration::from_millis(100));
+        let mut fills = venue.fills();
+
+        // canceled in flight
+        let order_id = venue.place_order(order(Side::Buy, dec!(1), None)).await.unwrap();
+        venue.cancel_order(&order_id).await.unwrap();
+        assert!(venue.cancel_order(&order_id).await.is_err());
+        assert_eq!(venue.order_state(&order_id).await.unwrap().status, OrderStatus::Canceled);
+
+        // filled at the price after the latency
+        venue.place_order(order(Side::Buy, dec!(1), None)).await.unwrap();
+        markets
+            .write()
+            .unwrap()
+            .update(
+                "SOL/USDT".parse().unwrap(),
+                Venue { exchange_id: "binance", market: "SOLUSDT".to_string() },
+                dec!(200)
+            );
+
+        let fill = timeout(Duration::from_secs(1), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.price, dec!(200.2));
+        assert!(fills.try_recv().is_err());
+    }
+
+    // read as PaperConfig::from_env reads them, without changing the environment of the
+    // tests running alongside
+    #[test]
+    fn test_config_balances() {
+        let balances = config::parse_assets::<Decimal>("PAPER_BALANCES", "usdt=1000, SOL=2.5");
+        let balances = balances.unwrap();
+        assert_eq!(balances["USDT"], dec!(1000));
+        assert_eq!(balances["SOL"], dec!(2.5));
+
+        assert!(config::parse_assets::<Decimal>("PAPER_BALANCES", "USDT").is_err());
+    }
+}
@@ -0,0 +1,869 @@
+use std::{
+    collections::{ BTreeMap, HashMap },
+    sync::{ Arc, Mutex },
+    time::{ Duration, SystemTime },
+};
+
+use base64::prelude::*;
+use borsh::BorshDeserialize;
+use ed25519_dalek::SigningKey;
+use rust_decimal::{ prelude::ToPrimitive, Decimal };
+use serde::{ de::DeserializeOwned, Deserialize };
+use serde_json::{ json, Value };
+use tokio::{ sync::broadcast, time::{ sleep, Instant } };
+
+use crate::{
+    config::{ self, ConfigError },
+    exchange::solana::{ self, symbol, Dex, PoolState, TICK_ARRAY_SIZE },
+};
+
+use super::{
+    transaction::{
+        self,
+        address,
+        associated_token_address,
+        find_program_address,
+        pubkey,
+        AccountMeta,
+        Instruction,
+        Message,
+        Pubkey,
+        Transaction,
+    },
+    ExecutionError,
+    ExecutionVenue,
+    Fill,
+    OrderRequest,
+    OrderState,
+    OrderStatus,
+    Side,
+};
+
+const EXCHANGE_ID: &str = "solana";
+// anchor discriminator of the clmm swap_v2 instruction
+const SWAP_V2: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+// tick arrays passed to a swap, the one of the current tick and the next ones crossed
+const SWAP_TICK_ARRAYS: usize = 3;
+const CONFIRM_INTERVAL: Duration = Duration::from_millis(500);
+// about when the blockhash expires and the transaction cannot land anymore
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Swap through a Raydium CLMM pool, in raw token amounts. Token accounts are the
+/// associated ones of the owner, so SOL has to be held wrapped.
+pub struct Swap<'a> {
+    pub pool: Pubkey,
+    pub pool_state: &'a PoolState,
+    pub owner: Pubkey,
+    // token 0 in for token 1
+    pub zero_for_one: bool,
+    // `amount` is the exact input, or the exact output
+    pub is_base_input: bool,
+    pub amount: u64,
+    // min output of an exact input, max input of an exact output
+    pub other_amount_threshold: u64,
+    pub tick_arrays: Vec<Pubkey>,
+}
+
+impl Swap<'_> {
+    /// The swap with its compute budget and priority fee, creating the output token
+    /// account if needed.
+    pub fn instructions(&self, compute_units: u32, priority_fee: u64) -> Vec<Instruction> {
+        let token_program = pubkey(transaction::TOKEN_PROGRAM_ID).unwrap();
+        let (_, output_mint) = self.mints();
+
+        vec![
+            transaction::set_compute_unit_limit(compute_units),
+            transaction::set_compute_unit_price(priority_fee),
+            transaction::create_associated_token_account_idempotent(
+                &self.owner,
+                &self.owner,
+                &output_mint,
+                &token_program
+            ),
+            self.swap_instruction()
+        ]
+    }
+
+    fn mints(&self) -> (Pubkey, Pubkey) {
+        let (mint_0, mint_1) = (self.pool_state.token_mint_0, self.pool_state.token_mint_1);
+        if self.zero_for_one { (mint_0, mint_1) } else { (mint_1, mint_0) }
+    }
+
+    fn swap_instruction(&self) -> Instruction {
+        let token_program = pubkey(transaction::TOKEN_PROGRAM_ID).unwrap();
+        let (input_mint, output_mint) = self.mints();
+
+        let (vault_0, vault_1) = (self.pool_state.token_vault_0, self.pool_state.token_vault_1);
+        let (input_vault, output_vault) = if self.zero_for_one {
+            (vault_0, vault_1)
+        } else {
+            (vault_1, vault_0)
+        };
+
+        let mut accounts = vec![
+            AccountMeta { pubkey: self.owner, is_signer: true, is_writable: false },
+            AccountMeta::readonly(self.pool_state.amm_config),
+            AccountMeta::writable(self.pool),
+            AccountMeta::writable(
+                associated_token_address(&self.owner, &input_mint, &token_program)
+            ),
+            AccountMeta::writable(
+                associated_token_address(&self.owner, &output_mint, &token_program)
+            ),
+            AccountMeta::writable(input_vault),
+            AccountMeta::writable(output_vault),
+            AccountMeta::writable(self.pool_state.observation_key),
+            AccountMeta::readonly(token_program),
+            AccountMeta::readonly(pubkey(transaction::TOKEN_2022_PROGRAM_ID).unwrap()),
+            AccountMeta::readonly(pubkey(transaction::MEMO_PROGRAM_ID).unwrap()),
+            AccountMeta::readonly(input_mint),
+            AccountMeta::readonly(output_mint),
+            // looked up by the program for ticks beyond the pool bitmap
+            AccountMeta::readonly(
+                find_program_address(
+                    &[b"pool_tick_array_bitmap_extension", &self.pool],
+                    &clmm_program_id()
+                )
+            )
+        ];
+        accounts.extend(self.tick_arrays.iter().copied().map(AccountMeta::writable));
+
+        let mut data = SWAP_V2.to_vec();
+        data.extend(self.amount.to_le_bytes());
+        data.extend(self.other_amount_threshold.to_le_bytes());
+        data.extend(0_u128.to_le_bytes()); // no price limit, the threshold protects the swap
+        data.push(u8::from(self.is_base_input));
+
+        Instruction { program_id: clmm_program_id(), accounts, data }
+    }
+}
+
+fn clmm_program_id() -> Pubkey {
+    pubkey(Dex::Raydium.program_id()).unwrap()
+}
+
+pub fn tick_array_address(pool: &Pubkey, start_tick_index: i32) -> Pubkey {
+    find_program_address(
+        &[b"tick_array", pool, &start_tick_index.to_be_bytes()],
+        &clmm_program_id()
+    )
+}
+
+// start ticks of the array of the current tick and of the next ones in the swap direction
+fn tick_array_starts(pool_state: &PoolState, zero_for_one: bool) -> Vec<i32> {
+    let span = i32::from(pool_state.tick_spacing) * TICK_ARRAY_SIZE;
+    let start = pool_state.tick_current.div_euclid(span) * span;
+    let step = if zero_for_one { -span } else { span };
+
+    (0..SWAP_TICK_ARRAYS as i32).map(|idx| start + idx * step).collect()
+}
+
+pub struct RaydiumConfig {
+    pub rpc_url: String,
+    pub keypair: SigningKey,
+    pub compute_units: u32,
+    pub priority_fee: u64, // micro lamports per compute unit
+}
+
+impl RaydiumConfig {
+    /// The solana cli keypair file at `SOLANA_KEYPAIR_PATH`, sending to `SOLANA_RPC_URL`
+    /// or helius when only `HELIUS_API_KEY` is set, with `SOLANA_COMPUTE_UNITS` and
+    /// `SOLANA_PRIORITY_FEE` in micro lamports per unit.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let rpc_url = solana::rpc_url()?;
+
+        let path = config
+            ::env_parse::<String>("SOLANA_KEYPAIR_PATH")?
+            .ok_or(ConfigError::Missing("SOLANA_KEYPAIR_PATH"))?;
+        let invalid = |reason: String| ConfigError::Invalid("SOLANA_KEYPAIR_PATH", reason);
+
+        // a json array of the secret and public key bytes
+        let keypair = std::fs
+            ::read_to_string(&path)
+            .map_err(|err| invalid(err.to_string()))
+            .and_then(|json| {
+                serde_json::from_str::<Vec<u8>>(&json).map_err(|err| invalid(err.to_string()))
+            })?
+            .try_into()
+            .map_err(|_| invalid("expected 64 bytes".to_string()))
+            .and_then(|bytes| {
+                SigningKey::from_keypair_bytes(&bytes).map_err(|err| invalid(err.to_string()))
+            })?;
+
+        Ok(RaydiumConfig {
+            rpc_url,
+            keypair,
+            compute_units: config::env_parse("SOLANA_COMPUTE_UNITS")?.unwrap_or(250_000),
+            priority_fee: config::env_parse("SOLANA_PRIORITY_FEE")?.unwrap_or(10_000),
+        })
+    }
+}
+
+/// Orders as swaps on the Raydium CLMM pool of the market, filled once their transaction
+/// is confirmed.
+pub struct RaydiumVenue {
+    client: Client,
+    orders: Arc<Mutex<HashMap<String, OrderState>>>,
+    fills: broadcast::Sender<Fill>,
+}
+
+impl RaydiumVenue {
+    pub fn new(config: RaydiumConfig) -> Self {
+        Self {
+            client: Client { http: reqwest::Client::new(), config: Arc::new(config) },
+            orders: Arc::new(Mutex::new(HashMap::new())),
+            fills: broadcast::channel(1024).0,
+        }
+    }
+
+    fn owner(&self) -> Pubkey {
+        self.client.config.keypair.verifying_key().to_bytes()
+    }
+}
+
+impl ExecutionVenue for RaydiumVenue {
+    async fn place_order(&mut self, order: OrderRequest) -> Result<String, ExecutionError> {
+        let rejected = |reason: &str| ExecutionError::Rejected(reason.to_string());
+
+        // the limit is what keeps the swap from being sandwiched
+        let limit_price = order.limit_price.ok_or(rejected("swaps need a limit price"))?;
+
+        let pool = pubkey(&order.market).map_err(|err| rejected(&err.to_string()))?;
+        let pool_state = self.client.pool_state(&order.market).await?;
+
+        let symbol_0 = symbol(&pool_state.token_mint_0);
+        let symbol_1 = symbol(&pool_state.token_mint_1);
+        let (base, quote) = (&order.instrument.base, &order.instrument.quote);
+
+        let (base_decimals, quote_decimals, base_is_token_0) = if
+            symbol_0.eq_ignore_ascii_case(base) &&
+            symbol_1.eq_ignore_ascii_case(quote)
+        {
+            (pool_state.mint_decimals_0, pool_state.mint_decimals_1, true)
+        } else if symbol_1.eq_ignore_ascii_case(base) && symbol_0.eq_ignore_ascii_case(quote) {
+            (pool_state.mint_decimals_1, pool_state.mint_decimals_0, false)
+        } else {
+            let reason = format!("pool {} does not trade {}", order.market, order.instrument);
+            return Err(rejected(&reason));
+        };
+
+        // buying the exact quantity for at most its limit, selling it for at least that
+        let (zero_for_one, is_base_input) = match order.side {
+            Side::Buy => (!base_is_token_0, false),
+            Side::Sell => (base_is_token_0, true),
+        };
+
+        let amount = raw_amount(order.quantity, base_decimals, false);
+        let other_amount_threshold = raw_amount(
+            order.quantity * limit_price,
+            quote_decimals,
+            order.side == Side::Buy
+        );
+        let (Some(amount), Some(other_amount_threshold)) = (amount, other_amount_threshold) else {
+            return Err(rejected("amount out of range"));
+        };
+
+        // only tick arrays holding liquidity exist
+        let candidates = tick_array_starts(&pool_state, zero_for_one)
+            .into_iter()
+            .map(|start| tick_array_address(&pool, start))
+            .collect::<Vec<_>>();
+        let tick_arrays = self.client.existing_accounts(&candidates).await?;
+        if tick_arrays.is_empty() {
+            return Err(rejected("no liquidity around the current tick"));
+        }
+
+        let swap = Swap {
+            pool,
+            pool_state: &pool_state,
+            owner: self.owner(),
+            zero_for_one,
+            is_base_input,
+            amount,
+            other_amount_threshold,
+            tick_arrays,
+        };
+
+        let config = &self.client.config;
+        let message = Message::new(
+            &swap.instructions(config.compute_units, config.priority_fee),
+            &swap.owner,
+            self.client.latest_blockhash().await?
+        );
+        let transaction = Transaction::sign(message, &config.keypair);
+
+        let signature = self.client.send_transaction(&transaction).await?;
+
+        self.orders.lock().unwrap().insert(signature.clone(), OrderState {
+            status: OrderStatus::Open,
+            executed_quantity: Decimal::ZERO,
+        });
+
+        let owner = address(&self.owner());
+        let mints = if base_is_token_0 {
+            (address(&pool_state.token_mint_0), address(&pool_state.token_mint_1))
+        } else {
+            (address(&pool_state.token_mint_1), address(&pool_state.token_mint_0))
+        };
+
+        tokio::spawn(
+            confirm(
+                self.client.clone(),
+                signature.clone(),
+                order,
+                owner,
+                mints,
+                self.orders.clone(),
+                self.fills.clone()
+            )
+        );
+
+        Ok(signature)
+    }
+
+    async fn cancel_order(&mut self, order_id: &str) -> Result<(), ExecutionError> {
+        Err(ExecutionError::Rejected(format!("transaction {order_id} cannot be canceled")))
+    }
+
+    async fn order_state(&mut self, order_id: &str) -> Result<OrderState, ExecutionError> {
+        self.orders
+            .lock()
+            .unwrap()
+            .get(order_id)
+            .copied()
+            .ok_or(ExecutionError::NotFound(order_id.to_string()))
+    }
+
+    async fn balances(&mut self) -> Result<BTreeMap<String, Decimal>, ExecutionError> {
+        let params = json!([
+            address(&self.owner()),
+            {"programId": transaction::TOKEN_PROGRAM_ID},
+            {"encoding": "jsonParsed"}
+        ]);
+        let response = self.client.rpc::<RpcValue<Vec<TokenAccount>>>(
+            "getTokenAccountsByOwner",
+            params
+        ).await?;
+
+        let mut balances = BTreeMap::new();
+        for account in response.value {
+            let info = account.account.data.parsed.info;
+            let Ok(mint) = pubkey(&info.mint) else {
+                continue;
+            };
+
+            *balances.entry(symbol(&mint)).or_default() += info.token_amount.decimal();
+        }
+
+        Ok(balances)
+    }
+
+    fn fills(&self) -> broadcast::Receiver<Fill> {
+        self.fills.subscribe()
+    }
+}
+
+// the amount in the smallest unit of the token
+fn raw_amount(amount: Decimal, decimals: u8, round_up: bool) -> Option<u64> {
+    let raw = amount * Decimal::from(10_u64.pow(u32::from(decimals)));
+    (if round_up { raw.ceil() } else { raw.floor() }).to_u64()
+}
+
+// waits for the transaction to land, reporting the fill from the owner token balances
+async fn confirm(
+    client: Client,
+    signature: String,
+    order: OrderRequest,
+    owner: String,
+    (base_mint, quote_mint): (String, String),
+    orders: Arc<Mutex<HashMap<String, OrderState>>>,
+    fills: broadcast::Sender<Fill>
+) {
+    let deadline = Instant::now() + CONFIRM_TIMEOUT;
+    let set_status = |status, executed_quantity| {
+        orders.lock().unwrap().insert(signature.clone(), OrderState { status, executed_quantity });
+    };
+
+    while Instant::now() < deadline {
+        sleep(CONFIRM_INTERVAL).await;
+
+        let status = match client.signature_status(&signature).await {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                continue;
+            }
+            Err(err) => {
+                log::debug!("{EXCHANGE_ID} {signature} {err}");
+                continue;
+            }
+        };
+
+        if status.err.is_some() {
+            log::debug!("{EXCHANGE_ID} {signature} failed {:?}", status.err);
+            set_status(OrderStatus::Expired, Decimal::ZERO);
+            return;
+        }
+
+        if status.confirmation_status.as_deref() == Some("processed") {
+            continue;
+        }
+
+        let meta = match client.transaction_meta(&signature).await {
+            Ok(meta) => meta,
+            Err(err) => {
+                log::warn!("{EXCHANGE_ID} {signature} landed without its balances {err}");
+                set_status(OrderStatus::Filled, order.quantity);
+                return;
+            }
+        };
+
+        let base = meta.balance_change(&owner, &base_mint).abs();
+        let quote = meta.balance_change(&owner, &quote_mint).abs();
+        set_status(OrderStatus::Filled, base);
+
+        // the pool fee is part of the price, the network fee is paid in sol
+        if let Some(price) = quote.checked_div(base) {
+            let _ = fills.send(Fill {
+                exchange_id: EXCHANGE_ID,
+                order_id: signature,
+                client_order_id: order.client_order_id,
+                market: order.market,
+                side: order.side,
+                quantity: base,
+                price,
+                fee: Decimal::ZERO,
+                timestamp: SystemTime::now(),
+            });
+        }
+        return;
+    }
+
+    set_status(OrderStatus::Expired, Decimal::ZERO);
+}
+
+#[derive(Clone)]
+struct Client {
+    http: reqwest::Client,
+    config: Arc<RaydiumConfig>,
+}
+
+impl Client {
+    async fn rpc<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value
+    ) -> Result<T, ExecutionError> {
+        let transport = |err: reqwest::Error| ExecutionError::Transport(err.to_string());
+
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        let body = self.http
+            .post(&self.config.rpc_url)
+            .header("Content-Type", "application/json")
+            .body(request.to_string())
+            .send().await
+            .map_err(transport)?
+            .text().await
+            .map_err(transport)?;
+
+        let response = serde_json
+            ::from_str::<RpcResponse<T>>(&body)
+            .map_err(|err| ExecutionError::Transport(format!("{method} {err}")))?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(ExecutionError::Rejected(error.message)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(ExecutionError::Transport(format!("{method} without result"))),
+        }
+    }
+
+    async fn pool_state(&self, pool: &str) -> Result<PoolState, ExecutionError> {
+        let params = json!([pool, {"encoding": "base64"}]);
+        let response = self.rpc::<RpcValue<Option<RpcAccount>>>("getAccountInfo", params).await?;
+
+        response.value
+            .ok_or(ExecutionError::NotFound(pool.to_string()))?
+            .bytes()
+            .and_then(|bytes| PoolState::try_from_slice(&bytes))
+            .map_err(|err| ExecutionError::Rejected(format!("not a clmm pool {pool} {err}")))
+    }
+
+    // the accounts that exist, in order
+    async fn existing_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Pubkey>, ExecutionError> {
+        let addresses = pubkeys.iter().map(address).collect::<Vec<_>>();
+        let params = json!([addresses, {
+            "encoding": "base64",
+            "dataSlice": {"offset": 0, "length": 0}
+        }]);
+        let response = self.rpc::<RpcValue<Vec<Option<Value>>>>(
+            "getMultipleAccounts",
+            params
+        ).await?;
+
+        Ok(
+            pubkeys
+                .iter()
+                .zip(response.value)
+                .filter_map(|(pubkey, account)| account.map(|_| *pubkey))
+                .collect()
+        )
+    }
+
+    async fn latest_blockhash(&self) -> Result<Pubkey, ExecutionError> {
+        let params = json!([{"commitment": "confirmed"}]);
+        let response = self.rpc::<RpcValue<Blockhash>>("getLatestBlockhash", params).await?;
+
+        pubkey(&response.value.blockhash).map_err(|err| ExecutionError::Transport(err.to_string()))
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<String, ExecutionError> {
+        let encoded = BASE64_STANDARD.encode(transaction.serialize());
+        let params = json!([encoded, {"encoding": "base64"}]);
+        let signature = self.rpc::<String>("sendTransaction", params).await?;
+
+        if signature != transaction.signature() {
+            let reason = format!("sent {signature} for another transaction");
+            return Err(ExecutionError::Transport(reason));
+        }
+        Ok(signature)
+    }
+
+    async fn signature_status(
+        &self,
+        signature: &str
+    ) -> Result<Option<SignatureStatus>, ExecutionError> {
+        let params = json!([[signature]]);
+        let response = self.rpc::<RpcValue<Vec<Option<SignatureStatus>>>>(
+            "getSignatureStatuses",
+            params
+        ).await?;
+
+        Ok(response.value.into_iter().next().flatten())
+    }
+
+    async fn transaction_meta(&self, signature: &str) -> Result<TransactionMeta, ExecutionError> {
+        let params = json!([signature, {
+            "encoding": "json",
+            "commitment": "confirmed",
+            "maxSupportedTransactionVersion": 0
+        }]);
+        let response = self.rpc::<ConfirmedTransaction>("getTransaction", params).await?;
+        Ok(response.meta)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcValue<T> {
+    value: T,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcAccount {
+    data: (String, String), // base64 data and its encoding
+}
+
+impl RpcAccount {
+    fn bytes(&self) -> Result<Vec<u8>, std::io::Error> {
+        BASE64_STANDARD
+            .decode(&self.data.0)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Blockhash {
+    blockhash: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SignatureStatus {
+    err: Option<Value>,
+    #[serde(rename = "confirmationStatus")]
+    confirmation_status: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ConfirmedTransaction {
+    meta: TransactionMeta,
+}
+
+#[derive(Deserialize, Debug)]
+struct TransactionMeta {
+    #[serde(rename = "preTokenBalances", default)]
+    pre_token_balances: Vec<TokenBalance>,
+    #[serde(rename = "postTokenBalances", default)]
+    post_token_balances: Vec<TokenBalance>,
+}
+
+impl TransactionMeta {
+    // how much of the mint the owner received, negative when spent
+    fn balance_change(&self, owner: &str, mint: &str) -> Decimal {
+        let total = |balances: &[TokenBalance]| {
+            balances
+                .iter()
+                .filter(|balance| balance.mint == mint && balance.owner.as_deref() == Some(owner))
+                .map(|balance| balance.ui_token_amount.decimal())
+                .sum::<Decimal>()
+        };
+
+        total(&self.post_token_balances) - total(&self.pre_token_balances)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenBalance {
+    mint: String,
+    owner: Option<String>,
+    #[serde(rename = "uiTokenAmount")]
+    ui_token_amount: TokenAmount,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenAmount {
+    amount: String, // raw
+    decimals: u32,
+}
+
+impl TokenAmount {
+    fn decimal(&self) -> Decimal {
+        self.amount
+            .parse::<i64>()
+            .map(|amount| Decimal::new(amount, self.decimals))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenAccount {
+    account: ParsedAccount,
+}
+
+#[derive(Deserialize, Debug)]
+struct ParsedAccount {
+    data: ParsedData,
+}
+
+#[derive(Deserialize, Debug)]
+struct ParsedData {
+    parsed: ParsedTokenAccount,
+}
+
+#[derive(Deserialize, Debug)]
+struct ParsedTokenAccount {
+    info: TokenAccountInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenAccountInfo {
+    mint: String,
+    #[serde(rename = "tokenAmount")]
+    token_amount: TokenAmount,
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{ routing::post, Json, Router };
+    use ed25519_dalek::{ Signature, Verifier };
+    use rust_decimal_macros::dec;
+    use tokio::{ net::TcpListener, time::timeout };
+
+    use crate::exchange::solana::tests::POOL_DATA;
+
+    use super::*;
+
+    const POOL: &str = "3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF";
+    const BLOCKHASH: &str = "EkSnNWid2cvwEVnVx9aBqawnmiCNiDgp3gUdkDPTKN1N";
+
+    fn keypair() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn pool_state() -> PoolState {
+        PoolState::try_from_slice(&BASE64_STANDARD.decode(POOL_DATA).unwrap()).unwrap()
+    }
+
+    // stand-in for a validator rpc, landing every transaction at 145.1 USDT per SOL
+    fn mock_rpc() -> Router {
+        async fn handle(Json(request): Json<Value>) -> Json<Value> {
+            let params = &request["params"];
+            let owner = address(&keypair().verifying_key().to_bytes());
+            let balance = |mint: &str, amount: &str, decimals: u32| {
+                json!({"accountIndex": 1, "mint": mint, "owner": owner,
+                    "uiTokenAmount": {"amount": amount, "decimals": decimals}})
+            };
+            let (sol, usdt) = (
+                "So11111111111111111111111111111111111111112",
+                "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB",
+            );
+
+            let result = match request["method"].as_str().unwrap() {
+                "getAccountInfo" => {
+                    json!({"context": {"slot": 1}, "value": {"data": [POOL_DATA, "base64"]}})
+                }
+                "getMultipleAccounts" => {
+                    let accounts = params[0].as_array().unwrap().len();
+                    let mut value = vec![json!(null); accounts];
+                    value[0] = json!({"data": ["", "base64"]});
+                    json!({"context": {"slot": 1}, "value": value})
+                }
+                "getLatestBlockhash" => {
+                    json!({"context": {"slot": 1}, "value": {"blockhash": BLOCKHASH}})
+                }
+                "sendTransaction" => {
+                    let bytes = BASE64_STANDARD.decode(params[0].as_str().unwrap()).unwrap();
+                    let signature = Signature::from_slice(&bytes[1..65]).unwrap();
+                    if keypair().verifying_key().verify(&bytes[65..], &signature).is_err() {
+                        return Json(
+                            json!({"jsonrpc": "2.0", "id": 1,
+                                "error": {"code": -32003, "message": "invalid signature"}})
+                        );
+                    }
+                    json!(bs58::encode(signature.to_bytes()).into_string())
+                }
+                "getSignatureStatuses" => {
+                    json!({"context": {"slot": 1},
+                        "value": [{"confirmationStatus": "confirmed", "err": null}]})
+                }
+                "getTransaction" => {
+                    json!({"meta": {
+                        "preTokenBalances": [balance(usdt, "1000000000", 6)],
+                        "postTokenBalances": [
+                            balance(usdt, "854900000", 6), balance(sol, "1000000000", 9)
+                        ],
+                    }})
+                }
+                "getTokenAccountsByOwner" => {
+                    let account = |mint: &str, amount: &str, decimals: u32| {
+                        json!({"account": {"data": {"parsed": {"info": {"mint": mint,
+                            "tokenAmount": {"amount": amount, "decimals": decimals}}}}}})
+                    };
+                    json!({"context": {"slot": 1}, "value": [
+                        account(sol, "2500000000", 9), account(usdt, "1000000000", 6)
+                    ]})
+                }
+                method => panic!("unexpected {method}"),
+            };
+
+            Json(json!({"jsonrpc": "2.0", "id": 1, "result": result}))
+        }
+
+        Router::new().route("/", post(handle))
+    }
+
+    async fn start() -> RaydiumVenue {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, mock_rpc()).await });
+
+        RaydiumVenue::new(RaydiumConfig {
+            rpc_url,
+            keypair: keypair(),
+            compute_units: 250_000,
+            priority_fee: 10_000,
+        })
+    }
+
+    #[test]
+    fn test_swap_transaction() {
+        let pool_state = pool_state();
+        let pool = pubkey(POOL).unwrap();
+
+        // selling 1 SOL for at least 145 USDT
+        let swap = Swap {
+            pool,
+            pool_state: &pool_state,
+            owner: keypair().verifying_key().to_bytes(),
+            zero_for_one: true,
+            is_base_input: true,
+            amount: 1_000_000_000,
+            other_amount_threshold: 145_000_000,
+            tick_arrays: tick_array_starts(&pool_state, true)
+                .into_iter()
+                .map(|start| tick_array_address(&pool, start))
+                .collect(),
+        };
+
+        let message = Message::new(
+            &swap.instructions(250_000, 10_000),
+            &swap.owner,
+            pubkey(BLOCKHASH).unwrap()
+        );
+        let transaction = Transaction::sign(message, &keypair());
+
+        // built by the solana sdk from the same instructions
+        assert_eq!(BASE64_STANDARD.encode(transaction.serialize()), SWAP_TRANSACTION);
+    }
+
+    #[test]
+    fn test_tick_array_starts() {
+        let pool_state = pool_state();
+        assert_eq!(pool_state.tick_current, -19310);
+
+        assert_eq!(tick_array_starts(&pool_state, true), vec![-19320, -19380, -19440]);
+        assert_eq!(tick_array_starts(&pool_state, false), vec![-19320, -19260, -19200]);
+    }
+
+    #[tokio::test]
+    async fn test_orders() {
+        let mut venue = start().await;
+        let mut fills = venue.fills();
+
+        let order = OrderRequest {
+            client_order_id: "arb-1-b".to_string(),
+            market: POOL.to_string(),
+            instrument: "SOL/USDT".parse().unwrap(),
+            side: Side::Buy,
+            quantity: dec!(1),
+            limit_price: Some(dec!(146)),
+        };
+
+        let order_id = venue.place_order(order.clone()).await.unwrap();
+        assert_eq!(venue.order_state(&order_id).await.unwrap().status, OrderStatus::Open);
+
+        let fill = timeout(Duration::from_secs(5), fills.recv()).await.unwrap().unwrap();
+        assert_eq!(fill.order_id, order_id);
+        assert_eq!((fill.quantity, fill.price), (dec!(1), dec!(145.1)));
+        assert_eq!(venue.order_state(&order_id).await.unwrap(), OrderState {
+            status: OrderStatus::Filled,
+            executed_quantity: dec!(1),
+        });
+
+        assert!(venue.cancel_order(&order_id).await.is_err());
+        let market_order = OrderRequest { limit_price: None, ..order.clone() };
+        assert!(venue.place_order(market_order).await.is_err());
+
+        let other_instrument = OrderRequest { instrument: "SOL/USDC".parse().unwrap(), ..order };
+        assert!(venue.place_order(other_instrument).await.is_err());
+
+        let balances = venue.balances().await.unwrap();
+        assert_eq!(balances["SOL"], dec!(2.5));
+        assert_eq!(balances["USDT"], dec!(1000));
+    }
+
+    const SWAP_TRANSACTION: &str = concat!(
+        "AU8S8kpapReemQfwm/gERQsyP0kUqCpUyss6hs305JsjAGD/V7s7XTlacmt5efbPh4L9jqR6IdM9kCjQdpkfBQ4B",
+        "AAsV6kpsY+KcUgq+9VB7Ey7F+ZVHdq6+vnuSQh7qaRRG0iwaaTyH1VZ/m4Cf0fJ5U9YhpcPX5C6pBKoM8elRwvX7",
+        "RilVamTMwt2uLJbU///D9LwGSmDN0NRrzLxw47UbZosoPaLXOxB16kQVVCj/4Sa0rRfHENAhBxESBz5RQGeDj8Fp",
+        "wjHQr5OLsd9of6ZExFGlbNCjNgXkoC3ejO7/7zVqTYEyI/jEKpluZxgXhpNa4IDc5FCdTKZKdoFy6RueBdiWjpeJ",
+        "jFauRi65JX8K8htc/MH5S4n/PccIBLTeHyO5oGuv4uF8HEPO+OkQySkMhaiuaWSydv0O8Kel4HJ7vB2GntSYQkW6",
+        "R1/0j91vYEGjE9BHdEvunNLz/Y8xitVOL11K18ZfAJEKszuGqJw+h6w1npcq8jKKJQiNKJ7m9p0kIFwAAAAAAAAA",
+        "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAMGRm/lIRcy/+ytunLDm+e8jOW7xfcSayxDmzpAAAAABUpTWpkpIQZN",
+        "JOhxYNo4fHw1td28kruB5B+oQEEFRI0Gm4hX/quBhPtof2NGGMA12sQ53BrrO1WYoPAAAAAAAQbd9uHXZaGT2cvh",
+        "Rs7reawctIXtX1s3kTqM9YV+/wCpBt324e51j94YQl285GzN2rYa/E2DuQ0n/r35KNihi/waizZvleaVpWiOP5IU",
+        "WbUMbpaoPa4oogKaHB1j82y3woFuZmMMO7ck3Fnkn2zEMG5gOmqsygb6PjTitArVl52NjJclj04kifG7PRApFI4N",
+        "gwtaE5na/xCEBI572Nvp+Fml1cqeBM9dtZC3FLov4yyxWRM/wcGStyJX/QfTnLBAHs4BDmCv7bInF71jGS9UFFo/",
+        "llozu4LSxwKess4eIIJkzEkOkozS44c7s0P8ldozF5ymD02/RsLDbpEpnVXU5rkECwAFApDQAwALAAkDECcAAAAA",
+        "AAASBgAJABQKDgEBExEAEQIDCQYBBw4PDA0UEAQIBSkrBO0LGskeYgDKmjsAAAAAQIakCAAAAAAAAAAAAAAAAAAA",
+        "AAAAAAAAAQ==",
+    );
+}
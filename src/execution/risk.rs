@@ -0,0 +1,409 @@
+use std::{
+    collections::{ BTreeMap, HashMap, VecDeque },
+    path::PathBuf,
+    sync::{ Arc, Mutex },
+    time::{ Duration, Instant, SystemTime, UNIX_EPOCH },
+};
+
+use rust_decimal::Decimal;
+use tokio::{ signal::unix::{ signal, SignalKind }, sync::watch };
+
+use crate::{
+    config::{ self, ConfigError },
+    engine::Engine,
+    markets::{ Markets, SharedMarkets },
+    Instrument,
+    Venue,
+};
+
+use super::{ ExecutionError, Fill, OrderRequest, Side };
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Limits every order is checked against before being sent, those not set not checked.
+#[derive(Debug, Clone, Default)]
+pub struct RiskLimits {
+    // of an order, in its quote
+    pub max_notional: Option<Decimal>,
+    // base bought minus sold, either way, per asset and venue
+    pub max_position: BTreeMap<String, Decimal>,
+    // since midnight utc, valued at the consensus prices
+    pub max_daily_loss: Option<Decimal>,
+    pub max_orders_per_second: Option<usize>,
+    // distance of a limit from the consensus price
+    pub price_band_bps: Option<Decimal>,
+}
+
+impl RiskLimits {
+    /// `RISK_MAX_NOTIONAL`, `RISK_MAX_POSITION` as `<asset>=<amount>` items, e.g. `SOL=100`,
+    /// `RISK_MAX_DAILY_LOSS`, `RISK_MAX_ORDERS_PER_SECOND` and `RISK_PRICE_BAND_BPS`.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(RiskLimits {
+            max_notional: config::env_parse("RISK_MAX_NOTIONAL")?,
+            max_position: config::env_assets("RISK_MAX_POSITION")?.unwrap_or_default(),
+            max_daily_loss: config::env_parse("RISK_MAX_DAILY_LOSS")?,
+            max_orders_per_second: config::env_parse("RISK_MAX_ORDERS_PER_SECOND")?,
+            price_band_bps: config::env_parse("RISK_PRICE_BAND_BPS")?,
+        })
+    }
+}
+
+/// Stops new orders once engaged, for good.
+#[derive(Clone)]
+pub struct KillSwitch(Arc<watch::Sender<bool>>);
+
+impl Default for KillSwitch {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
+
+impl KillSwitch {
+    pub fn engage(&self, reason: &str) {
+        if !self.0.send_replace(true) {
+            log::warn!("kill switch engaged by {reason}");
+        }
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    pub async fn engaged(&self) {
+        let _ = self.0.subscribe().wait_for(|engaged| *engaged).await;
+    }
+}
+
+/// Engages the kill switch on sigusr1 or once the file at `path` exists.
+pub async fn watch_kill_switch(kill_switch: KillSwitch, path: Option<PathBuf>) {
+    let mut sigusr1 = signal(SignalKind::user_defined1()).expect("cannot listen for sigusr1");
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    while !kill_switch.is_engaged() {
+        tokio::select! {
+            _ = sigusr1.recv() => kill_switch.engage("sigusr1"),
+            _ = interval.tick() => {
+                if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+                    kill_switch.engage(&path.display().to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Pre-trade checks, fed with the fills of every venue.
+#[derive(Clone)]
+pub struct Risk {
+    limits: Arc<RiskLimits>,
+    markets: SharedMarkets,
+    kill_switch: KillSwitch,
+    state: Arc<Mutex<RiskState>>,
+}
+
+#[derive(Default)]
+struct RiskState {
+    // orders sent within the last second
+    sent: VecDeque<Instant>,
+    // base bought minus sold per market
+    positions: HashMap<Venue, (Instrument, Decimal)>,
+    // days since the epoch the flows are for
+    day: u64,
+    // base and quote received today per instrument, in the common quote when converted
+    flows: HashMap<Instrument, (Decimal, Decimal)>,
+}
+
+impl RiskState {
+    fn roll(&mut self, day: u64) {
+        if day != self.day {
+            self.day = day;
+            self.flows.clear();
+        }
+    }
+
+    // the flows valued at the consensus prices, those without any left out
+    fn daily_pnl(&self, markets: &Markets) -> Decimal {
+        self.flows
+            .iter()
+            .filter_map(|(instrument, (base, quote))| {
+                let price = markets.engine(instrument).and_then(Engine::median_price);
+                price.map(|price| quote + base * price)
+            })
+            .sum()
+    }
+}
+
+impl Risk {
+    pub fn new(limits: RiskLimits, markets: SharedMarkets, kill_switch: KillSwitch) -> Self {
+        Self { limits: Arc::new(limits), markets, kill_switch, state: Default::default() }
+    }
+
+    /// Checks orders going out together on their venues, counted against the order rate
+    /// only when all of them pass.
+    pub fn check(&self, orders: &[(&'static str, &OrderRequest)]) -> Result<(), ExecutionError> {
+        let rejected = |reason: String| Err(ExecutionError::Rejected(reason));
+
+        if self.kill_switch.is_engaged() {
+            return rejected("kill switch engaged".to_string());
+        }
+
+        let markets = self.markets.read().unwrap();
+        let mut state = self.state.lock().unwrap();
+
+        let now = Instant::now();
+        while state.sent.front().is_some_and(|sent| now - *sent >= Duration::from_secs(1)) {
+            state.sent.pop_front();
+        }
+
+        if let Some(max) = self.limits.max_orders_per_second {
+            if state.sent.len() + orders.len() > max {
+                return rejected(format!("more than {max} orders per second"));
+            }
+        }
+
+        if let Some(max) = self.limits.max_daily_loss {
+            state.roll(today());
+            let pnl = state.daily_pnl(&markets);
+            if -pnl > max {
+                return rejected(format!("daily loss {} over {max}", -pnl.round_dp(2)));
+            }
+        }
+
+        for (exchange_id, order) in orders {
+            let venue = Venue { exchange_id, market: order.market.clone() };
+            let consensus = consensus_price(&markets, &venue);
+
+            if let Some(max) = self.limits.max_notional {
+                let Some(price) = order.limit_price.or(consensus) else {
+                    return rejected(format!("no price for {}", order.instrument));
+                };
+
+                let notional = order.quantity * price;
+                if notional > max {
+                    return rejected(format!("notional {notional} over {max}"));
+                }
+            }
+
+            if let (Some(band), Some(limit), Some(consensus)) = (
+                self.limits.price_band_bps,
+                order.limit_price,
+                consensus,
+            ) {
+                let bps = ((limit - consensus) / consensus).abs() * Decimal::from(10_000);
+                if bps > band {
+                    let reason = format!("limit {limit} {} bps from {consensus}", bps.round_dp(2));
+                    return rejected(reason);
+                }
+            }
+
+            if let Some(max) = self.limits.max_position.get(&order.instrument.base) {
+                let position = state.positions
+                    .iter()
+                    .filter(|(venue, (instrument, _))| {
+                        venue.exchange_id == *exchange_id &&
+                            instrument.base == order.instrument.base
+                    })
+                    .map(|(_, (_, position))| position)
+                    .sum::<Decimal>();
+
+                let position = match order.side {
+                    Side::Buy => position + order.quantity,
+                    Side::Sell => position - order.quantity,
+                };
+                if position.abs() > *max {
+                    let base = &order.instrument.base;
+                    return rejected(format!("{exchange_id} {base} position {position} over {max}"));
+                }
+            }
+        }
+
+        state.sent.extend(std::iter::repeat_n(now, orders.len()));
+        Ok(())
+    }
+
+    pub fn record_fill(&self, fill: &Fill) {
+        let venue = Venue { exchange_id: fill.exchange_id, market: fill.market.clone() };
+
+        let markets = self.markets.read().unwrap();
+        let (Some(instrument), Some(native)) = (
+            markets.instrument(&venue).cloned(),
+            markets.native_instrument(&venue).cloned(),
+        ) else {
+            log::warn!("{venue} fill {} of an unknown market", fill.order_id);
+            return;
+        };
+
+        let base = match fill.side {
+            Side::Buy => fill.quantity,
+            Side::Sell => -fill.quantity,
+        };
+        let quote = -base * fill.price - fill.fee;
+        let quote = markets.conversion(&venue).map_or(quote, |conversion| {
+            conversion.to_common(quote)
+        });
+        drop(markets);
+
+        let day = fill.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() / DAY)
+            .unwrap_or_default();
+
+        let mut state = self.state.lock().unwrap();
+        state.roll(day);
+
+        let (_, position) = state.positions
+            .entry(venue)
+            .or_insert_with(|| (native, Decimal::ZERO));
+        *position += base;
+
+        let (flow_base, flow_quote) = state.flows.entry(instrument).or_default();
+        *flow_base += base;
+        *flow_quote += quote;
+    }
+
+    /// Base bought minus sold per market, in the instrument it quotes, those flat left out.
+    pub fn positions(&self) -> Vec<(Venue, Instrument, Decimal)> {
+        self.state
+            .lock()
+            .unwrap()
+            .positions.iter()
+            .filter(|(_, (_, position))| !position.is_zero())
+            .map(|(venue, (instrument, position))| (venue.clone(), instrument.clone(), *position))
+            .collect()
+    }
+
+    /// Median price of the venue instrument, in the venue quote.
+    pub fn consensus_price(&self, venue: &Venue) -> Option<Decimal> {
+        consensus_price(&self.markets.read().unwrap(), venue)
+    }
+}
+
+fn consensus_price(markets: &Markets, venue: &Venue) -> Option<Decimal> {
+    let price = markets.engine(markets.instrument(venue)?)?.median_price()?;
+    Some(markets.to_native(venue, price))
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / DAY)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn venue(exchange_id: &'static str) -> Venue {
+        Venue { exchange_id, market: "SOL/USDT".to_string() }
+    }
+
+    // consensus at 100
+    fn risk(limits: RiskLimits) -> Risk {
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+
+        let mut markets = Markets::new(dec!(10));
+        markets.update(sol.clone(), venue("binance"), dec!(99));
+        markets.update(sol.clone(), venue("kraken"), dec!(100));
+        markets.update(sol, venue("solana"), dec!(101));
+
+        Risk::new(limits, Arc::new(RwLock::new(markets)), KillSwitch::default())
+    }
+
+    fn order(side: Side, quantity: Decimal, limit_price: Decimal) -> OrderRequest {
+        OrderRequest {
+            client_order_id: "arb-1".to_string(),
+            market: "SOL/USDT".to_string(),
+            instrument: "SOL/USDT".parse().unwrap(),
+            side,
+            quantity,
+            limit_price: Some(limit_price),
+        }
+    }
+
+    fn fill(side: Side, quantity: Decimal, price: Decimal) -> Fill {
+        Fill {
+            exchange_id: "kraken",
+            order_id: "1".to_string(),
+            client_order_id: "arb-1".to_string(),
+            market: "SOL/USDT".to_string(),
+            side,
+            quantity,
+            price,
+            fee: dec!(0),
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_notional_and_price_band() {
+        let risk = risk(RiskLimits {
+            max_notional: Some(dec!(1000)),
+            price_band_bps: Some(dec!(50)),
+            ..Default::default()
+        });
+
+        assert!(risk.check(&[("kraken", &order(Side::Buy, dec!(9), dec!(100.5)))]).is_ok());
+        assert!(risk.check(&[("kraken", &order(Side::Buy, dec!(11), dec!(100)))]).is_err());
+        assert!(risk.check(&[("kraken", &order(Side::Sell, dec!(1), dec!(99.4)))]).is_err());
+    }
+
+    #[test]
+    fn test_position() {
+        let risk = risk(RiskLimits {
+            max_position: BTreeMap::from([("SOL".to_string(), dec!(10))]),
+            ..Default::default()
+        });
+
+        risk.record_fill(&fill(Side::Buy, dec!(8), dec!(100)));
+        assert_eq!(risk.positions(), vec![(venue("kraken"), "SOL/USDT".parse().unwrap(), dec!(8))]);
+
+        assert!(risk.check(&[("kraken", &order(Side::Buy, dec!(3), dec!(100)))]).is_err());
+        assert!(risk.check(&[("kraken", &order(Side::Sell, dec!(18), dec!(100)))]).is_ok());
+        // the position is per venue
+        assert!(risk.check(&[("binance", &order(Side::Buy, dec!(3), dec!(100)))]).is_ok());
+    }
+
+    #[test]
+    fn test_daily_loss() {
+        let risk = risk(RiskLimits { max_daily_loss: Some(dec!(50)), ..Default::default() });
+
+        // bought 10 at 104 worth 100 now, then sold 5 of them at 97
+        risk.record_fill(&fill(Side::Buy, dec!(10), dec!(104)));
+        assert!(risk.check(&[("kraken", &order(Side::Sell, dec!(1), dec!(100)))]).is_ok());
+
+        risk.record_fill(&fill(Side::Sell, dec!(5), dec!(97)));
+        assert!(risk.check(&[("kraken", &order(Side::Sell, dec!(1), dec!(100)))]).is_err());
+    }
+
+    #[test]
+    fn test_order_rate() {
+        let risk = risk(RiskLimits { max_orders_per_second: Some(3), ..Default::default() });
+        let order = order(Side::Buy, dec!(1), dec!(100));
+
+        assert!(risk.check(&[("kraken", &order), ("binance", &order)]).is_ok());
+        assert!(risk.check(&[("kraken", &order), ("binance", &order)]).is_err());
+        assert!(risk.check(&[("kraken", &order)]).is_ok());
+        assert!(risk.check(&[("kraken", &order)]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch() {
+        let risk = risk(RiskLimits::default());
+        let order = order(Side::Buy, dec!(1), dec!(100));
+        assert!(risk.check(&[("kraken", &order)]).is_ok());
+
+        let path = std::env::temp_dir().join(format!("kill-switch-{}", std::process::id()));
+        let watch = tokio::spawn(watch_kill_switch(risk.kill_switch.clone(), Some(path.clone())));
+
+        std::fs::write(&path, "").unwrap();
+        tokio::time::timeout(Duration::from_secs(3), risk.kill_switch.engaged()).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        watch.await.unwrap();
+
+        assert!(risk.check(&[("kraken", &order)]).is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::{ Signer, SigningKey };
+use sha2::{ Digest, Sha256 };
+
+/// Solana account address.
+pub type Pubkey = [u8; 32];
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+pub const MEMO_PROGRAM_ID: &str = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
+pub const COMPUTE_BUDGET_PROGRAM_ID: &str = "ComputeBudget111111111111111111111111111111";
+
+pub fn pubkey(address: &str) -> Result<Pubkey, std::io::Error> {
+    bs58::decode(address)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid address {address}")
+            )
+        )
+}
+
+pub fn address(pubkey: &Pubkey) -> String {
+    bs58::encode(pubkey).into_string()
+}
+
+/// Program derived address of the seeds, with the highest bump putting it off the curve.
+pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Pubkey {
+    (0..=u8::MAX)
+        .rev()
+        .find_map(|bump| {
+            let mut hasher = Sha256::new();
+            seeds.iter().for_each(|seed| hasher.update(seed));
+            hasher.update([bump]);
+            hasher.update(program_id);
+            hasher.update(b"ProgramDerivedAddress");
+
+            let address: Pubkey = hasher.finalize().into();
+            CompressedEdwardsY(address).decompress().is_none().then_some(address)
+        })
+        .expect("a bump puts the address off the curve")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountMeta {
+    pub fn writable(pubkey: Pubkey) -> Self {
+        Self { pubkey, is_signer: false, is_writable: true }
+    }
+
+    pub fn readonly(pubkey: Pubkey) -> Self {
+        Self { pubkey, is_signer: false, is_writable: false }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+}
+
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    Instruction {
+        program_id: pubkey(COMPUTE_BUDGET_PROGRAM_ID).unwrap(),
+        accounts: vec![],
+        data: [&[2][..], &units.to_le_bytes()].concat(),
+    }
+}
+
+/// Priority fee per compute unit.
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    Instruction {
+        program_id: pubkey(COMPUTE_BUDGET_PROGRAM_ID).unwrap(),
+        accounts: vec![],
+        data: [&[3][..], &micro_lamports.to_le_bytes()].concat(),
+    }
+}
+
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    find_program_address(
+        &[owner, token_program, mint],
+        &pubkey(ASSOCIATED_TOKEN_PROGRAM_ID).unwrap()
+    )
+}
+
+/// Creates the associated token account unless it exists already.
+pub fn create_associated_token_account_idempotent(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey
+) -> Instruction {
+    Instruction {
+        program_id: pubkey(ASSOCIATED_TOKEN_PROGRAM_ID).unwrap(),
+        accounts: vec![
+            AccountMeta { pubkey: *payer, is_signer: true, is_writable: true },
+            AccountMeta::writable(associated_token_address(owner, mint, token_program)),
+            AccountMeta::readonly(*owner),
+            AccountMeta::readonly(*mint),
+            AccountMeta::readonly(pubkey(SYSTEM_PROGRAM_ID).unwrap()),
+            AccountMeta::readonly(*token_program)
+        ],
+        data: vec![1],
+    }
+}
+
+/// Legacy transaction message, accounts referenced by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    // required signatures, readonly signed and readonly unsigned accounts
+    header: [u8; 3],
+    account_keys: Vec<Pubkey>,
+    recent_blockhash: Pubkey,
+    instructions: Vec<(u8, Vec<u8>, Vec<u8>)>, // program, accounts and data
+}
+
+impl Message {
+    /// Orders the accounts as the runtime expects, the payer first then signers before
+    /// the others and writable before readonly, each group by address.
+    pub fn new(instructions: &[Instruction], payer: &Pubkey, recent_blockhash: Pubkey) -> Self {
+        // address -> signer, writable
+        let mut keys = BTreeMap::<Pubkey, (bool, bool)>::new();
+        keys.insert(*payer, (true, true));
+
+        for instruction in instructions {
+            keys.entry(instruction.program_id).or_default();
+
+            for account in &instruction.accounts {
+                let (is_signer, is_writable) = keys.entry(account.pubkey).or_default();
+                *is_signer |= account.is_signer;
+                *is_writable |= account.is_writable;
+            }
+        }
+
+        let group = |signer: bool, writable: bool| {
+            keys.iter()
+                .filter(move |(key, flags)| *key != payer && **flags == (signer, writable))
+                .map(|(key, _)| *key)
+        };
+
+        let mut account_keys = vec![*payer];
+        account_keys.extend(group(true, true));
+        let readonly_signed = group(true, false).count();
+        account_keys.extend(group(true, false));
+        let required_signatures = account_keys.len();
+        account_keys.extend(group(false, true));
+        let readonly_unsigned = group(false, false).count();
+        account_keys.extend(group(false, false));
+
+        let index = |key: &Pubkey| {
+            account_keys.iter().position(|account_key| account_key == key).unwrap() as u8
+        };
+
+        let instructions = instructions
+            .iter()
+            .map(|instruction| {
+                (
+                    index(&instruction.program_id),
+                    instruction.accounts
+                        .iter()
+                        .map(|account| index(&account.pubkey))
+                        .collect(),
+                    instruction.data.clone(),
+                )
+            })
+            .collect();
+
+        Self {
+            header: [required_signatures as u8, readonly_signed as u8, readonly_unsigned as u8],
+            account_keys,
+            recent_blockhash,
+            instructions,
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_vec();
+
+        short_vec(&mut bytes, self.account_keys.len());
+        self.account_keys.iter().for_each(|key| bytes.extend(key));
+        bytes.extend(self.recent_blockhash);
+
+        short_vec(&mut bytes, self.instructions.len());
+        for (program, accounts, data) in &self.instructions {
+            bytes.push(*program);
+            short_vec(&mut bytes, accounts.len());
+            bytes.extend(accounts);
+            short_vec(&mut bytes, data.len());
+            bytes.extend(data);
+        }
+
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    signatures: Vec<[u8; 64]>,
+    message: Message,
+}
+
+impl Transaction {
+    /// Signed by the payer, the only signer of the transactions built here.
+    pub fn sign(message: Message, payer: &SigningKey) -> Self {
+        let signature = payer.sign(&message.serialize()).to_bytes();
+        Self { signatures: vec![signature], message }
+    }
+
+    /// Base58 of the first signature, which identifies the transaction.
+    pub fn signature(&self) -> String {
+        bs58::encode(self.signatures[0]).into_string()
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        short_vec(&mut bytes, self.signatures.len());
+        self.signatures.iter().for_each(|signature| bytes.extend(signature));
+        bytes.extend(self.message.serialize());
+        bytes
+    }
+}
+
+// compact length prefix, 7 bits per byte
+fn short_vec(bytes: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+
+        if len == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_program_address() {
+        // usdc associated token account of a known wallet
+        let owner = pubkey("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM").unwrap();
+        let mint = pubkey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap();
+
+        let ata = associated_token_address(&owner, &mint, &pubkey(TOKEN_PROGRAM_ID).unwrap());
+        assert_eq!(address(&ata), "FGETo8T8wMcN2wCjav8VK6eh3dLk63evNDPxzLSJra8B");
+    }
+
+    #[test]
+    fn test_short_vec() {
+        for (len, expected) in [
+            (0, vec![0]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x01]),
+            (16384, vec![0x80, 0x80, 0x01]),
+        ] {
+            let mut bytes = vec![];
+            short_vec(&mut bytes, len);
+            assert_eq!(bytes, expected);
+        }
+    }
+
+    #[test]
+    fn test_message() {
+        let [payer, program, readonly, writable] = [[4; 32], [1; 32], [2; 32], [3; 32]];
+        let instruction = Instruction {
+            program_id: program,
+            accounts: vec![AccountMeta::readonly(readonly), AccountMeta::writable(writable)],
+            data: vec![7],
+        };
+
+        let message = Message::new(&[instruction], &payer, [9; 32]);
+
+        assert_eq!(message.header, [1, 0, 2]);
+        assert_eq!(message.account_keys, vec![payer, writable, program, readonly]);
+        assert_eq!(message.instructions, vec![(2, vec![3, 1], vec![7])]);
+    }
+}
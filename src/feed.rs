@@ -0,0 +1,272 @@
+use std::{ collections::HashSet, sync::Arc, time::Duration };
+
+use async_tungstenite::{
+    tokio::accept_async,
+    tungstenite::{ protocol::{ frame::coding::CloseCode, CloseFrame }, Message },
+};
+use futures::prelude::*;
+use serde::Deserialize;
+use serde_json::{ json, Value };
+use tokio::{
+    net::{ TcpListener, TcpStream },
+    select,
+    sync::broadcast::{ self, error::RecvError },
+    time,
+};
+
+use crate::{ markets::SharedMarkets, server, Instrument };
+
+// a client that cannot take a message for this long is dropped
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Engine updates pushed to the websocket clients subscribed to their instrument.
+#[derive(Clone)]
+pub struct Feed {
+    tx: broadcast::Sender<FeedMessage>,
+}
+
+#[derive(Debug, Clone)]
+struct FeedMessage {
+    instrument: Instrument,
+    payload: Arc<str>, // serialized once for every client
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum ClientRequest {
+    Subscribe {
+        instruments: Vec<String>,
+    },
+    Unsubscribe {
+        instruments: Vec<String>,
+    },
+}
+
+impl Feed {
+    /// Clients lagging more than `capacity` messages behind are disconnected.
+    pub fn new(capacity: usize) -> Self {
+        Self { tx: broadcast::channel(capacity).0 }
+    }
+
+    pub fn publish_best(&self, instrument: &Instrument, best: Value) {
+        self.publish(instrument, json!({"type": "best", "data": best}));
+    }
+
+    pub fn publish_opportunity(&self, instrument: &Instrument, opportunity: Value) {
+        self.publish(instrument, json!({"type": "opportunity", "data": opportunity}));
+    }
+
+    fn publish(&self, instrument: &Instrument, payload: Value) {
+        // no clients connected otherwise
+        let _ = self.tx.send(FeedMessage {
+            instrument: instrument.clone(),
+            payload: payload.to_string().into(),
+        });
+    }
+}
+
+pub async fn run_feed_server(listener: TcpListener, feed: Feed, markets: SharedMarkets) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(run_client(stream, feed.tx.subscribe(), markets.clone()));
+            }
+            Err(err) => {
+                log::warn!("feed cannot accept {err}");
+            }
+        }
+    }
+}
+
+async fn run_client(
+    stream: TcpStream,
+    mut rx: broadcast::Receiver<FeedMessage>,
+    markets: SharedMarkets
+) {
+    let Ok(mut conn) = accept_async(stream).await else {
+        return;
+    };
+
+    let mut instruments = HashSet::<Instrument>::new();
+
+    loop {
+        let payload = select! {
+            res = rx.recv() => {
+                match res {
+                    Ok(message) if instruments.contains(&message.instrument) => {
+                        message.payload.to_string()
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::debug!("feed dropping slow client {skipped} messages behind");
+                        let _ = conn.close(Some(CloseFrame {
+                            code: CloseCode::Policy,
+                            reason: "slow consumer".into(),
+                        })).await;
+                        break;
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+
+            res = conn.next() => {
+                match res {
+                    Some(Ok(Message::Text(request))) => {
+                        request_response(&request, &mut instruments, &markets)
+                    }
+                    Some(Ok(Message::Ping(value))) => {
+                        let _ = conn.send(Message::Pong(value)).await;
+                        continue;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                }
+            }
+        };
+
+        match time::timeout(SEND_TIMEOUT, conn.send(Message::Text(payload))).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => break,
+            Err(_) => {
+                log::debug!("feed dropping stuck client");
+                break;
+            }
+        }
+    }
+}
+
+// applies a client request, answering with the current best prices of new subscriptions
+fn request_response(
+    request: &str,
+    instruments: &mut HashSet<Instrument>,
+    markets: &SharedMarkets
+) -> String {
+    let parse = |names: Vec<String>| {
+        names
+            .iter()
+            .map(|name| name.parse::<Instrument>())
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let response = match serde_json::from_str::<ClientRequest>(request) {
+        Ok(ClientRequest::Subscribe { instruments: names }) =>
+            parse(names).map(|subscribed| {
+                let markets = markets.read().unwrap();
+                let best = subscribed
+                    .iter()
+                    .filter_map(|instrument| {
+                        let engine = markets.engine(instrument)?;
+                        Some(server::best_json(instrument, engine))
+                    })
+                    .collect::<Vec<_>>();
+
+                instruments.extend(subscribed);
+
+                json!({"type": "subscribed", "instruments": names_of(instruments), "best": best})
+            }),
+        Ok(ClientRequest::Unsubscribe { instruments: names }) =>
+            parse(names).map(|unsubscribed| {
+                unsubscribed.iter().for_each(|instrument| {
+                    instruments.remove(instrument);
+                });
+
+                json!({"type": "subscribed", "instruments": names_of(instruments)})
+            }),
+        Err(err) => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, err)),
+    };
+
+    response.unwrap_or_else(|err| json!({"type": "error", "message": err.to_string()})).to_string()
+}
+
+fn names_of(instruments: &HashSet<Instrument>) -> Vec<String> {
+    let mut names = instruments.iter().map(Instrument::to_string).collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use async_tungstenite::{ tokio::{ connect_async, ConnectStream }, WebSocketStream };
+    use rust_decimal_macros::dec;
+
+    use crate::{ markets::Markets, Venue };
+
+    use super::*;
+
+    async fn start(capacity: usize) -> (Feed, SharedMarkets, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let feed = Feed::new(capacity);
+        let markets: SharedMarkets = Arc::new(RwLock::new(Markets::new(dec!(10))));
+        tokio::spawn(run_feed_server(listener, feed.clone(), markets.clone()));
+
+        (feed, markets, url)
+    }
+
+    async fn next(conn: &mut WebSocketStream<ConnectStream>) -> Value {
+        let Some(Ok(Message::Text(payload))) = conn.next().await else {
+            panic!("no message");
+        };
+        serde_json::from_str(&payload).unwrap()
+    }
+
+    async fn subscribe(conn: &mut WebSocketStream<ConnectStream>, instrument: &str) -> Value {
+        conn.send(
+            Message::Text(json!({"op": "subscribe", "instruments": [instrument]}).to_string())
+        ).await.unwrap();
+        next(conn).await
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let (feed, markets, url) = start(16).await;
+
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+        let venue = Venue { exchange_id: "binance", market: "SOLUSDT".to_string() };
+        markets.write().unwrap().update(sol.clone(), venue, dec!(100));
+
+        let (mut conn, _) = connect_async(url).await.unwrap();
+
+        let response = subscribe(&mut conn, "sol/usdt").await;
+        assert_eq!(response["type"], "subscribed");
+        assert_eq!(response["instruments"], json!(["SOL/USDT"]));
+        assert_eq!(response["best"][0]["lowest"]["price"], "100");
+
+        // only subscribed instruments are forwarded
+        feed.publish_best(&"ETH/USDT".parse().unwrap(), json!({"instrument": "ETH/USDT"}));
+        feed.publish_best(&sol, json!({"instrument": "SOL/USDT"}));
+
+        assert_eq!(next(&mut conn).await, json!({"type": "best", "data": {"instrument": "SOL/USDT"}}));
+
+        conn.send(
+            Message::Text(json!({"op": "unsubscribe", "instruments": ["SOL/USDT"]}).to_string())
+        ).await.unwrap();
+        assert_eq!(next(&mut conn).await["instruments"], json!([]));
+
+        conn.send(Message::Text("{}".to_string())).await.unwrap();
+        assert_eq!(next(&mut conn).await["type"], "error");
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer() {
+        let (feed, _, url) = start(1).await;
+
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+
+        let (mut conn, _) = connect_async(url).await.unwrap();
+        subscribe(&mut conn, "SOL/USDT").await;
+
+        // the client task cannot run in between, so it falls behind the channel
+        for _ in 0..3 {
+            feed.publish_opportunity(&sol, json!({}));
+        }
+
+        let Some(Ok(Message::Close(Some(frame)))) = conn.next().await else {
+            panic!("not closed");
+        };
+        assert_eq!(frame.code, CloseCode::Policy);
+    }
+}
@@ -0,0 +1,350 @@
+use std::{ collections::{ BTreeMap, VecDeque }, fmt, time::SystemTime };
+
+use rust_decimal::{ Decimal, MathematicalOps };
+
+use crate::{ execution::Side, Instrument, Venue };
+
+// recent cycles kept for the api
+const MAX_CYCLES: usize = 100;
+
+/// Trade of a cycle, from one asset to the next on a venue market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub venue: Venue,
+    pub instrument: Instrument,
+    // buying the base with the quote, or selling it for the quote
+    pub side: Side,
+    pub price: Decimal,
+}
+
+impl Step {
+    fn from(&self) -> &str {
+        match self.side {
+            Side::Buy => &self.instrument.quote,
+            Side::Sell => &self.instrument.base,
+        }
+    }
+}
+
+/// Trades ending in the asset they started from with more of it, fees paid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    pub steps: Vec<Step>,
+    // on what the first step spends
+    pub return_bps: Decimal,
+    pub detected_at: SystemTime,
+}
+
+impl Cycle {
+    /// Assets traded through, e.g. `USDT→SOL→BTC→USDT`.
+    pub fn path(&self) -> String {
+        let mut assets = self.steps.iter().map(|step| step.from()).collect::<Vec<_>>();
+        assets.extend(self.steps.first().map(Step::from));
+        assets.join("→")
+    }
+
+    fn same_trades(&self, other: &Cycle) -> bool {
+        let trades = |cycle: &Cycle| {
+            cycle.steps
+                .iter()
+                .map(|step| (step.venue.clone(), step.side))
+                .collect::<Vec<_>>()
+        };
+        trades(self) == trades(other)
+    }
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps = self.steps
+            .iter()
+            .map(|step| format!("{:?} {} @ {}", step.side, step.venue, step.price.round_dp(8)))
+            .collect::<Vec<_>>();
+
+        write!(
+            f,
+            "{} {} bps: {}",
+            self.path(),
+            self.return_bps.round_dp(2).normalize(),
+            steps.join(", ")
+        )
+    }
+}
+
+/// Exchange rates between assets, two per venue market, whose negative log cycles are the
+/// trades returning more than they spend.
+pub struct Graph {
+    // bid and ask per venue market
+    quotes: BTreeMap<Venue, (Instrument, Decimal, Decimal)>,
+    fee_bps: Decimal,
+    // per exchange, uppercased
+    venue_fee_bps: BTreeMap<String, Decimal>,
+}
+
+// edge of the graph, from the asset spent to the asset received
+struct Edge {
+    from: usize,
+    to: usize,
+    // received per spent, fees paid
+    rate: Decimal,
+    weight: Decimal,
+    step: Step,
+}
+
+impl Graph {
+    pub fn new(fee_bps: Decimal, venue_fee_bps: BTreeMap<String, Decimal>) -> Self {
+        Self { quotes: BTreeMap::new(), fee_bps, venue_fee_bps }
+    }
+
+    /// Updates the prices the base sells at (bid) and buys at (ask) on the venue market,
+    /// returning whether they changed.
+    pub fn update(
+        &mut self,
+        venue: Venue,
+        instrument: Instrument,
+        bid: Decimal,
+        ask: Decimal
+    ) -> bool {
+        // no rate to take the logarithm of
+        if bid <= Decimal::ZERO || ask <= Decimal::ZERO {
+            return self.quotes.remove(&venue).is_some();
+        }
+
+        let quote = (instrument, bid, ask);
+        if self.quotes.get(&venue) == Some(&quote) {
+            return false;
+        }
+        self.quotes.insert(venue, quote);
+        true
+    }
+
+    /// Leaves the venue market out, returning whether it was in.
+    pub fn remove(&mut self, venue: &Venue) -> bool {
+        self.quotes.remove(venue).is_some()
+    }
+
+    /// Finds a cycle returning more than it spends with Bellman-Ford, over the negative
+    /// logarithm of the rates.
+    pub fn cycle(&self) -> Option<Cycle> {
+        let mut assets = self.quotes
+            .values()
+            .flat_map(|(instrument, _, _)| [&instrument.base, &instrument.quote])
+            .collect::<Vec<_>>();
+        assets.sort();
+        assets.dedup();
+
+        let index = |asset: &String| assets.binary_search(&asset).unwrap();
+        let edges = self.edges(index);
+
+        // from a virtual source reaching every asset at no cost
+        let mut distances = vec![Decimal::ZERO; assets.len()];
+        let mut predecessors = vec![None; assets.len()];
+        let mut relaxed = None;
+
+        for _ in 0..assets.len() {
+            relaxed = None;
+            for (i, edge) in edges.iter().enumerate() {
+                let distance = distances[edge.from] + edge.weight;
+                if distance < distances[edge.to] {
+                    distances[edge.to] = distance;
+                    predecessors[edge.to] = Some(i);
+                    relaxed = Some(edge.to);
+                }
+            }
+            relaxed?;
+        }
+
+        // still relaxing after as many rounds as assets, back into the cycle
+        let mut asset = relaxed?;
+        for _ in 0..assets.len() {
+            asset = edges[predecessors[asset]?].from;
+        }
+
+        let start = asset;
+        let mut cycle = vec![];
+        loop {
+            let edge = &edges[predecessors[asset]?];
+            cycle.push(edge);
+            asset = edge.from;
+            if asset == start {
+                break;
+            }
+        }
+        cycle.reverse();
+
+        // from its first venue, the same cycle found again starting alike
+        let first = (0..cycle.len()).min_by_key(|&i| &cycle[i].step.venue)?;
+        cycle.rotate_left(first);
+
+        // the logarithms are rounded, the rates are not
+        let rate = cycle.iter().map(|edge| edge.rate).product::<Decimal>();
+        if rate <= Decimal::ONE {
+            return None;
+        }
+
+        Some(Cycle {
+            steps: cycle.into_iter().map(|edge| edge.step.clone()).collect(),
+            return_bps: (rate - Decimal::ONE) * Decimal::from(10_000),
+            detected_at: SystemTime::now(),
+        })
+    }
+
+    fn edges(&self, index: impl Fn(&String) -> usize) -> Vec<Edge> {
+        self.quotes
+            .iter()
+            .flat_map(|(venue, (instrument, bid, ask))| {
+                let fee_bps = self.venue_fee_bps
+                    .get(&venue.exchange_id.to_uppercase())
+                    .unwrap_or(&self.fee_bps);
+                let kept = Decimal::ONE - fee_bps / Decimal::from(10_000);
+
+                let (base, quote) = (index(&instrument.base), index(&instrument.quote));
+                let step = |side, price| Step {
+                    venue: venue.clone(),
+                    instrument: instrument.clone(),
+                    side,
+                    price,
+                };
+
+                [
+                    (quote, base, kept / ask, step(Side::Buy, *ask)),
+                    (base, quote, kept * bid, step(Side::Sell, *bid)),
+                ].map(|(from, to, rate, step)| Edge {
+                    from,
+                    to,
+                    rate,
+                    weight: -rate.ln(),
+                    step,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Keeps the cycles found, newest first, recording one again only once it changes.
+#[derive(Default)]
+pub struct Cycles {
+    cycles: VecDeque<Cycle>, // newest first
+}
+
+impl Cycles {
+    /// Records the cycle, returning whether it is new.
+    pub fn record(&mut self, cycle: Cycle) -> bool {
+        let last = self.cycles.iter().find(|last| last.same_trades(&cycle));
+        if last.is_some_and(|last| last.steps == cycle.steps) {
+            return false;
+        }
+
+        self.cycles.push_front(cycle);
+        self.cycles.truncate(MAX_CYCLES);
+        true
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cycle> {
+        self.cycles.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn binance(market: &str) -> Venue {
+        Venue { exchange_id: "binance", market: market.to_string() }
+    }
+
+    fn update(graph: &mut Graph, market: &str, instrument: &str, price: Decimal) {
+        graph.update(binance(market), instrument.parse().unwrap(), price, price);
+    }
+
+    // SOL at 100 USDT or 0.002 BTC, BTC at 50000 USDT, consistent
+    fn graph(fee_bps: Decimal) -> Graph {
+        let mut graph = Graph::new(fee_bps, BTreeMap::new());
+        update(&mut graph, "SOLUSDT", "SOL/USDT", dec!(100));
+        update(&mut graph, "SOLBTC", "SOL/BTC", dec!(0.002));
+        update(&mut graph, "BTCUSDT", "BTC/USDT", dec!(50000));
+        graph
+    }
+
+    #[test]
+    fn test_update() {
+        let mut graph = graph(dec!(0));
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+
+        assert!(!graph.update(binance("SOLUSDT"), sol.clone(), dec!(100), dec!(100)));
+        assert!(graph.update(binance("SOLUSDT"), sol.clone(), dec!(99.9), dec!(100.1)));
+
+        // without a rate, left out
+        assert!(graph.update(binance("SOLUSDT"), sol.clone(), dec!(-1), dec!(100.1)));
+        assert!(!graph.update(binance("SOLUSDT"), sol, dec!(0), dec!(0)));
+        assert_eq!(graph.cycle(), None);
+    }
+
+    #[test]
+    fn test_no_cycle() {
+        assert_eq!(graph(dec!(0)).cycle(), None);
+        assert_eq!(Graph::new(dec!(0), BTreeMap::new()).cycle(), None);
+    }
+
+    #[test]
+    fn test_cycle() {
+        // BTC up to 50500 USDT: buy SOL with USDT, sell it for BTC, sell the BTC
+        let mut graph = graph(dec!(0));
+        update(&mut graph, "BTCUSDT", "BTC/USDT", dec!(50500));
+
+        let cycle = graph.cycle().unwrap();
+        assert_eq!(cycle.return_bps, dec!(100));
+        assert_eq!(
+            cycle.to_string(),
+            "BTC→USDT→SOL→BTC 100 bps: Sell binance:BTCUSDT @ 50500, \
+             Buy binance:SOLUSDT @ 100, Sell binance:SOLBTC @ 0.002"
+        );
+
+        assert!(graph.remove(&binance("SOLBTC")));
+        assert!(!graph.remove(&binance("SOLBTC")));
+        assert_eq!(graph.cycle(), None);
+    }
+
+    #[test]
+    fn test_fees() {
+        // 30 bps a trade take more than the 50 bps the prices give
+        let mut graph = graph(dec!(0));
+        update(&mut graph, "BTCUSDT", "BTC/USDT", dec!(50250));
+        assert!(graph.cycle().is_some());
+
+        let mut graph = Graph::new(dec!(0), BTreeMap::from([("BINANCE".to_string(), dec!(30))]));
+        update(&mut graph, "SOLUSDT", "SOL/USDT", dec!(100));
+        update(&mut graph, "SOLBTC", "SOL/BTC", dec!(0.002));
+        update(&mut graph, "BTCUSDT", "BTC/USDT", dec!(50250));
+        assert_eq!(graph.cycle(), None);
+    }
+
+    #[test]
+    fn test_across_venues() {
+        // bought on binance, sold on kraken
+        let mut graph = Graph::new(dec!(10), BTreeMap::new());
+        graph.update(binance("SOLUSDT"), "SOL/USDT".parse().unwrap(), dec!(99.9), dec!(100));
+        let kraken = Venue { exchange_id: "kraken", market: "SOL/USDT".to_string() };
+        graph.update(kraken, "SOL/USDT".parse().unwrap(), dec!(101), dec!(101.1));
+
+        let cycle = graph.cycle().unwrap();
+        assert_eq!(cycle.path(), "USDT→SOL→USDT");
+        assert_eq!(cycle.return_bps.round_dp(2), dec!(79.81));
+    }
+
+    #[test]
+    fn test_cycles() {
+        let mut graph = graph(dec!(0));
+        update(&mut graph, "BTCUSDT", "BTC/USDT", dec!(50500));
+
+        let mut cycles = Cycles::default();
+        assert!(cycles.record(graph.cycle().unwrap()));
+        assert!(!cycles.record(graph.cycle().unwrap()));
+
+        update(&mut graph, "BTCUSDT", "BTC/USDT", dec!(50600));
+        assert!(cycles.record(graph.cycle().unwrap()));
+        assert_eq!(cycles.iter().count(), 2);
+    }
+}
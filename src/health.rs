@@ -0,0 +1,259 @@
+use std::{ collections::BTreeMap, sync::{ LazyLock, Mutex }, time::{ Duration, SystemTime, UNIX_EPOCH } };
+
+use serde::Serialize;
+
+/// Connection state of the venues, shared by the websocket tasks and the http server.
+pub static VENUES: LazyLock<Health> = LazyLock::new(Health::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VenueState {
+    Connecting,
+    Subscribed,
+    Streaming,
+    // streaming but without prices for longer than the venue allows
+    Stale,
+    // waiting to retry after a failed or dropped connection
+    Backoff,
+}
+
+impl VenueState {
+    // from the least to the most healthy
+    fn rank(self) -> u8 {
+        match self {
+            VenueState::Backoff => 0,
+            VenueState::Connecting => 1,
+            VenueState::Subscribed => 2,
+            VenueState::Stale => 3,
+            VenueState::Streaming => 4,
+        }
+    }
+}
+
+/// State of a venue, that of its least healthy connection.
+#[derive(Debug, Clone, Serialize)]
+pub struct VenueStatus {
+    pub exchange_id: &'static str,
+    pub state: VenueState,
+    // unix millis
+    pub since_ms: u64,
+    // on any of its connections
+    pub last_price_ms: Option<u64>,
+    pub connections: usize,
+}
+
+struct ConnectionHealth {
+    state: VenueState,
+    since: SystemTime,
+    last_price: Option<SystemTime>,
+    stale_after: Duration,
+    // subscribed to on the connection, those without any left out of the venue state
+    markets: usize,
+}
+
+/// Connection states keyed by venue and connection, the markets of a venue being sharded
+/// across several.
+#[derive(Default)]
+pub struct Health {
+    connections: Mutex<BTreeMap<(&'static str, usize), ConnectionHealth>>,
+}
+
+impl Health {
+    pub fn connecting(
+        &self,
+        exchange_id: &'static str,
+        connection: usize,
+        stale_after: Duration,
+        markets: usize
+    ) {
+        let mut connections = self.connections.lock().unwrap();
+        let health = connections.entry((exchange_id, connection)).or_insert_with(|| {
+            ConnectionHealth {
+                state: VenueState::Connecting,
+                since: SystemTime::now(),
+                last_price: None,
+                stale_after,
+                markets,
+            }
+        });
+
+        (health.stale_after, health.markets) = (stale_after, markets);
+        transition(health, VenueState::Connecting);
+    }
+
+    pub fn markets(&self, exchange_id: &'static str, connection: usize, markets: usize) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(health) = connections.get_mut(&(exchange_id, connection)) {
+            health.markets = markets;
+        }
+    }
+
+    pub fn subscribed(&self, exchange_id: &'static str, connection: usize) {
+        self.set_state(exchange_id, connection, VenueState::Subscribed);
+    }
+
+    pub fn backoff(&self, exchange_id: &'static str, connection: usize) {
+        self.set_state(exchange_id, connection, VenueState::Backoff);
+    }
+
+    pub fn price_received(&self, exchange_id: &'static str, connection: usize) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(health) = connections.get_mut(&(exchange_id, connection)) {
+            transition(health, VenueState::Streaming);
+            health.last_price = Some(SystemTime::now());
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<VenueStatus> {
+        let now = SystemTime::now();
+        let mut statuses = Vec::<VenueStatus>::new();
+
+        let connections = self.connections.lock().unwrap();
+
+        // emptied by unsubscribing, without prices to go stale on
+        let connections = connections.iter().filter(|(_, health)| health.markets > 0);
+
+        for ((exchange_id, _), health) in connections {
+            let mut state = health.state;
+            let mut since = health.since;
+
+            if let (VenueState::Streaming, Some(last_price)) = (state, health.last_price) {
+                let stale_since = last_price + health.stale_after;
+                if stale_since <= now {
+                    state = VenueState::Stale;
+                    since = stale_since;
+                }
+            }
+
+            let status = VenueStatus {
+                exchange_id,
+                state,
+                since_ms: unix_millis(since),
+                last_price_ms: health.last_price.map(unix_millis),
+                connections: 1,
+            };
+
+            // the connections of a venue are next to one another
+            match statuses.last_mut() {
+                Some(venue) if venue.exchange_id == *exchange_id => {
+                    if status.state.rank() < venue.state.rank() {
+                        (venue.state, venue.since_ms) = (status.state, status.since_ms);
+                    }
+                    venue.last_price_ms = venue.last_price_ms.max(status.last_price_ms);
+                    venue.connections += 1;
+                }
+                _ => statuses.push(status),
+            }
+        }
+
+        statuses
+    }
+
+    /// Number of venues streaming fresh prices.
+    pub fn streaming(&self) -> usize {
+        self.statuses()
+            .iter()
+            .filter(|status| status.state == VenueState::Streaming)
+            .count()
+    }
+
+    fn set_state(&self, exchange_id: &'static str, connection: usize, state: VenueState) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(health) = connections.get_mut(&(exchange_id, connection)) {
+            transition(health, state);
+        }
+    }
+}
+
+fn transition(health: &mut ConnectionHealth, state: VenueState) {
+    if health.state != state {
+        health.state = state;
+        health.since = SystemTime::now();
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        let health = Health::default();
+
+        // unknown venues are ignored until they connect
+        health.price_received("binance", 0);
+        assert!(health.statuses().is_empty());
+
+        health.connecting("binance", 0, Duration::from_secs(60), 1);
+        health.connecting("kraken", 0, Duration::from_secs(60), 1);
+        assert_eq!(health.statuses()[0].state, VenueState::Connecting);
+
+        health.subscribed("binance", 0);
+        assert_eq!(health.statuses()[0].state, VenueState::Subscribed);
+        assert_eq!(health.streaming(), 0);
+
+        health.price_received("binance", 0);
+        let statuses = health.statuses();
+        assert_eq!(statuses[0].state, VenueState::Streaming);
+        assert!(statuses[0].last_price_ms.is_some());
+        assert_eq!(statuses[1].state, VenueState::Connecting);
+        assert_eq!(health.streaming(), 1);
+
+        health.backoff("binance", 0);
+        assert_eq!(health.statuses()[0].state, VenueState::Backoff);
+        assert_eq!(health.streaming(), 0);
+    }
+
+    #[test]
+    fn test_stale() {
+        let health = Health::default();
+
+        health.connecting("uniswap", 0, Duration::ZERO, 1);
+        health.price_received("uniswap", 0);
+
+        let statuses = health.statuses();
+        assert_eq!(statuses[0].state, VenueState::Stale);
+        assert_eq!(Some(statuses[0].since_ms), statuses[0].last_price_ms);
+        assert_eq!(health.streaming(), 0);
+    }
+
+    #[test]
+    fn test_connections() {
+        let health = Health::default();
+
+        health.connecting("binance", 0, Duration::from_secs(60), 1);
+        health.connecting("binance", 1, Duration::from_secs(60), 1);
+        health.subscribed("binance", 0);
+        health.price_received("binance", 0);
+
+        // streaming on one connection, connecting on the other
+        let statuses = health.statuses();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].state, VenueState::Connecting);
+        assert_eq!(statuses[0].connections, 2);
+        assert!(statuses[0].last_price_ms.is_some());
+        assert_eq!(health.streaming(), 0);
+
+        health.price_received("binance", 1);
+        assert_eq!(health.statuses()[0].state, VenueState::Streaming);
+        assert_eq!(health.streaming(), 1);
+
+        health.backoff("binance", 1);
+        assert_eq!(health.statuses()[0].state, VenueState::Backoff);
+
+        // left out once its markets are unsubscribed from
+        health.markets("binance", 1, 0);
+        let statuses = health.statuses();
+        assert_eq!(statuses[0].state, VenueState::Streaming);
+        assert_eq!(statuses[0].connections, 1);
+
+        health.markets("binance", 0, 0);
+        assert!(health.statuses().is_empty());
+    }
+}
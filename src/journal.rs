@@ -0,0 +1,578 @@
+use std::{ collections::BTreeMap, fmt, path::Path, time::{ SystemTime, UNIX_EPOCH } };
+
+use rusqlite::{ params, types::Type, Connection, OpenFlags, Row };
+use rust_decimal::Decimal;
+use tokio::sync::mpsc;
+
+use crate::{
+    execution::{ coordinator::Leg, Fill, OrderRequest, OrderState, Side },
+    markets::Opportunity,
+};
+
+const SCHEMA: &str =
+    "
+    CREATE TABLE IF NOT EXISTS opportunities (
+        id TEXT PRIMARY KEY,
+        detected_at_ms INTEGER NOT NULL,
+        instrument TEXT NOT NULL,
+        buy_venue TEXT NOT NULL,
+        buy_price TEXT NOT NULL,
+        sell_venue TEXT NOT NULL,
+        sell_price TEXT NOT NULL,
+        spread_bps TEXT NOT NULL,
+        -- prefixing the client order ids of its trades
+        trade_id TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS orders (
+        exchange_id TEXT NOT NULL,
+        order_id TEXT,
+        client_order_id TEXT NOT NULL,
+        market TEXT NOT NULL,
+        instrument TEXT NOT NULL,
+        side TEXT NOT NULL,
+        quantity TEXT NOT NULL,
+        limit_price TEXT,
+        status TEXT NOT NULL,
+        executed_quantity TEXT NOT NULL,
+        settled_at_ms INTEGER NOT NULL,
+        UNIQUE (exchange_id, order_id)
+    );
+    CREATE TABLE IF NOT EXISTS fills (
+        exchange_id TEXT NOT NULL,
+        order_id TEXT NOT NULL,
+        client_order_id TEXT NOT NULL,
+        market TEXT NOT NULL,
+        side TEXT NOT NULL,
+        quantity TEXT NOT NULL,
+        price TEXT NOT NULL,
+        fee TEXT NOT NULL,
+        timestamp_ms INTEGER NOT NULL
+    );
+    ";
+
+enum Record {
+    Opportunity(Box<Opportunity>),
+    Order {
+        exchange_id: &'static str,
+        request: OrderRequest,
+        order_id: Option<String>,
+        state: OrderState,
+        settled_at: SystemTime,
+    },
+    Fill(Fill),
+}
+
+/// Opportunities, orders and fills persisted to SQLite, written off the async tasks.
+///
+/// The default journal records nothing.
+#[derive(Clone)]
+pub struct Journal {
+    records: mpsc::UnboundedSender<Record>,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self { records: mpsc::unbounded_channel().0 }
+    }
+}
+
+impl Journal {
+    /// Opens the database, creating its tables, and starts writing to it until every clone
+    /// of the journal is dropped, the writer then to be joined.
+    pub fn open(path: &Path) -> Result<(Self, JournalWriter), rusqlite::Error> {
+        let mut writer = Writer::open(path)?;
+
+        let (records, mut receiver) = mpsc::unbounded_channel();
+        let thread = std::thread::spawn(move || {
+            while let Some(record) = receiver.blocking_recv() {
+                if let Err(err) = writer.write(&record) {
+                    log::error!("cannot journal {err}");
+                }
+            }
+        });
+
+        Ok((Self { records }, JournalWriter(thread)))
+    }
+
+    pub fn opportunity(&self, opportunity: &Opportunity) {
+        self.send(Record::Opportunity(Box::new(opportunity.clone())));
+    }
+
+    /// Records the order as settled by the leg.
+    pub fn order(&self, request: &OrderRequest, leg: &Leg) {
+        self.send(Record::Order {
+            exchange_id: leg.exchange_id,
+            request: request.clone(),
+            order_id: leg.order_id.clone(),
+            state: leg.state,
+            settled_at: SystemTime::now(),
+        });
+    }
+
+    pub fn fill(&self, fill: &Fill) {
+        self.send(Record::Fill(fill.clone()));
+    }
+
+    fn send(&self, record: Record) {
+        // closed when not journaling
+        let _ = self.records.send(record);
+    }
+}
+
+/// Thread writing the records of an open journal.
+pub struct JournalWriter(std::thread::JoinHandle<()>);
+
+impl JournalWriter {
+    /// Waits for the records to be written once every clone of the journal is dropped.
+    pub fn join(self) {
+        if self.0.join().is_err() {
+            log::error!("journal writer panicked");
+        }
+    }
+}
+
+struct Writer {
+    connection: Connection,
+}
+
+impl Writer {
+    fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(Self { connection })
+    }
+
+    fn write(&mut self, record: &Record) -> Result<(), rusqlite::Error> {
+        match record {
+            Record::Opportunity(opportunity) => {
+                self.connection.execute(
+                    "INSERT OR IGNORE INTO opportunities
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        opportunity.id(),
+                        millis(opportunity.detected_at),
+                        opportunity.instrument.to_string(),
+                        opportunity.buy_venue.to_string(),
+                        opportunity.buy_price.to_string(),
+                        opportunity.sell_venue.to_string(),
+                        opportunity.sell_price.to_string(),
+                        opportunity.spread_bps.to_string(),
+                        opportunity.trade_id()
+                    ]
+                )?;
+            }
+            Record::Order { exchange_id, request, order_id, state, settled_at } => {
+                // never placed when the venue assigned no id
+                let status = match order_id {
+                    Some(_) => format!("{:?}", state.status).to_lowercase(),
+                    None => "rejected".to_string(),
+                };
+
+                self.connection.execute(
+                    "INSERT OR REPLACE INTO orders
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    params![
+                        exchange_id,
+                        order_id,
+                        request.client_order_id,
+                        request.market,
+                        request.instrument.to_string(),
+                        side(request.side),
+                        request.quantity.to_string(),
+                        request.limit_price.map(|price| price.to_string()),
+                        status,
+                        state.executed_quantity.to_string(),
+                        millis(*settled_at)
+                    ]
+                )?;
+            }
+            Record::Fill(fill) => {
+                self.connection.execute(
+                    "INSERT INTO fills VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        fill.exchange_id,
+                        fill.order_id,
+                        fill.client_order_id,
+                        fill.market,
+                        side(fill.side),
+                        fill.quantity.to_string(),
+                        fill.price.to_string(),
+                        fill.fee.to_string(),
+                        millis(fill.timestamp)
+                    ]
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn side(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+}
+
+fn decimal(row: &Row, index: usize) -> Result<Decimal, rusqlite::Error> {
+    row.get::<_, String>(index)?
+        .parse()
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(err)))
+}
+
+// the trade a client order id belongs to, e.g. `arb-lqu5m2o0000` of its `-b` leg
+fn trade_id(client_order_id: &str) -> &str {
+    match client_order_id.rsplit_once('-') {
+        Some((trade_id, _)) => trade_id,
+        None => client_order_id,
+    }
+}
+
+/// Profit and loss in the quote, fees deducted from the realized part.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pnl {
+    pub trades: usize,
+    pub realized: Decimal,
+    // open positions marked at the last price filled of their instrument
+    pub unrealized: Decimal,
+    pub fees: Decimal,
+}
+
+impl std::ops::AddAssign for Pnl {
+    fn add_assign(&mut self, other: Self) {
+        self.trades += other.trades;
+        self.realized += other.realized;
+        self.unrealized += other.unrealized;
+        self.fees += other.fees;
+    }
+}
+
+// fills of the orders sharing a trade id
+#[derive(Default)]
+struct Trade {
+    strategy: String,
+    venue_pair: String,
+    // of the first fill, UTC
+    day: String,
+    instrument: String,
+    bought: Decimal,
+    bought_cost: Decimal,
+    sold: Decimal,
+    sold_proceeds: Decimal,
+    fees: Decimal,
+}
+
+impl Trade {
+    fn pnl(&self, mark: Decimal) -> Pnl {
+        let average = |notional: Decimal, quantity: Decimal| {
+            if quantity.is_zero() { Decimal::ZERO } else { notional / quantity }
+        };
+        let (buy_price, sell_price) = (
+            average(self.bought_cost, self.bought),
+            average(self.sold_proceeds, self.sold),
+        );
+
+        let matched = self.bought.min(self.sold);
+        let position = self.bought - self.sold;
+        let unrealized = if position.is_sign_positive() {
+            position * (mark - buy_price)
+        } else {
+            -position * (sell_price - mark)
+        };
+
+        Pnl {
+            trades: 1,
+            realized: matched * (sell_price - buy_price) - self.fees,
+            unrealized,
+            fees: self.fees,
+        }
+    }
+}
+
+/// PnL of the journaled trades per strategy, venue pair and day.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub by_strategy: BTreeMap<String, Pnl>,
+    pub by_venue_pair: BTreeMap<String, Pnl>,
+    pub by_day: BTreeMap<String, Pnl>,
+}
+
+impl Report {
+    pub fn read(path: &Path) -> Result<Self, rusqlite::Error> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        let mut statement = connection.prepare(
+            "SELECT trade_id, buy_venue, sell_venue FROM opportunities"
+        )?;
+        let venue_pairs = statement
+            .query_map([], |row| {
+                let (buy_venue, sell_venue) = (row.get::<_, String>(1)?, row.get::<_, String>(2)?);
+                Ok((row.get::<_, String>(0)?, format!("{buy_venue} -> {sell_venue}")))
+            })?
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        // fills of orders never settled keep to their venue market
+        let mut statement = connection.prepare(
+            "SELECT f.client_order_id, f.exchange_id,
+                COALESCE(o.instrument, f.exchange_id || ':' || f.market),
+                f.side, f.quantity, f.price, f.fee, date(f.timestamp_ms / 1000, 'unixepoch')
+             FROM fills f
+             LEFT JOIN orders o ON o.exchange_id = f.exchange_id AND o.order_id = f.order_id
+             ORDER BY f.timestamp_ms, f.rowid"
+        )?;
+        let mut rows = statement.query([])?;
+
+        let mut trades = BTreeMap::<String, Trade>::new();
+        let mut marks = BTreeMap::<String, Decimal>::new();
+
+        while let Some(row) = rows.next()? {
+            let client_order_id = row.get::<_, String>(0)?;
+            let exchange_id = row.get::<_, String>(1)?;
+            let instrument = row.get::<_, String>(2)?;
+            let (quantity, price, fee) = (decimal(row, 4)?, decimal(row, 5)?, decimal(row, 6)?);
+
+            let trade_id = trade_id(&client_order_id);
+            let trade = trades.entry(trade_id.to_string()).or_insert_with(|| Trade {
+                strategy: trade_id.split('-').next().unwrap_or_default().to_string(),
+                venue_pair: venue_pairs.get(trade_id).cloned().unwrap_or(exchange_id),
+                day: row.get(7).unwrap_or_default(),
+                instrument: instrument.clone(),
+                ..Default::default()
+            });
+
+            if row.get::<_, String>(3)? == side(Side::Buy) {
+                trade.bought += quantity;
+                trade.bought_cost += quantity * price;
+            } else {
+                trade.sold += quantity;
+                trade.sold_proceeds += quantity * price;
+            }
+            trade.fees += fee;
+
+            marks.insert(instrument, price);
+        }
+
+        let mut report = Report::default();
+        for trade in trades.values() {
+            let pnl = trade.pnl(marks.get(&trade.instrument).copied().unwrap_or_default());
+
+            *report.by_strategy.entry(trade.strategy.clone()).or_default() += pnl;
+            *report.by_venue_pair.entry(trade.venue_pair.clone()).or_default() += pnl;
+            *report.by_day.entry(trade.day.clone()).or_default() += pnl;
+        }
+
+        Ok(report)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections = [
+            ("strategy", &self.by_strategy),
+            ("venue pair", &self.by_venue_pair),
+            ("day", &self.by_day),
+        ];
+
+        for (index, (name, rows)) in sections.into_iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(
+                f,
+                "{name:<48}{:>8}{:>16}{:>16}{:>16}",
+                "trades",
+                "realized",
+                "unrealized",
+                "fees"
+            )?;
+
+            for (key, pnl) in rows {
+                writeln!(
+                    f,
+                    "{key:<48}{:>8}{:>16}{:>16}{:>16}",
+                    pnl.trades,
+                    pnl.realized.round_dp(4),
+                    pnl.unrealized.round_dp(4),
+                    pnl.fees.round_dp(4)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rust_decimal_macros::dec;
+
+    use crate::{ execution::OrderStatus, markets::Markets, Instrument, Venue };
+
+    use super::*;
+
+    fn path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    // SOL/USDT bought on binance at 100 and sold on kraken at 101
+    fn opportunity() -> Opportunity {
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+        let venue = |exchange_id| Venue { exchange_id, market: "SOL/USDT".to_string() };
+
+        let mut markets = Markets::new(dec!(10));
+        markets.update(sol.clone(), venue("binance"), dec!(100));
+        markets.update(sol, venue("kraken"), dec!(101));
+        let mut opportunity = markets.opportunities().next().cloned().unwrap();
+
+        // 2024-01-01T00:00:00Z
+        opportunity.detected_at = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        opportunity
+    }
+
+    fn order(opportunity: &Opportunity, exchange_id: &'static str, side: Side) -> Record {
+        let leg = if side == Side::Buy { "b" } else { "s" };
+        Record::Order {
+            exchange_id,
+            request: OrderRequest {
+                client_order_id: format!("{}-{leg}", opportunity.trade_id()),
+                market: "SOL/USDT".to_string(),
+                instrument: opportunity.instrument.clone(),
+                side,
+                quantity: dec!(2),
+                limit_price: Some(dec!(100)),
+            },
+            order_id: Some(format!("{exchange_id}-1")),
+            state: OrderState { status: OrderStatus::Filled, executed_quantity: dec!(2) },
+            settled_at: opportunity.detected_at,
+        }
+    }
+
+    fn fill(exchange_id: &'static str, client_order_id: &str, side: Side, price: Decimal) -> Fill {
+        Fill {
+            exchange_id,
+            order_id: format!("{exchange_id}-1"),
+            client_order_id: client_order_id.to_string(),
+            market: "SOL/USDT".to_string(),
+            side,
+            quantity: dec!(2),
+            price,
+            fee: dec!(0.1),
+            timestamp: opportunity().detected_at + Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_trade_id() {
+        assert_eq!(trade_id("arb-1-b"), "arb-1");
+        assert_eq!(trade_id("arb-1-c"), "arb-1");
+        assert_eq!(trade_id("arb-lqu5m2o0000-s"), "arb-lqu5m2o0000");
+        assert_eq!(trade_id("flatten-lqu5m2o0-0"), "flatten-lqu5m2o0");
+        assert_eq!(trade_id("manual"), "manual");
+    }
+
+    #[test]
+    fn test_report() {
+        let path = path("journal-report");
+        let mut writer = Writer::open(&path).unwrap();
+
+        let opportunity = opportunity();
+        let id = opportunity.trade_id();
+        let records = [
+            Record::Opportunity(Box::new(opportunity.clone())),
+            order(&opportunity, "binance", Side::Buy),
+            order(&opportunity, "kraken", Side::Sell),
+            Record::Fill(fill("binance", &format!("{id}-b"), Side::Buy, dec!(100))),
+            Record::Fill(fill("kraken", &format!("{id}-s"), Side::Sell, dec!(101))),
+            // bought back on the next day without an order journaled
+            Record::Fill(Fill {
+                quantity: dec!(1),
+                timestamp: opportunity.detected_at + Duration::from_secs(86_400),
+                ..fill("kraken", "flatten-lqu5m2o0-0", Side::Buy, dec!(102))
+            }),
+        ];
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        // opportunities are journaled once
+        writer.write(&records[0]).unwrap();
+
+        let report = Report::read(&path).unwrap();
+        let arb = Pnl { trades: 1, realized: dec!(1.8), unrealized: dec!(0), fees: dec!(0.2) };
+        let flatten = Pnl {
+            trades: 1,
+            realized: dec!(-0.1),
+            unrealized: dec!(0),
+            fees: dec!(0.1),
+        };
+        assert_eq!(report.by_strategy, BTreeMap::from([
+            ("arb".to_string(), arb),
+            ("flatten".to_string(), flatten),
+        ]));
+        assert_eq!(report.by_venue_pair, BTreeMap::from([
+            ("binance:SOL/USDT -> kraken:SOL/USDT".to_string(), arb),
+            ("kraken".to_string(), flatten),
+        ]));
+        assert_eq!(report.by_day, BTreeMap::from([
+            ("2024-01-01".to_string(), arb),
+            ("2024-01-02".to_string(), flatten),
+        ]));
+
+        let text = report.to_string();
+        assert!(text.contains("binance:SOL/USDT -> kraken:SOL/USDT"), "{text}");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_unrealized() {
+        let path = path("journal-unrealized");
+        let mut writer = Writer::open(&path).unwrap();
+
+        // a leg alone, then the instrument trading higher
+        writer.write(&Record::Fill(fill("binance", "arb-1-b", Side::Buy, dec!(100)))).unwrap();
+        let fill = Fill {
+            order_id: "2".to_string(),
+            quantity: dec!(1),
+            ..fill("binance", "arb-2-b", Side::Buy, dec!(103))
+        };
+        writer.write(&Record::Fill(fill)).unwrap();
+
+        let report = Report::read(&path).unwrap();
+        assert_eq!(report.by_strategy["arb"], Pnl {
+            trades: 2,
+            realized: dec!(-0.2),
+            unrealized: dec!(6),
+            fees: dec!(0.2),
+        });
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_journal() {
+        let path = path("journal-open");
+        let (journal, writer) = Journal::open(&path).unwrap();
+        journal.fill(&fill("binance", "arb-1-b", Side::Buy, dec!(100)));
+
+        // written off the task, by the time the writer is joined
+        drop(journal);
+        writer.join();
+        let report = Report::read(&path).unwrap();
+        assert_eq!(report.by_strategy["arb"].trades, 1);
+
+        Journal::default().fill(&fill("binance", "arb-1-b", Side::Buy, dec!(100)));
+        std::fs::remove_file(path).unwrap();
+    }
+}
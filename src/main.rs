@@ -1,108 +1,886 @@
-use env_logger::Env;
-use futures::future::select_all;
+use std::{
+    collections::{ BTreeMap, HashMap },
+    fmt,
+    fs::File,
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+    sync::{ Arc, RwLock },
+    time::{ Duration, Instant, SystemTime },
+};
+
+use env_logger::{ Env, Target };
+use futures::future::join_all;
 use rust_decimal::Decimal;
-use tokio::{ join, sync::watch::{ error::RecvError, Receiver } };
-use websocket::run_websocket;
+use rust_decimal_macros::dec;
+use tokio::{ sync::mpsc::Receiver, task::JoinHandle };
+use websocket::{ run_websocket, Control, ExchangeWebSocketConfig };
 
+mod clmm;
+mod config;
+mod conversion;
 mod exchange;
 mod engine;
+mod execution;
+mod feed;
+mod graph;
+mod health;
+mod journal;
+mod markets;
+mod metrics;
+mod output;
+mod server;
+mod tui;
 mod websocket;
 
-use exchange::{ binance::Binance, kraken::Kraken, helius::Helius };
+use config::ConfigError;
+use conversion::CrossRates;
+use execution::{
+    binance::{ BinanceConfig, BinanceVenue },
+    coordinator::{ Coordinator, CoordinatorConfig },
+    inventory::Inventory,
+    kraken::{ KrakenConfig, KrakenVenue },
+    paper::{ PaperConfig, PaperVenue },
+    raydium::{ RaydiumConfig, RaydiumVenue },
+    risk::{ watch_kill_switch, KillSwitch, Risk, RiskLimits },
+    ExecutionVenue,
+    TradingVenue,
+};
+use feed::Feed;
+use graph::Graph;
+use journal::{ Journal, Report };
+use markets::{ Markets, Opportunity, SharedMarkets };
+use output::{ OutputFormat, PriceLine };
+use server::ServerState;
+use exchange::{
+    binance::Binance,
+    kraken::{ Kraken, KrakenRates },
+    solana::{ Solana, Commitment },
+    uniswap::Uniswap,
+};
 
-pub type Sender<T> = tokio::sync::watch::Sender<T>;
+pub type Sender<T> = tokio::sync::mpsc::Sender<T>;
 
 #[derive(Default, Debug, Clone)]
 struct MarketPrice {
     exchange_id: &'static str,
     market: String,
+    // market subscribed to when it names another, e.g. the pair a pool was discovered for
+    subscription: Option<String>,
+    instrument: Instrument,
     price: Decimal,
+    slot: Option<u64>,
+    commitment: Option<Commitment>,
+    block_number: Option<u64>,
+    quote: Option<SizedQuote>,
+    // top of the book, on venues that have one
+    bid: Option<Decimal>,
+    ask: Option<Decimal>,
+    // when the websocket task received it
+    received_at: Option<Instant>,
+    // when the venue says the price changed, on venues that say
+    event_time: Option<SystemTime>,
+}
+
+/// Effective prices to sell (bid) and buy (ask) `notional` worth of the base in the quote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SizedQuote {
+    notional: Decimal,
+    bid: Decimal,
+    ask: Decimal,
+}
+
+impl MarketPrice {
+    fn venue(&self) -> Venue {
+        Venue { exchange_id: self.exchange_id, market: self.market.clone() }
+    }
+
+    // since the venue changed the price, or since it was received when the venue does not say
+    fn age(&self) -> Option<Duration> {
+        match (self.event_time, self.received_at) {
+            (Some(event_time), _) => {
+                Some(SystemTime::now().duration_since(event_time).unwrap_or_default())
+            }
+            (None, Some(received_at)) => Some(received_at.elapsed()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Exchange market or on-chain pool a price is quoted on.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Venue {
+    exchange_id: &'static str,
+    market: String,
+}
+
+impl fmt::Display for Venue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.exchange_id, self.market)
+    }
+}
+
+/// Asset pair a price is for, e.g. `SOL/USDT`, whatever each venue names its market.
+#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Instrument {
+    base: String,
+    quote: String,
+}
+
+impl fmt::Display for Instrument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
+
+impl FromStr for Instrument {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((base, quote)) if !base.is_empty() && !quote.is_empty() =>
+                Ok(Instrument { base: base.to_uppercase(), quote: quote.to_uppercase() }),
+            _ =>
+                Err(
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("invalid instrument {s}")
+                    )
+                ),
+        }
+    }
+}
+
+type WebSocketTask = (JoinHandle<()>, Control);
+
+// spawns the venue task sending its prices to `tx` when it has markets configured in
+// `markets_env`, checking its endpoints and session upfront, its markets changed later
+// through the returned control
+fn spawn_websocket<T>(
+    tx: &Sender<Vec<MarketPrice>>,
+    markets_env: &'static str,
+    default_markets: &[&str]
+)
+    -> Result<Option<WebSocketTask>, ConfigError>
+    where T: ExchangeWebSocketConfig + 'static, T::Session: Send + Sync
+{
+    let markets = config
+        ::env_list(markets_env)?
+        .unwrap_or_else(|| default_markets.iter().map(|market| market.to_string()).collect());
+
+    if markets.is_empty() {
+        return Ok(None);
+    }
+
+    T::urls()?;
+    T::session()?;
+
+    let (tx, (control, control_rx)) = (tx.clone(), tokio::sync::mpsc::unbounded_channel());
+    let future = tokio::spawn(async move {
+        let markets = markets.iter().map(String::as_str).collect::<Vec<_>>();
+        run_websocket::<T>(tx, &markets, control_rx).await;
+    });
+
+    Ok(Some((future, Control::new::<T>(control))))
+}
+
+// updates a feed client may lag behind before being dropped
+const FEED_CAPACITY: usize = 256;
+// cross rates read by default when converting to a common quote
+const QUOTE_RATE_MARKETS: [&str; 2] = ["USDT/USD", "USDC/USD"];
+// frames of prices the venues may send ahead of the engine before waiting for it
+const PRICES_CAPACITY: usize = 1024;
+
+struct Settings {
+    http_addr: SocketAddr,
+    feed_addr: SocketAddr,
+    // venues that must be streaming fresh prices to be ready
+    min_ready_venues: usize,
+    // spreads worth recording as opportunities
+    min_spread_bps: Decimal,
+    output: OutputFormat,
+    // csv output file
+    output_path: PathBuf,
+    // logs while the dashboard owns the terminal
+    log_path: PathBuf,
+    // base traded on every opportunity, no trading when not set
+    trade_quantity: Option<Decimal>,
+    // exchanges sent real orders, the others being simulated
+    trade_live: Vec<String>,
+    // engages the kill switch once it exists
+    kill_switch_path: Option<PathBuf>,
+    // closes the positions traded once the kill switch is engaged
+    kill_switch_flatten: bool,
+    // between balances read from the trading venues
+    inventory_reconcile: Duration,
+    // SQLite database of the opportunities, orders and fills
+    journal_path: PathBuf,
+    // every price expressed in, prices in each venue quote when not set
+    quote_currency: Option<String>,
+    // kraken markets the rates to the above are read off
+    quote_rate_markets: Vec<String>,
+    // prices older are left out of the comparisons
+    max_quote_age: Option<Duration>,
+    // taken by each trade of a cycle across markets
+    cycle_fee_bps: Decimal,
+    // per exchange, instead of the above
+    cycle_venue_fee_bps: BTreeMap<String, Decimal>,
+    // between searches for cycles, when prices changed
+    cycle_interval: Duration,
+}
+
+impl Settings {
+    fn from_env() -> Result<Self, ConfigError> {
+        let quote_currency = config
+            ::env_parse::<String>("QUOTE_CURRENCY")?
+            .map(|quote| quote.to_uppercase());
+        let quote_rate_markets = config
+            ::env_list("QUOTE_RATE_MARKETS")?
+            .unwrap_or_else(|| QUOTE_RATE_MARKETS.iter().map(|pair| pair.to_string()).collect());
+
+        // the prices in other quotes would be dropped without rates to convert them with
+        if quote_currency.is_some() && quote_rate_markets.is_empty() {
+            return Err(
+                ConfigError::Invalid("QUOTE_RATE_MARKETS", "no rates to QUOTE_CURRENCY".to_string())
+            );
+        }
+
+        Ok(Settings {
+            http_addr: config::env_parse("HTTP_ADDR")?.unwrap_or(([127, 0, 0, 1], 9090).into()),
+            feed_addr: config::env_parse("FEED_ADDR")?.unwrap_or(([127, 0, 0, 1], 9091).into()),
+            min_ready_venues: config::env_parse("READY_MIN_VENUES")?.unwrap_or(1),
+            min_spread_bps: config::env_parse("OPPORTUNITY_MIN_BPS")?.unwrap_or(dec!(10)),
+            output: config::env_parse("OUTPUT")?.unwrap_or(OutputFormat::Table),
+            output_path: config::env_parse("OUTPUT_PATH")?.unwrap_or("prices.csv".into()),
+            log_path: config::env_parse("LOG_PATH")?.unwrap_or("arbitrage.log".into()),
+            trade_quantity: config::env_parse("TRADE_QUANTITY")?,
+            trade_live: config::env_list("TRADE_LIVE")?.unwrap_or_default(),
+            kill_switch_path: config::env_parse("KILL_SWITCH_PATH")?,
+            kill_switch_flatten: config::env_parse("KILL_SWITCH_FLATTEN")?.unwrap_or(false),
+            inventory_reconcile: Duration::from_secs(
+                config::env_parse("INVENTORY_RECONCILE_SECS")?.unwrap_or(60)
+            ),
+            journal_path: config::env_parse("JOURNAL_PATH")?.unwrap_or("journal.sqlite".into()),
+            quote_currency,
+            quote_rate_markets,
+            max_quote_age: config::env_parse("QUOTE_MAX_AGE_MS")?.map(Duration::from_millis),
+            cycle_fee_bps: fee_bps(
+                "CYCLE_FEE_BPS",
+                config::env_parse("CYCLE_FEE_BPS")?.unwrap_or(dec!(10))
+            )?,
+            // e.g. binance=7.5,kraken=26
+            cycle_venue_fee_bps: config
+                ::env_assets("CYCLE_VENUE_FEE_BPS")?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(exchange, fee)| Ok((exchange, fee_bps("CYCLE_VENUE_FEE_BPS", fee)?)))
+                .collect::<Result<_, ConfigError>>()?,
+            cycle_interval: Duration::from_millis(
+                config::env_parse("CYCLE_INTERVAL_MS")?.unwrap_or(100)
+            ),
+        })
+    }
+}
+
+// a fee taking every unit traded leaves no rate to trade at
+fn fee_bps(name: &'static str, fee_bps: Decimal) -> Result<Decimal, ConfigError> {
+    if fee_bps >= dec!(10_000) {
+        return Err(ConfigError::Invalid(name, format!("fee {fee_bps} bps not under 10000")));
+    }
+    Ok(fee_bps)
 }
 
 #[tokio::main]
 async fn main() {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let settings = Settings::from_env();
 
-    let (tx, mut rx_binance) = tokio::sync::watch::channel(MarketPrice::default());
-    let future_binance = tokio::spawn(async move {
-        run_websocket::<Binance>(tx, &["solusdt"]).await
-    });
+    let mut logger = env_logger::Builder::from_env(Env::default().default_filter_or("info"));
+    if let Ok(Settings { output: OutputFormat::Tui, log_path, .. }) = &settings {
+        match File::options().create(true).append(true).open(log_path) {
+            Ok(file) => {
+                logger.target(Target::Pipe(Box::new(file)));
+            }
+            Err(err) => {
+                eprintln!("{}", ConfigError::Invalid("LOG_PATH", err.to_string()));
+                std::process::exit(1);
+            }
+        }
+    }
+    logger.init();
 
-    let (tx, mut rx_kraken) = tokio::sync::watch::channel(MarketPrice::default());
-    let future_kraken = tokio::spawn(async move {
-        run_websocket::<Kraken>(tx, &["SOL/USDT"]).await;
-    });
+    let settings = match settings {
+        Ok(settings) => settings,
+        Err(err) => {
+            log::error!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    // `arbitrage report` prints the PnL of the journal instead of running
+    if std::env::args().nth(1).as_deref() == Some("report") {
+        match Report::read(&settings.journal_path) {
+            Ok(report) => print!("{report}"),
+            Err(err) => {
+                log::error!("{}", ConfigError::Invalid("JOURNAL_PATH", err.to_string()));
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // the prices of every venue, the engine stopping once all of them are done
+    let (tx, mut prices_rx) = tokio::sync::mpsc::channel(PRICES_CAPACITY);
+    let venues = [
+        (Binance::EXCHANGE_ID, spawn_websocket::<Binance>(&tx, "BINANCE_MARKETS", &["solusdt"])),
+        (Kraken::EXCHANGE_ID, spawn_websocket::<Kraken>(&tx, "KRAKEN_MARKETS", &["SOL/USDT"])),
+        // pool addresses and/or pairs to discover pools for, e.g. SOL/USDC@processed
+        (
+            Solana::EXCHANGE_ID,
+            spawn_websocket::<Solana>(
+                &tx,
+                "SOLANA_MARKETS",
+                &["3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF"]
+            ),
+        ),
+        // pools as <address>@<base>/<quote>, disabled unless configured
+        (Uniswap::EXCHANGE_ID, spawn_websocket::<Uniswap>(&tx, "UNISWAP_MARKETS", &[])),
+    ];
+    drop(tx);
+
+    let mut futures = vec![];
+    // markets subscribed to and unsubscribed from over http
+    let mut subscriptions = BTreeMap::new();
+
+    for (exchange_id, venue) in venues {
+        match venue {
+            Ok(Some((future, control))) => {
+                futures.push(future);
+                subscriptions.insert(exchange_id, control);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                // fail at startup rather than inside the spawned tasks
+                log::error!("{err}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if futures.is_empty() {
+        log::error!("no venues configured");
+        std::process::exit(1);
+    }
+
+    // cross rates between quote currencies, read off kraken
+    let mut rates = None;
+    let mut rates_rx: Option<Receiver<Vec<MarketPrice>>> = None;
+    if let Some(quote) = settings.quote_currency.clone() {
+        let (tx, rx) = tokio::sync::mpsc::channel(PRICES_CAPACITY);
+        let pairs = settings.quote_rate_markets.iter().map(String::as_str).collect::<Vec<_>>();
+        match spawn_websocket::<KrakenRates>(&tx, "QUOTE_RATE_MARKETS", &pairs) {
+            Ok(Some((future, control))) => {
+                futures.push(future);
+                rates_rx = Some(rx);
+                subscriptions.insert(KrakenRates::EXCHANGE_ID, control);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                log::error!("{err}");
+                std::process::exit(1);
+            }
+        }
+        rates = Some(CrossRates::new(quote));
+    }
+
+    let mut sink = match output::open_sink(settings.output, &settings.output_path) {
+        Ok(sink) => sink,
+        Err(err) => {
+            log::error!("{}", ConfigError::Invalid("OUTPUT_PATH", err.to_string()));
+            std::process::exit(1);
+        }
+    };
+
+    let (journal, journal_writer) = match Journal::open(&settings.journal_path) {
+        Ok(journal) => journal,
+        Err(err) => {
+            log::error!("{}", ConfigError::Invalid("JOURNAL_PATH", err.to_string()));
+            std::process::exit(1);
+        }
+    };
+
+    let graph = Graph::new(settings.cycle_fee_bps, settings.cycle_venue_fee_bps);
+    let markets: SharedMarkets = Arc::new(
+        RwLock::new(Markets::new(settings.min_spread_bps).with_graph(graph))
+    );
+
+    let mut trading_venues = HashMap::new();
+    // opportunities to the trading task, when trading
+    let mut trades = None;
+
+    let kill_switch = KillSwitch::default();
+    tokio::spawn(watch_kill_switch(kill_switch.clone(), settings.kill_switch_path.clone()));
+
+    if let Some(quantity) = settings.trade_quantity {
+        let config = match PaperConfig::from_env() {
+            Ok(config) => config,
+            Err(err) => {
+                log::error!("{err}");
+                std::process::exit(1);
+            }
+        };
+
+        let limits = match RiskLimits::from_env() {
+            Ok(limits) => limits,
+            Err(err) => {
+                log::error!("{err}");
+                std::process::exit(1);
+            }
+        };
+        let risk = Risk::new(limits, markets.clone(), kill_switch.clone());
+        let inventory = Inventory::new(markets.clone());
+        // stops the fill tasks once the coordinator is done
+        let (trading_done, _) = tokio::sync::watch::channel(false);
+
+        for exchange_id in [
+            Binance::EXCHANGE_ID,
+            Kraken::EXCHANGE_ID,
+            Solana::EXCHANGE_ID,
+            Uniswap::EXCHANGE_ID,
+        ] {
+            let live = settings.trade_live.iter().any(|live| live == exchange_id);
+
+            let venue = match exchange_id {
+                Binance::EXCHANGE_ID if live => {
+                    BinanceConfig::from_env().map(|config| {
+                        TradingVenue::Binance(BinanceVenue::new(config))
+                    })
+                }
+                Kraken::EXCHANGE_ID if live => {
+                    KrakenConfig::from_env().map(|config| {
+                        TradingVenue::Kraken(KrakenVenue::new(config))
+                    })
+                }
+                Solana::EXCHANGE_ID if live => {
+                    RaydiumConfig::from_env().map(|config| {
+                        TradingVenue::Raydium(RaydiumVenue::new(config))
+                    })
+                }
+                _ if live => {
+                    Err(ConfigError::Invalid("TRADE_LIVE", format!("cannot trade {exchange_id}")))
+                }
+                _ => {
+                    let venue = PaperVenue::new(exchange_id, markets.clone(), config.clone());
+                    Ok(TradingVenue::Paper(venue))
+                }
+            };
+
+            let venue = match venue {
+                Ok(venue) => venue,
+                Err(err) => {
+                    log::error!("{err}");
+                    std::process::exit(1);
+                }
+            };
+
+            let (mut fills, risk, inventory) = (venue.fills(), risk.clone(), inventory.clone());
+            let (journal, mut trading_done) = (journal.clone(), trading_done.subscribe());
+            futures.push(
+                tokio::spawn(async move {
+                    loop {
+                        // the fills of the last orders are in once trading is done
+                        let fill = tokio::select! {
+                            biased;
+                            fill = fills.recv() => fill,
+                            _ = trading_done.wait_for(|done| *done) => break,
+                        };
+                        let Ok(fill) = fill else {
+                            break;
+                        };
+
+                        risk.record_fill(&fill);
+                        inventory.record_fill(&fill);
+                        journal.fill(&fill);
+                        log::info!(
+                            "{} filled {} {:?} {} {} @ {} fee {}",
+                            fill.exchange_id,
+                            fill.order_id,
+                            fill.side,
+                            fill.quantity,
+                            fill.market,
+                            fill.price.round_dp(4),
+                            fill.fee.round_dp(4)
+                        );
+                    }
+                })
+            );
+
+            trading_venues.insert(exchange_id, venue);
+        }
+
+        let config = match CoordinatorConfig::from_env() {
+            Ok(config) => config,
+            Err(err) => {
+                log::error!("{err}");
+                std::process::exit(1);
+            }
+        };
+
+        // one trade at a time, the next opportunity waiting while one is traded
+        let (sender, mut opportunities) = tokio::sync::mpsc::channel::<Opportunity>(1);
+        trades = Some(sender);
+
+        let mut coordinator = Coordinator::new(trading_venues, config, risk, journal.clone());
+        let (kill_switch, flatten) = (kill_switch.clone(), settings.kill_switch_flatten);
+        let mut reconcile = tokio::time::interval(settings.inventory_reconcile);
+        futures.push(
+            tokio::spawn(async move {
+                loop {
+                    let opportunity = tokio::select! {
+                        _ = reconcile.tick() => {
+                            for (exchange_id, venue) in coordinator.venues_mut().iter_mut() {
+                                match venue.balances().await {
+                                    Ok(balances) => inventory.reconcile(exchange_id, balances),
+                                    Err(err) => log::warn!("{exchange_id} balances {err}"),
+                                }
+                            }
+                            continue;
+                        }
+                        opportunity = opportunities.recv() => opportunity,
+                        _ = kill_switch.engaged() => None,
+                    };
+                    let Some(opportunity) = opportunity else {
+                        break;
+                    };
+
+                    let newly_short = inventory.flag_rebalancing(&opportunity, quantity);
+                    for (exchange_id, asset) in newly_short {
+                        log::warn!("{exchange_id} short of {asset} to trade, rebalancing needed");
+                    }
+
+                    // in the steps of the configured quantity
+                    let quantity = inventory
+                        .tradable_quantity(&opportunity)
+                        .min(quantity)
+                        .trunc_with_scale(quantity.scale());
+                    if quantity.is_zero() {
+                        log::debug!("{} not traded without inventory", opportunity.instrument);
+                        continue;
+                    }
+
+                    match coordinator.execute(&opportunity, quantity).await {
+                        Ok(execution) => {
+                            log::info!(
+                                "{} {} bought {} sold {}",
+                                opportunity.instrument,
+                                execution.state(),
+                                execution.buy.state.executed_quantity,
+                                execution.sell.state.executed_quantity
+                            );
+                        }
+                        Err(err) => {
+                            log::warn!("{} not traded {err}", opportunity.instrument);
+                        }
+                    }
+                }
+
+                if flatten && kill_switch.is_engaged() {
+                    coordinator.flatten().await;
+                }
+
+                for (exchange_id, venue) in coordinator.venues_mut().iter_mut() {
+                    if let Ok(balances) = venue.balances().await {
+                        log::info!("{exchange_id} balances {balances:?}");
+                    }
+                }
+
+                trading_done.send_replace(true);
+            })
+        );
+    }
+
+    let (listener, feed_listener) = match
+        tokio::try_join!(
+            tokio::net::TcpListener::bind(settings.http_addr),
+            tokio::net::TcpListener::bind(settings.feed_addr)
+        )
+    {
+        Ok(listeners) => listeners,
+        Err(err) => {
+            log::error!("{}", ConfigError::Invalid("HTTP_ADDR or FEED_ADDR", err.to_string()));
+            std::process::exit(1);
+        }
+    };
+
+    let feed = Feed::new(FEED_CAPACITY);
+
+    // not joined, they serve until the process exits
+    tokio::spawn(
+        server::run_server(listener, ServerState {
+            min_ready_venues: settings.min_ready_venues,
+            markets: markets.clone(),
+            kill_switch: kill_switch.clone(),
+            subscriptions,
+        })
+    );
+    tokio::spawn(feed::run_feed_server(feed_listener, feed.clone(), markets.clone()));
+
+    // set by the dashboard to stop the engine, and by the engine to stop the dashboard
+    let (shutdown, mut shutdown_rx) = tokio::sync::watch::channel(false);
+
+    if settings.output == OutputFormat::Tui {
+        let (markets, shutdown) = (markets.clone(), shutdown.clone());
+        futures.push(
+            tokio::task::spawn_blocking(move || {
+                if let Err(err) = tui::run_tui(markets, shutdown) {
+                    log::error!("dashboard {err}");
+                }
+            })
+        );
+    }
 
-    let (tx, mut rx_helius) = tokio::sync::watch::channel(MarketPrice::default());
-    let future_helius = tokio::spawn(async {
-        run_websocket::<Helius>(tx, &["3nMFwZXwY1s1M5s8vYAHqd4wGs4iSxXE4LRoUMMYqEgF"]).await;
-    });
 
     let future_engine = tokio::spawn(async move {
-        let mut engine = engine::Engine::<&str>::default();
+        let mut slots = engine::Sequencer::<Venue>::default();
+        let mut blocks = engine::Sequencer::<Venue>::default();
+        // searched for cycles on the interval, once the prices changed
+        let mut graph_changed = false;
+        let mut cycle_interval = tokio::time::interval(settings.cycle_interval);
+        cycle_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // venues gone silent are dropped on the interval, when prices have a maximum age
+        let max_quote_age = settings.max_quote_age.unwrap_or(Duration::MAX);
+        let mut stale_interval = tokio::time::interval(
+            (max_quote_age / 2).clamp(Duration::from_millis(10), Duration::from_secs(60))
+        );
+        stale_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         let mut sigterm = tokio::signal::unix
             ::signal(tokio::signal::unix::SignalKind::terminate())
             .expect("cannot listen for sigterm");
 
         loop {
-            async fn future_receiver_changed(
-                rx: &mut Receiver<MarketPrice>
-            ) -> (Result<(), RecvError>, &mut Receiver<MarketPrice>) {
-                (rx.changed().await, rx)
-            }
-
-            let receivers_changed = [
-                Box::pin(future_receiver_changed(&mut rx_binance)),
-                Box::pin(future_receiver_changed(&mut rx_kraken)),
-                Box::pin(future_receiver_changed(&mut rx_helius)),
-            ];
+            let rates_received = async {
+                match rates_rx.as_mut() {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
 
             tokio::select! {
                 _ = tokio::signal::ctrl_c() => {
                     log::warn!("ctrl+c received");
+                    kill_switch.engage("ctrl+c");
                     break;
                 },
                 _ = sigterm.recv() => {
                     log::warn!("sigterm received");
+                    kill_switch.engage("sigterm");
+                    break;
+                },
+                _ = shutdown_rx.changed() => {
+                    log::warn!("shutdown requested");
                     break;
                 },     
 
-                ((res, rx), ..) = select_all(receivers_changed) => {
-                    if res.is_err() {
-                        // sender is closed
+                _ = cycle_interval.tick(), if graph_changed => {
+                    graph_changed = false;
+
+                    if let Some(cycle) = markets.write().unwrap().search_cycle() {
+                        log::info!("Cycle {cycle}");
+                    }
+                },
+
+                _ = stale_interval.tick(), if settings.max_quote_age.is_some() => {
+                    for venue in markets.write().unwrap().remove_older(max_quote_age) {
+                        log::debug!("{venue} dropped without a price for {max_quote_age:?}");
+                        graph_changed = true;
+                    }
+                },
+
+                rate_prices = rates_received => {
+                    let Some(rate_prices) = rate_prices else {
                         break;
+                    };
+
+                    // venue prices pick the rate up with their next update
+                    if let Some(rates) = rates.as_mut() {
+                        for rate in rate_prices {
+                            log::debug!("{} rate {} {}", rate.instrument, rate.price, rate.venue());
+                            rates.update(rate.instrument.clone(), rate.venue(), rate.price);
+                        }
                     }
+                },
 
-                    let market_price = rx.borrow_and_update().clone();
+                market_prices = prices_rx.recv() => {
+                    let Some(market_prices) = market_prices else {
+                        // every venue is done
+                        break;
+                    };
 
-                    log::trace!("{market_price:?}");
+                    // the prices of a frame one after the other
+                    for market_price in market_prices {
+                        log::trace!("{market_price:?}");
 
-                    engine.update(market_price.exchange_id, market_price.price);
+                        if let Some(slot) = market_price.slot {
+                            if !slots.advance(market_price.venue(), slot) {
+                                log::debug!(
+                                    "{} {} dropping stale slot {slot} {:?}",
+                                    market_price.exchange_id,
+                                    market_price.market,
+                                    market_price.commitment
+                                );
+                                continue;
+                            }
+                        }
 
-                    // print prices list
-                    log::info!("");
-                    log::info!("Prices:");
+                        if let Some(block_number) = market_price.block_number {
+                            if !blocks.advance_or_repeat(market_price.venue(), block_number) {
+                                log::debug!(
+                                    "{} {} dropping stale block {block_number}",
+                                    market_price.exchange_id,
+                                    market_price.market
+                                );
+                                continue;
+                            }
+                        }
 
-                    engine
-                        .iter()
-                        .enumerate()
-                        .for_each(|(idx, (price, exchange_ids))| {
-                            let mut price = *price;
-                            price.rescale(4);
+                        // the last price of the venue goes too, no longer to be trusted
+                        let age = market_price.age();
+                        if let (Some(max_age), Some(age)) = (settings.max_quote_age, age) {
+                            if age > max_age {
+                                log::debug!("{} dropping {age:?} old price", market_price.venue());
+                                metrics::price_stale(market_price.exchange_id);
+                                graph_changed |= markets
+                                    .write()
+                                    .unwrap()
+                                    .remove_venue(&market_price.venue());
+                                continue;
+                            }
+                        }
 
-                            log::info!(
-                                "   {} - {price} {:?}",
-                                idx + 1,
-                                exchange_ids.map(|id| *id).collect::<Vec<_>>()
+                        metrics::price_processed(&market_price);
+
+                        {
+                            let mut markets = markets.write().unwrap();
+                            markets.subscription(
+                                market_price.venue(),
+                                market_price.subscription.clone()
+                            );
+                            // still on its way when its market was unsubscribed from
+                            if markets.unsubscribed(&market_price.venue()) {
+                                continue;
+                            }
+
+                            // every market in its own quote, as traded
+                            graph_changed |= markets.update_graph(
+                                market_price.venue(),
+                                market_price.instrument.clone(),
+                                market_price.bid.unwrap_or(market_price.price),
+                                market_price.ask.unwrap_or(market_price.price)
+                            );
+                            // aged out on the interval when it goes silent
+                            let priced_at = age.and_then(|age| Instant::now().checked_sub(age));
+                            markets.priced_at(
+                                market_price.venue(),
+                                priced_at.unwrap_or_else(Instant::now)
+                            );
+                        }
+
+                        // in the common quote, once the rates to it are known
+                        let conversion = match rates.as_ref().map(|rates| {
+                            rates.conversion(&market_price.instrument)
+                        }) {
+                            Some(Ok(conversion)) => conversion,
+                            None => None,
+                            Some(Err(err)) => {
+                                log::debug!("{} not converted {err}", market_price.venue());
+                                continue;
+                            }
+                        };
+                        let (instrument, price) = match &conversion {
+                            Some(conversion) => (
+                                conversion.instrument(),
+                                conversion.to_common(market_price.price),
+                            ),
+                            None => (market_price.instrument.clone(), market_price.price),
+                        };
+
+                        let mut opportunity = None;
+
+                        {
+                            let mut markets = markets.write().unwrap();
+                            markets.convert(market_price.venue(), conversion);
+                            markets.quote(market_price.venue(), market_price.quote);
+                            markets.book(market_price.venue(), market_price.bid, market_price.ask);
+                            let changes = markets.update(
+                                instrument.clone(),
+                                market_price.venue(),
+                                price
                             );
-                        });
+
+                            let Some(engine) = markets.engine(&instrument) else {
+                                continue;
+                            };
+
+                            if changes.best {
+                                feed.publish_best(
+                                    &instrument,
+                                    server::best_json(&instrument, engine)
+                                );
+                            }
+                            if
+                                let (true, Some(new_opportunity)) = (
+                                    changes.opportunity,
+                                    markets.opportunities().next(),
+                                )
+                            {
+                                feed.publish_opportunity(
+                                    &instrument,
+                                    server::opportunity_json(new_opportunity)
+                                );
+                                journal.opportunity(new_opportunity);
+                                opportunity = Some(new_opportunity.clone());
+                            }
+
+                            if
+                                let (Some((lowest, _)), Some((highest, _))) = (
+                                    engine.lowest_price(),
+                                    engine.highest_price(),
+                                )
+                            {
+                                metrics::spread(&instrument, *lowest, *highest);
+                            }
+
+                            if let Some(sink) = sink.as_mut() {
+                                let line = PriceLine::new(
+                                    &instrument,
+                                    engine,
+                                    markets.conversions()
+                                );
+                                if let Err(err) = sink.write(&line) {
+                                    log::error!("cannot write output {err}");
+                                }
+                            }
+                        }
+
+                        // sent once the markets are released, the paper venues read them
+                        if let (Some(trades), Some(opportunity)) = (&trades, opportunity) {
+                            if trades.try_send(opportunity).is_err() {
+                                log::debug!("trading busy, opportunity skipped");
+                            }
+                        }
+                    }
                 }
             }
         }
+
+        shutdown.send_replace(true);
     });
 
-    let _ = join!(future_engine, future_binance, future_kraken, future_helius);
+    futures.push(future_engine);
+    join_all(futures).await;
+
+    // the journal clones went with the tasks, what they recorded is written before exiting
+    journal_writer.join();
 
     log::info!("gracefully exiting!");
 }
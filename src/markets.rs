@@ -0,0 +1,626 @@
+use std::{
+    collections::{ BTreeMap, BTreeSet, VecDeque },
+    sync::{ Arc, RwLock },
+    time::{ Duration, Instant, SystemTime, UNIX_EPOCH },
+};
+
+use rust_decimal::Decimal;
+
+use crate::{
+    conversion::Conversion,
+    engine::Engine,
+    graph::{ Cycle, Cycles, Graph },
+    Instrument,
+    SizedQuote,
+    Venue,
+};
+
+// recent opportunities kept for the api
+const MAX_OPPORTUNITIES: usize = 100;
+
+/// Markets written by the engine loop and read by the http server.
+pub type SharedMarkets = Arc<RwLock<Markets>>;
+
+/// Buying an instrument on its cheapest venue and selling it on its dearest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opportunity {
+    pub instrument: Instrument,
+    pub buy_venue: Venue,
+    pub buy_price: Decimal,
+    pub sell_venue: Venue,
+    pub sell_price: Decimal,
+    pub spread_bps: Decimal,
+    // of the opportunities recorded since the start, telling apart those of one millisecond
+    pub sequence: u64,
+    // buying at the sized ask and selling at the sized bid, where the venues quote trade sizes
+    pub executable_bps: Decimal,
+    pub detected_at: SystemTime,
+    // of the venue prices not in the instrument quote
+    pub buy_conversion: Option<Conversion>,
+    pub sell_conversion: Option<Conversion>,
+    // in the venue quote
+    pub buy_quote: Option<SizedQuote>,
+    pub sell_quote: Option<SizedQuote>,
+}
+
+impl Opportunity {
+    /// Names the opportunity in the journal and the logs.
+    pub fn id(&self) -> String {
+        let instrument = format!("{}{}", self.instrument.base, self.instrument.quote);
+
+        format!("arb-{}-{}-{}", instrument.to_lowercase(), self.detected_at_ms(), self.sequence)
+    }
+
+    /// Names the trades of the opportunity, prefixing their client order ids, short enough
+    /// for the 18 characters kraken takes with the leg appended.
+    pub fn trade_id(&self) -> String {
+        // the sequence wrapping around, only telling apart those of one millisecond
+        let sequence = u128::from(self.sequence % 36_u64.pow(3));
+
+        format!("arb-{}{}", base36(self.detected_at_ms(), 8), base36(sequence, 3))
+    }
+
+    fn detected_at_ms(&self) -> u128 {
+        self.detected_at
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default()
+    }
+
+    /// Instrument the venue of a leg quotes, with the price converted back to its quote.
+    pub fn native(&self, venue: &Venue, price: Decimal) -> (Instrument, Decimal) {
+        let conversion = if *venue == self.buy_venue {
+            &self.buy_conversion
+        } else {
+            &self.sell_conversion
+        };
+
+        match conversion {
+            Some(conversion) => (conversion.native.clone(), conversion.to_native(price)),
+            None => (self.instrument.clone(), price),
+        }
+    }
+}
+
+/// Digits and lowercase letters of the value, zero padded to `width`.
+pub fn base36(mut value: u128, width: usize) -> String {
+    let mut digits = vec![];
+    while value > 0 || digits.len() < width {
+        digits.push(char::from_digit((value % 36) as u32, 36).unwrap());
+        value /= 36;
+    }
+
+    digits.into_iter().rev().collect()
+}
+
+/// What a price update changed for its instrument.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Changes {
+    // lowest or highest price, or the venue quoting it
+    pub best: bool,
+    // a new opportunity was recorded
+    pub opportunity: bool,
+}
+
+/// Prices of every venue per instrument, in a common quote when converted.
+pub struct Markets {
+    engines: BTreeMap<Instrument, Engine<Venue>>,
+    conversions: BTreeMap<Venue, Conversion>,
+    quotes: BTreeMap<Venue, SizedQuote>,
+    books: BTreeMap<Venue, (Option<Decimal>, Option<Decimal>)>, // top bid and ask, as quoted
+    subscriptions: BTreeMap<Venue, String>, // market subscribed to, e.g. pair -> pool found
+    priced_at: BTreeMap<Venue, Instant>, // when the venue changed its price, as far as known
+    unsubscribed: BTreeSet<Venue>, // markets whose prices still queued are left out
+    opportunities: VecDeque<Opportunity>, // newest first
+    next_sequence: u64,
+    graph: Graph, // every market in its own quote, as traded
+    cycles: Cycles,
+    min_spread_bps: Decimal,
+}
+
+impl Markets {
+    pub fn new(min_spread_bps: Decimal) -> Self {
+        Self {
+            engines: BTreeMap::new(),
+            conversions: BTreeMap::new(),
+            quotes: BTreeMap::new(),
+            books: BTreeMap::new(),
+            subscriptions: BTreeMap::new(),
+            priced_at: BTreeMap::new(),
+            unsubscribed: BTreeSet::new(),
+            opportunities: VecDeque::new(),
+            next_sequence: 0,
+            graph: Graph::new(Decimal::ZERO, BTreeMap::new()),
+            cycles: Cycles::default(),
+            min_spread_bps,
+        }
+    }
+
+    /// Searches cycles with the fees of the graph, none taken otherwise.
+    pub fn with_graph(mut self, graph: Graph) -> Self {
+        self.graph = graph;
+        self
+    }
+
+    /// Sets how the prices of the venue were converted, before they are updated.
+    pub fn convert(&mut self, venue: Venue, conversion: Option<Conversion>) {
+        match conversion {
+            Some(conversion) => self.conversions.insert(venue, conversion),
+            None => self.conversions.remove(&venue),
+        };
+    }
+
+    /// Sets the trade size the venue quoted, before its price is updated.
+    pub fn quote(&mut self, venue: Venue, quote: Option<SizedQuote>) {
+        match quote {
+            Some(quote) => self.quotes.insert(venue, quote),
+            None => self.quotes.remove(&venue),
+        };
+    }
+
+    /// Sets the top of the venue book in its own quote, before its price is updated.
+    pub fn book(&mut self, venue: Venue, bid: Option<Decimal>, ask: Option<Decimal>) {
+        if bid.is_none() && ask.is_none() {
+            self.books.remove(&venue);
+        } else {
+            self.books.insert(venue, (bid, ask));
+        }
+    }
+
+    /// Sets the market subscribed to that the venue was found through, when it is another,
+    /// before its price is updated.
+    pub fn subscription(&mut self, venue: Venue, subscription: Option<String>) {
+        match subscription {
+            Some(subscription) => self.subscriptions.insert(venue, subscription),
+            None => self.subscriptions.remove(&venue),
+        };
+    }
+
+    /// Sets when the venue changed its price, before it is updated, for `remove_older`.
+    pub fn priced_at(&mut self, venue: Venue, priced_at: Instant) {
+        self.priced_at.insert(venue, priced_at);
+    }
+
+    /// Best bid of the venue in its own quote, on venues that have a book.
+    pub fn native_bid(&self, venue: &Venue) -> Option<Decimal> {
+        self.books.get(venue)?.0
+    }
+
+    /// Best ask of the venue in its own quote, on venues that have a book.
+    pub fn native_ask(&self, venue: &Venue) -> Option<Decimal> {
+        self.books.get(venue)?.1
+    }
+
+    /// Updates the venue price, recording the opportunity it opens if any.
+    pub fn update(&mut self, instrument: Instrument, venue: Venue, price: Decimal) -> Changes {
+        if self.unsubscribed(&venue) {
+            return Changes::default();
+        }
+
+        let engine = self.engines.entry(instrument.clone()).or_default();
+
+        let previous_best = best(engine);
+        engine.update(venue, price);
+
+        let mut changes = Changes { best: best(engine) != previous_best, opportunity: false };
+
+        let Some(mut opportunity) = opportunity(
+            &instrument,
+            engine,
+            &self.conversions,
+            &self.quotes
+        ) else {
+            return changes;
+        };
+
+        if opportunity.executable_bps < self.min_spread_bps {
+            return changes;
+        }
+
+        // the same spread is only recorded again once it changes
+        let last = self.opportunities.iter().find(|last| last.instrument == instrument);
+        if
+            last.is_some_and(
+                |last|
+                    last.buy_venue == opportunity.buy_venue &&
+                    last.buy_price == opportunity.buy_price &&
+                    last.sell_venue == opportunity.sell_venue &&
+                    last.sell_price == opportunity.sell_price
+            )
+        {
+            return changes;
+        }
+
+        opportunity.sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        self.opportunities.push_front(opportunity);
+        self.opportunities.truncate(MAX_OPPORTUNITIES);
+
+        changes.opportunity = true;
+        changes
+    }
+
+    /// Updates the bid and ask of the venue in its own quote for the cycles, returning
+    /// whether they changed.
+    pub fn update_graph(
+        &mut self,
+        venue: Venue,
+        instrument: Instrument,
+        bid: Decimal,
+        ask: Decimal
+    ) -> bool {
+        self.graph.update(venue, instrument, bid, ask)
+    }
+
+    /// Subscribes the venue market again, its prices no longer left out.
+    pub fn subscribe(&mut self, venue: &Venue) {
+        self.unsubscribed.remove(venue);
+    }
+
+    /// Drops the venue, and those found through it, leaving out their prices from then on,
+    /// those still on their way included.
+    pub fn unsubscribe(&mut self, venue: Venue) {
+        self.remove_venue(&venue);
+        self.unsubscribed.insert(venue);
+    }
+
+    /// Whether the venue, or the market it was found through, was unsubscribed from.
+    pub fn unsubscribed(&self, venue: &Venue) -> bool {
+        self.unsubscribed.contains(venue) ||
+            self.subscriptions.get(venue).is_some_and(|subscription| {
+                self.unsubscribed.contains(
+                    &(Venue { exchange_id: venue.exchange_id, market: subscription.clone() })
+                )
+            })
+    }
+
+    /// Drops the venue, and those found through it, from the prices and cycles, e.g. once
+    /// gone stale, returning whether any was there.
+    pub fn remove_venue(&mut self, venue: &Venue) -> bool {
+        let venues = self.subscriptions
+            .iter()
+            .filter(|(found, subscription)| {
+                found.exchange_id == venue.exchange_id && **subscription == venue.market
+            })
+            .map(|(found, _)| found.clone())
+            .chain(std::iter::once(venue.clone()))
+            .collect::<Vec<_>>();
+
+        let mut removed = false;
+        for venue in &venues {
+            self.engines.retain(|_, engine| {
+                removed |= engine.remove(venue).is_some();
+                engine.len() > 0
+            });
+            removed |= self.graph.remove(venue);
+            self.conversions.remove(venue);
+            self.quotes.remove(venue);
+            self.books.remove(venue);
+            self.subscriptions.remove(venue);
+            self.priced_at.remove(venue);
+        }
+        removed
+    }
+
+    /// Drops the venues whose price is older than `max_age`, e.g. gone silent, returning them.
+    pub fn remove_older(&mut self, max_age: Duration) -> Vec<Venue> {
+        let venues = self.priced_at
+            .iter()
+            .filter(|(_, priced_at)| priced_at.elapsed() > max_age)
+            .map(|(venue, _)| venue.clone())
+            .collect::<Vec<_>>();
+
+        for venue in &venues {
+            self.remove_venue(venue);
+        }
+        venues
+    }
+
+    pub fn engine(&self, instrument: &Instrument) -> Option<&Engine<Venue>> {
+        self.engines.get(instrument)
+    }
+
+    /// Instrument the venue prices are ranked under, in the common quote when converted.
+    pub fn instrument(&self, venue: &Venue) -> Option<&Instrument> {
+        self.engines
+            .iter()
+            .find(|(_, engine)| engine.price(venue).is_some())
+            .map(|(instrument, _)| instrument)
+    }
+
+    /// Instrument the venue quotes, e.g. for its fills.
+    pub fn native_instrument(&self, venue: &Venue) -> Option<&Instrument> {
+        match self.conversions.get(venue) {
+            Some(conversion) => Some(&conversion.native),
+            None => self.instrument(venue),
+        }
+    }
+
+    /// Price of the venue in its own quote.
+    pub fn native_price(&self, venue: &Venue) -> Option<Decimal> {
+        let price = *self.engine(self.instrument(venue)?)?.price(venue)?;
+        Some(self.to_native(venue, price))
+    }
+
+    /// Converts a price of the venue instrument back to the venue quote.
+    pub fn to_native(&self, venue: &Venue, price: Decimal) -> Decimal {
+        match self.conversions.get(venue) {
+            Some(conversion) => conversion.to_native(price),
+            None => price,
+        }
+    }
+
+    pub fn conversion(&self, venue: &Venue) -> Option<&Conversion> {
+        self.conversions.get(venue)
+    }
+
+    pub fn conversions(&self) -> &BTreeMap<Venue, Conversion> {
+        &self.conversions
+    }
+
+    pub fn engines(&self) -> impl Iterator<Item = (&Instrument, &Engine<Venue>)> {
+        self.engines.iter()
+    }
+
+    /// Records the cycle when it returns at least the minimum spread, returning whether it
+    /// is new.
+    pub fn record_cycle(&mut self, cycle: Cycle) -> bool {
+        cycle.return_bps >= self.min_spread_bps && self.cycles.record(cycle)
+    }
+
+    /// Searches the graph for a cycle, returning it when recorded.
+    pub fn search_cycle(&mut self) -> Option<Cycle> {
+        let cycle = self.graph.cycle()?;
+        self.record_cycle(cycle.clone()).then_some(cycle)
+    }
+
+    /// Recent cycles across markets, newest first.
+    pub fn cycles(&self) -> impl Iterator<Item = &Cycle> {
+        self.cycles.iter()
+    }
+
+    /// Recent opportunities, newest first.
+    pub fn opportunities(&self) -> impl Iterator<Item = &Opportunity> {
+        self.opportunities.iter()
+    }
+}
+
+fn best(engine: &Engine<Venue>) -> Option<(Decimal, Venue, Decimal, Venue)> {
+    let (lowest, lowest_venues) = engine.lowest_price()?;
+    let (highest, highest_venues) = engine.highest_price()?;
+
+    Some((*lowest, lowest_venues.min()?.clone(), *highest, highest_venues.min()?.clone()))
+}
+
+fn opportunity(
+    instrument: &Instrument,
+    engine: &Engine<Venue>,
+    conversions: &BTreeMap<Venue, Conversion>,
+    quotes: &BTreeMap<Venue, SizedQuote>
+) -> Option<Opportunity> {
+    let (buy_price, buy_venues) = engine.lowest_price()?;
+    let (sell_price, sell_venues) = engine.highest_price()?;
+
+    if buy_price >= sell_price || buy_price.is_zero() {
+        return None;
+    }
+
+    let (buy_venue, sell_venue) = (buy_venues.min()?, sell_venues.min()?);
+    let (buy_quote, sell_quote) = (quotes.get(buy_venue).copied(), quotes.get(sell_venue).copied());
+
+    let to_common = |venue: &Venue, price: Decimal| {
+        match conversions.get(venue) {
+            Some(conversion) => conversion.to_common(price),
+            None => price,
+        }
+    };
+    let buy_executable = buy_quote.map_or(*buy_price, |quote| to_common(buy_venue, quote.ask));
+    let sell_executable = sell_quote.map_or(*sell_price, |quote| to_common(sell_venue, quote.bid));
+
+    Some(Opportunity {
+        instrument: instrument.clone(),
+        buy_venue: buy_venue.clone(),
+        buy_price: *buy_price,
+        sell_venue: sell_venue.clone(),
+        sell_price: *sell_price,
+        spread_bps: ((sell_price - buy_price) / buy_price) * Decimal::from(10_000),
+        sequence: 0,
+        executable_bps: ((sell_executable - buy_executable) / buy_executable) *
+        Decimal::from(10_000),
+        detected_at: SystemTime::now(),
+        buy_conversion: conversions.get(buy_venue).cloned(),
+        sell_conversion: conversions.get(sell_venue).cloned(),
+        buy_quote,
+        sell_quote,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn venue(exchange_id: &'static str) -> Venue {
+        Venue { exchange_id, market: "SOL/USDT".to_string() }
+    }
+
+    #[test]
+    fn test_update() {
+        let sol: Instrument = "SOL/USDT".parse().unwrap();
+        let eth: Instrument = "ETH/USDT".parse().unwrap();
+
+        let mut markets = Markets::new(dec!(10));
+        assert!(markets.update(sol.clone(), venue("binance"), dec!(100)).best);
+        assert!(markets.update(eth.clone(), venue("kraken"), dec!(2000)).best);
+        assert!(!markets.update(eth.clone(), venue("kraken"), dec!(2000)).best);
+
+        assert_eq!(markets.engines().count(), 2);
+        assert_eq!(*markets.engine(&sol).unwrap().lowest_price().unwrap().0, dec!(100));
+        assert!(markets.engine(&"BTC/USDT".parse().unwrap()).is_none());
+        assert_eq!(markets.instrument(&venue("kraken")), Some(&eth));
+        assert_eq!(markets.instrument(&venue("solana")), None);
+        assert_eq!(markets.opportunities().count(), 0);
+    }
+
+    #[test]
+    fn test_remove_venue() {
+        let sol: Instrument = "SOL/USDT".parse().unwrap();
+        let pool = |market: &str| Venue { exchange_id: "solana", market: market.to_string() };
+
+        let mut markets = Markets::new(dec!(10));
+        markets.update(sol.clone(), venue("binance"), dec!(100));
+        markets.book(venue("binance"), Some(dec!(99.9)), Some(dec!(100.1)));
+        markets.update_graph(venue("binance"), sol.clone(), dec!(99.9), dec!(100.1));
+        // two pools discovered for the pair
+        for (market, price) in [("pool-a", dec!(101)), ("pool-b", dec!(102))] {
+            markets.subscription(pool(market), Some("SOL/USDT".to_string()));
+            markets.update(sol.clone(), pool(market), price);
+        }
+
+        assert!(markets.remove_venue(&venue("binance")));
+        assert!(!markets.remove_venue(&venue("binance")));
+        assert_eq!(markets.instrument(&venue("binance")), None);
+        assert_eq!(markets.native_bid(&venue("binance")), None);
+        assert!(!markets.update_graph(venue("binance"), sol.clone(), dec!(0), dec!(0)));
+
+        assert!(markets.remove_venue(&pool("SOL/USDT")));
+        assert!(markets.engine(&sol).is_none());
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let sol: Instrument = "SOL/USDT".parse().unwrap();
+        let pool = Venue { exchange_id: "solana", market: "pool-a".to_string() };
+        let pair = Venue { exchange_id: "solana", market: "SOL/USDT".to_string() };
+
+        let mut markets = Markets::new(dec!(10));
+        markets.subscription(pool.clone(), Some("SOL/USDT".to_string()));
+        markets.update(sol.clone(), pool.clone(), dec!(100));
+        markets.unsubscribe(pair.clone());
+
+        // a price queued before, through the pair unsubscribed from
+        markets.subscription(pool.clone(), Some("SOL/USDT".to_string()));
+        assert!(markets.unsubscribed(&pool));
+        assert_eq!(markets.update(sol.clone(), pool.clone(), dec!(101)), Changes::default());
+        assert!(markets.engine(&sol).is_none());
+
+        markets.subscribe(&pair);
+        assert!(markets.update(sol.clone(), pool, dec!(101)).best);
+    }
+
+    #[test]
+    fn test_remove_older() {
+        let sol: Instrument = "SOL/USDT".parse().unwrap();
+
+        let mut markets = Markets::new(dec!(10));
+        markets.priced_at(venue("binance"), Instant::now() - Duration::from_secs(10));
+        markets.update(sol.clone(), venue("binance"), dec!(100));
+        markets.priced_at(venue("kraken"), Instant::now());
+        markets.update(sol.clone(), venue("kraken"), dec!(101));
+
+        assert_eq!(markets.remove_older(Duration::from_secs(5)), vec![venue("binance")]);
+        assert_eq!(markets.instrument(&venue("binance")), None);
+        assert_eq!(markets.instrument(&venue("kraken")), Some(&sol));
+        assert!(markets.remove_older(Duration::from_secs(5)).is_empty());
+    }
+
+    #[test]
+    fn test_opportunities() {
+        let sol: Instrument = "SOL/USDT".parse().unwrap();
+
+        let mut markets = Markets::new(dec!(10));
+        markets.update(sol.clone(), venue("binance"), dec!(100));
+
+        // below the minimum spread
+        markets.update(sol.clone(), venue("kraken"), dec!(100.05));
+        assert_eq!(markets.opportunities().count(), 0);
+
+        assert!(markets.update(sol.clone(), venue("kraken"), dec!(100.5)).opportunity);
+        assert_eq!(
+            markets.update(sol.clone(), venue("binance"), dec!(100)),
+            Changes { best: false, opportunity: false }
+        );
+        assert!(markets.update(sol.clone(), venue("solana"), dec!(101)).opportunity);
+
+        let opportunities = markets.opportunities().collect::<Vec<_>>();
+        assert_eq!(opportunities.len(), 2);
+        assert_eq!(opportunities[0].buy_venue, venue("binance"));
+        assert_eq!(opportunities[0].sell_venue, venue("solana"));
+        assert_eq!(opportunities[0].spread_bps, dec!(100));
+        assert_eq!(opportunities[1].sell_venue, venue("kraken"));
+
+        // told apart within a millisecond
+        let (mut first, mut second) = (opportunities[1].clone(), opportunities[0].clone());
+        (first.detected_at, second.detected_at) = (UNIX_EPOCH, UNIX_EPOCH);
+        assert_eq!(first.id(), "arb-solusdt-0-0");
+        assert_eq!(second.id(), "arb-solusdt-0-1");
+        assert_eq!(first.trade_id(), "arb-00000000000");
+        assert_eq!(second.trade_id(), "arb-00000000001");
+
+        // 2024-01-01T00:00:00Z
+        first.detected_at = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        first.sequence = 36_u64.pow(3) + 35;
+        assert_eq!(first.trade_id(), "arb-lqu5m2o000z");
+    }
+
+    #[test]
+    fn test_quoted_opportunities() {
+        let sol: Instrument = "SOL/USDT".parse().unwrap();
+        let quote = |bid, ask| Some(SizedQuote { notional: dec!(1000), bid, ask });
+
+        let mut markets = Markets::new(dec!(10));
+        markets.quote(venue("solana"), quote(dec!(99.9), dec!(100.45)));
+        markets.update(sol.clone(), venue("solana"), dec!(100));
+
+        // 50 bps at the top of the book, but 5 buying the trade size
+        assert!(!markets.update(sol.clone(), venue("kraken"), dec!(100.5)).opportunity);
+        assert_eq!(markets.opportunities().count(), 0);
+
+        markets.quote(venue("solana"), quote(dec!(99.9), dec!(100.1)));
+        assert!(markets.update(sol.clone(), venue("solana"), dec!(100)).opportunity);
+
+        let opportunity = markets.opportunities().next().unwrap();
+        assert_eq!(opportunity.spread_bps, dec!(50));
+        assert_eq!(opportunity.executable_bps.round_dp(2), dec!(39.96));
+        assert_eq!(opportunity.buy_quote, quote(dec!(99.9), dec!(100.1)));
+        assert_eq!(opportunity.sell_quote, None);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let sol_usd: Instrument = "SOL/USD".parse().unwrap();
+        let binance = Venue { exchange_id: "binance", market: "SOLUSDT".to_string() };
+        let conversion = Conversion {
+            native: "SOL/USDT".parse().unwrap(),
+            rate: dec!(1.0002),
+            assets: vec!["USDT".to_string(), "USD".to_string()],
+            venues: vec![Venue { exchange_id: "kraken", market: "USDT/USD".to_string() }],
+        };
+
+        // binance quotes 100 USDT
+        let mut markets = Markets::new(dec!(10));
+        markets.convert(binance.clone(), Some(conversion.clone()));
+        markets.update(sol_usd.clone(), binance.clone(), dec!(100.02));
+        markets.update(sol_usd.clone(), venue("kraken"), dec!(101));
+
+        assert_eq!(markets.instrument(&binance), Some(&sol_usd));
+        assert_eq!(markets.native_instrument(&binance), Some(&conversion.native));
+        assert_eq!(markets.native_instrument(&venue("kraken")), Some(&sol_usd));
+        assert_eq!(markets.native_price(&binance), Some(dec!(100)));
+        assert_eq!(markets.native_price(&venue("kraken")), Some(dec!(101)));
+
+        let opportunity = markets.opportunities().next().unwrap();
+        assert_eq!(opportunity.buy_conversion, Some(conversion.clone()));
+        assert_eq!(opportunity.sell_conversion, None);
+        assert_eq!(
+            opportunity.native(&binance, dec!(101)),
+            (conversion.native.clone(), dec!(100.97980404))
+        );
+        assert_eq!(opportunity.native(&venue("kraken"), dec!(100.02)), (sol_usd, dec!(100.02)));
+
+        markets.convert(binance.clone(), None);
+        assert_eq!(markets.conversion(&binance), None);
+    }
+}
@@ -0,0 +1,285 @@
+use std::{ collections::HashMap, sync::{ LazyLock, Mutex }, time::{ Instant, SystemTime } };
+
+use prometheus::{
+    exponential_buckets,
+    register_gauge_vec,
+    register_histogram_vec,
+    register_int_counter_vec,
+    Encoder,
+    GaugeVec,
+    HistogramVec,
+    IntCounterVec,
+    TextEncoder,
+};
+use rust_decimal::{ prelude::ToPrimitive, Decimal };
+
+use crate::{ Instrument, MarketPrice };
+
+static MESSAGES_RECEIVED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "arbitrage_messages_received_total",
+        "Websocket messages received per venue",
+        &["exchange"]
+    ).unwrap()
+});
+
+static PARSE_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "arbitrage_parse_failures_total",
+        "Websocket text messages that did not parse into a price",
+        &["exchange"]
+    ).unwrap()
+});
+
+static RECONNECTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "arbitrage_reconnects_total",
+        "Websocket connections attempted after the first one",
+        &["exchange"]
+    ).unwrap()
+});
+
+static LAST_MESSAGE_AGE: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "arbitrage_last_message_age_seconds",
+        "Seconds since the last websocket message per venue",
+        &["exchange"]
+    ).unwrap()
+});
+
+static TICK_TO_ENGINE: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "arbitrage_tick_to_engine_seconds",
+        "Time from receiving a price on the websocket to the engine processing it",
+        &["exchange"],
+        // 10 micros to ~5 secs
+        exponential_buckets(0.00001, 2.5, 15).unwrap()
+    ).unwrap()
+});
+
+static EXCHANGE_TO_RECEIVE: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "arbitrage_exchange_to_receive_seconds",
+        "Time from the venue event behind a price to receiving it, clocks apart included",
+        &["exchange"],
+        // 100 micros to ~30 secs
+        exponential_buckets(0.0001, 2.5, 15).unwrap()
+    ).unwrap()
+});
+
+static SLOT_LAG: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "arbitrage_slot_lag_slots",
+        "Slots a price is behind the newest slot the venue notified, some 400 millis each",
+        &["exchange"],
+        // 1 to 512 slots
+        exponential_buckets(1.0, 2.0, 10).unwrap()
+    ).unwrap()
+});
+
+static STALE_PRICES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "arbitrage_stale_prices_total",
+        "Prices left out of the comparisons for being too old",
+        &["exchange"]
+    ).unwrap()
+});
+
+static PRICE: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!("arbitrage_price", "Current price per venue", &["exchange", "market"]).unwrap()
+});
+
+static BID: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!("arbitrage_best_bid", "Current best bid per venue", &["exchange", "market"]).unwrap()
+});
+
+static ASK: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!("arbitrage_best_ask", "Current best ask per venue", &["exchange", "market"]).unwrap()
+});
+
+static SPREAD: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "arbitrage_cross_venue_spread",
+        "Highest minus lowest price across venues per instrument",
+        &["instrument"]
+    ).unwrap()
+});
+
+static SPREAD_BPS: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "arbitrage_cross_venue_spread_bps",
+        "Cross venue spread in basis points of the lowest price per instrument",
+        &["instrument"]
+    ).unwrap()
+});
+
+static INVENTORY: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!("arbitrage_inventory", "Balance per venue and asset", &["exchange", "asset"]).unwrap()
+});
+
+static REBALANCE_NEEDED: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "arbitrage_rebalance_needed",
+        "Whether the venue holds too little of the asset to trade the configured quantity",
+        &["exchange", "asset"]
+    ).unwrap()
+});
+
+// ages are only known at scrape time
+static LAST_MESSAGE: LazyLock<Mutex<HashMap<&'static str, Instant>>> = LazyLock::new(Default::default);
+
+static NEWEST_SLOT: LazyLock<Mutex<HashMap<&'static str, u64>>> = LazyLock::new(Default::default);
+
+pub fn message_received(exchange_id: &'static str, received_at: Instant) {
+    MESSAGES_RECEIVED.with_label_values(&[exchange_id]).inc();
+    LAST_MESSAGE.lock().unwrap().insert(exchange_id, received_at);
+}
+
+/// Messages received so far from the venue.
+pub fn messages_received(exchange_id: &'static str) -> u64 {
+    MESSAGES_RECEIVED.with_label_values(&[exchange_id]).get()
+}
+
+pub fn parse_failed(exchange_id: &'static str) {
+    PARSE_FAILURES.with_label_values(&[exchange_id]).inc();
+}
+
+/// Records how long the price took from the venue event to arrive, a venue clock ahead
+/// counting as no time.
+pub fn event_received(exchange_id: &'static str, event_time: SystemTime) {
+    let latency = SystemTime::now().duration_since(event_time).unwrap_or_default();
+    EXCHANGE_TO_RECEIVE.with_label_values(&[exchange_id]).observe(latency.as_secs_f64());
+}
+
+// on-chain venues say the slot instead of the time of the event
+pub fn slot_received(exchange_id: &'static str, slot: u64) {
+    let mut newest_slots = NEWEST_SLOT.lock().unwrap();
+    let newest = newest_slots.entry(exchange_id).or_insert(slot);
+    *newest = (*newest).max(slot);
+
+    SLOT_LAG.with_label_values(&[exchange_id]).observe((*newest - slot) as f64);
+}
+
+pub fn price_stale(exchange_id: &'static str) {
+    STALE_PRICES.with_label_values(&[exchange_id]).inc();
+}
+
+pub fn reconnected(exchange_id: &'static str) {
+    RECONNECTS.with_label_values(&[exchange_id]).inc();
+}
+
+/// Records a price accepted by the engine.
+pub fn price_processed(market_price: &MarketPrice) {
+    let labels = [market_price.exchange_id, market_price.market.as_str()];
+
+    if let Some(received_at) = market_price.received_at {
+        TICK_TO_ENGINE.with_label_values(&[market_price.exchange_id]).observe(
+            received_at.elapsed().as_secs_f64()
+        );
+    }
+
+    PRICE.with_label_values(&labels).set(to_f64(market_price.price));
+
+    if let Some(bid) = market_price.bid {
+        BID.with_label_values(&labels).set(to_f64(bid));
+    }
+    if let Some(ask) = market_price.ask {
+        ASK.with_label_values(&labels).set(to_f64(ask));
+    }
+}
+
+pub fn spread(instrument: &Instrument, lowest: Decimal, highest: Decimal) {
+    let instrument = instrument.to_string();
+    let spread = highest - lowest;
+
+    SPREAD.with_label_values(&[&instrument]).set(to_f64(spread));
+    if !lowest.is_zero() {
+        SPREAD_BPS.with_label_values(&[&instrument]).set(
+            to_f64((spread / lowest) * Decimal::from(10_000))
+        );
+    }
+}
+
+pub fn inventory(exchange_id: &str, asset: &str, balance: Decimal) {
+    INVENTORY.with_label_values(&[exchange_id, asset]).set(to_f64(balance));
+}
+
+pub fn rebalance_needed(exchange_id: &str, asset: &str, needed: bool) {
+    REBALANCE_NEEDED.with_label_values(&[exchange_id, asset]).set(f64::from(u8::from(needed)));
+}
+
+/// Renders all metrics in the prometheus text format.
+pub fn render() -> String {
+    for (exchange_id, last_message) in LAST_MESSAGE.lock().unwrap().iter() {
+        LAST_MESSAGE_AGE.with_label_values(&[exchange_id]).set(last_message.elapsed().as_secs_f64());
+    }
+
+    let mut buffer = vec![];
+    if let Err(err) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        log::error!("cannot encode metrics {err}");
+    }
+
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        message_received("metrics_test", Instant::now());
+        parse_failed("metrics_test");
+        reconnected("metrics_test");
+
+        price_processed(
+            &(MarketPrice {
+                exchange_id: "metrics_test",
+                market: "SOL/USDT".to_string(),
+                price: dec!(150.5),
+                bid: Some(dec!(150.4)),
+                ask: Some(dec!(150.6)),
+                received_at: Some(Instant::now()),
+                ..Default::default()
+            })
+        );
+        spread(&"METRICS/TEST".parse().unwrap(), dec!(100), dec!(101));
+        inventory("metrics_test", "SOL", dec!(2.5));
+        rebalance_needed("metrics_test", "USDT", true);
+        event_received("metrics_test", SystemTime::now() - std::time::Duration::from_millis(20));
+        price_stale("metrics_test");
+        // the second one slot behind the first
+        slot_received("metrics_test", 101);
+        slot_received("metrics_test", 100);
+
+        let metrics = render();
+
+        assert!(metrics.contains("arbitrage_messages_received_total{exchange=\"metrics_test\"} 1"));
+        assert_eq!(messages_received("metrics_test"), 1);
+        assert!(metrics.contains("arbitrage_parse_failures_total{exchange=\"metrics_test\"} 1"));
+        assert!(metrics.contains("arbitrage_reconnects_total{exchange=\"metrics_test\"} 1"));
+        assert!(
+            metrics.contains(
+                "arbitrage_exchange_to_receive_seconds_count{exchange=\"metrics_test\"} 1"
+            )
+        );
+        assert!(metrics.contains("arbitrage_slot_lag_slots_count{exchange=\"metrics_test\"} 2"));
+        assert!(metrics.contains("arbitrage_slot_lag_slots_sum{exchange=\"metrics_test\"} 1"));
+        assert!(metrics.contains("arbitrage_stale_prices_total{exchange=\"metrics_test\"} 1"));
+        assert!(metrics.contains("arbitrage_last_message_age_seconds{exchange=\"metrics_test\"}"));
+        assert!(metrics.contains("arbitrage_tick_to_engine_seconds_count{exchange=\"metrics_test\"} 1"));
+        assert!(metrics.contains("arbitrage_price{exchange=\"metrics_test\",market=\"SOL/USDT\"} 150.5"));
+        assert!(metrics.contains("arbitrage_best_bid{exchange=\"metrics_test\",market=\"SOL/USDT\"} 150.4"));
+        assert!(metrics.contains("arbitrage_best_ask{exchange=\"metrics_test\",market=\"SOL/USDT\"} 150.6"));
+        assert!(metrics.contains("arbitrage_cross_venue_spread{instrument=\"METRICS/TEST\"} 1"));
+        assert!(metrics.contains("arbitrage_cross_venue_spread_bps{instrument=\"METRICS/TEST\"} 100"));
+        assert!(metrics.contains("arbitrage_inventory{asset=\"SOL\",exchange=\"metrics_test\"} 2.5"));
+        assert!(metrics.contains("arbitrage_rebalance_needed{asset=\"USDT\",exchange=\"metrics_test\"} 1"));
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    str::FromStr,
+    time::{ SystemTime, UNIX_EPOCH },
+};
+
+use rust_decimal::Decimal;
+use serde_json::json;
+
+use crate::{ conversion::{ self, Conversion }, engine::Engine, Instrument, Venue };
+
+/// Prices of an instrument after an update, ranked from the lowest.
+#[derive(Debug, Clone)]
+pub struct PriceLine {
+    pub timestamp: SystemTime,
+    pub instrument: Instrument,
+    // price -> sorted venues, with the conversion of their price if any
+    pub levels: Vec<(Decimal, Vec<String>)>,
+    pub spread: Option<Decimal>,
+}
+
+impl PriceLine {
+    pub fn new(
+        instrument: &Instrument,
+        engine: &Engine<Venue>,
+        conversions: &BTreeMap<Venue, Conversion>
+    ) -> Self {
+        let levels = engine
+            .iter()
+            .map(|(price, venues)| {
+                let mut venues = venues
+                    .map(|venue| conversion::label(venue, conversions.get(venue)))
+                    .collect::<Vec<_>>();
+                venues.sort();
+                (*price, venues)
+            })
+            .collect::<Vec<_>>();
+
+        let spread = match (levels.first(), levels.last()) {
+            (Some((lowest, _)), Some((highest, _))) => Some(highest - lowest),
+            _ => None,
+        };
+
+        Self { timestamp: SystemTime::now(), instrument: instrument.clone(), levels, spread }
+    }
+
+    fn timestamp_ms(&self) -> u64 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    pub fn spread_bps(&self) -> Option<Decimal> {
+        let (lowest, _) = self.levels.first()?;
+        self.spread?
+            .checked_div(*lowest)
+            .map(|spread| (spread * Decimal::from(10_000)).round_dp(2).normalize())
+    }
+}
+
+pub trait OutputSink {
+    fn write(&mut self, line: &PriceLine) -> std::io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    // the dashboard reads the markets itself
+    Tui,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "tui" => Ok(Self::Tui),
+            _ => Err(format!("unknown output {s}, expected table, json, csv or tui")),
+        }
+    }
+}
+
+/// Opens the sink, json lines going to stdout and csv rows appended to `path`, `None`
+/// when the output does not take lines.
+pub fn open_sink(
+    format: OutputFormat,
+    path: &Path
+) -> std::io::Result<Option<Box<dyn OutputSink + Send>>> {
+    match format {
+        OutputFormat::Table => Ok(Some(Box::new(TableSink))),
+        OutputFormat::Json => Ok(Some(Box::new(JsonLinesSink(std::io::stdout())))),
+        OutputFormat::Csv => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            let header = file.metadata()?.len() == 0;
+            Ok(Some(Box::new(CsvSink { writer: file, header })))
+        }
+        OutputFormat::Tui => Ok(None),
+    }
+}
+
+/// The human readable table, through the logger.
+pub struct TableSink;
+
+impl OutputSink for TableSink {
+    fn write(&mut self, line: &PriceLine) -> std::io::Result<()> {
+        log::info!("");
+        log::info!("Prices {}:", line.instrument);
+
+        line.levels
+            .iter()
+            .enumerate()
+            .for_each(|(idx, (price, venues))| {
+                let mut price = *price;
+                price.rescale(4);
+
+                log::info!("   {} - {price} {venues:?}", idx + 1);
+            });
+
+        Ok(())
+    }
+}
+
+pub struct JsonLinesSink<W>(pub W);
+
+impl<W: Write> OutputSink for JsonLinesSink<W> {
+    fn write(&mut self, line: &PriceLine) -> std::io::Result<()> {
+        let prices = line.levels
+            .iter()
+            .map(|(price, venues)| json!({"price": price, "venues": venues}))
+            .collect::<Vec<_>>();
+
+        let json =
+            json!({
+                "timestamp_ms": line.timestamp_ms(),
+                "instrument": line.instrument.to_string(),
+                "prices": prices,
+                "spread": line.spread,
+                "spread_bps": line.spread_bps(),
+            });
+
+        writeln!(self.0, "{json}")?;
+        self.0.flush()
+    }
+}
+
+/// Rows of `timestamp_ms,instrument,venues,spread,spread_bps`, venues ranked as
+/// `venue=price` separated by `;`.
+pub struct CsvSink<W> {
+    pub writer: W,
+    pub header: bool, // still to be written
+}
+
+impl<W: Write> OutputSink for CsvSink<W> {
+    fn write(&mut self, line: &PriceLine) -> std::io::Result<()> {
+        if self.header {
+            writeln!(self.writer, "timestamp_ms,instrument,venues,spread,spread_bps")?;
+            self.header = false;
+        }
+
+        let venues = line.levels
+            .iter()
+            .flat_map(|(price, venues)| venues.iter().map(move |venue| format!("{venue}={price}")))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        writeln!(
+            self.writer,
+            "{},{},{},{},{}",
+            line.timestamp_ms(),
+            csv_field(&line.instrument.to_string()),
+            csv_field(&venues),
+            line.spread.map(|spread| spread.to_string()).unwrap_or_default(),
+            line.spread_bps().map(|spread_bps| spread_bps.to_string()).unwrap_or_default()
+        )?;
+        self.writer.flush()
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::*;
+
+    fn line() -> PriceLine {
+        let mut engine = Engine::<Venue>::default();
+        engine.update(Venue { exchange_id: "kraken", market: "SOL/USDT".to_string() }, dec!(101));
+        engine.update(Venue { exchange_id: "binance", market: "SOLUSDT".to_string() }, dec!(100));
+
+        PriceLine {
+            timestamp: UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_000),
+            ..PriceLine::new(&"SOL/USDT".parse().unwrap(), &engine, &BTreeMap::new())
+        }
+    }
+
+    #[test]
+    fn test_price_line() {
+        let line = line();
+
+        assert_eq!(line.levels.len(), 2);
+        assert_eq!(line.levels[0], (dec!(100), vec!["binance:SOLUSDT".to_string()]));
+        assert_eq!(line.spread, Some(dec!(1)));
+        assert_eq!(line.spread_bps(), Some(dec!(100)));
+    }
+
+    #[test]
+    fn test_price_line_conversions() {
+        let mut engine = Engine::<Venue>::default();
+        let solana = Venue { exchange_id: "solana", market: "pool".to_string() };
+        engine.update(solana.clone(), dec!(100.01));
+
+        let conversion = Conversion {
+            native: "SOL/USDC".parse().unwrap(),
+            rate: dec!(0.9999),
+            assets: vec!["USDC".to_string(), "USD".to_string()],
+            venues: vec![Venue { exchange_id: "kraken", market: "USDC/USD".to_string() }],
+        };
+        let conversions = BTreeMap::from([(solana, conversion)]);
+        let line = PriceLine::new(&"SOL/USD".parse().unwrap(), &engine, &conversions);
+
+        assert_eq!(line.levels, vec![(
+            dec!(100.01),
+            vec!["solana:pool (USDC→USD 0.9999 kraken:USDC/USD)".to_string()],
+        )]);
+    }
+
+    #[test]
+    fn test_json_lines_sink() {
+        let mut sink = JsonLinesSink(vec![]);
+        sink.write(&line()).unwrap();
+        sink.write(&line()).unwrap();
+
+        let output = String::from_utf8(sink.0).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(lines[0]).unwrap(),
+            json!({
+                "timestamp_ms": 1_700_000_000_000_u64,
+                "instrument": "SOL/USDT",
+                "prices": [
+                    {"price": "100", "venues": ["binance:SOLUSDT"]},
+                    {"price": "101", "venues": ["kraken:SOL/USDT"]}
+                ],
+                "spread": "1",
+                "spread_bps": "100"
+            })
+        );
+    }
+
+    #[test]
+    fn test_csv_sink() {
+        let mut sink = CsvSink { writer: vec![], header: true };
+        sink.write(&line()).unwrap();
+        sink.write(&line()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "timestamp_ms,instrument,venues,spread,spread_bps\n\
+             1700000000000,SOL/USDT,binance:SOLUSDT=100;kraken:SOL/USDT=101,1,100\n\
+             1700000000000,SOL/USDT,binance:SOLUSDT=100;kraken:SOL/USDT=101,1,100\n"
+        );
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,487 @@
+use std::{ collections::BTreeMap, time::UNIX_EPOCH };
+
+use axum::{
+    extract::{ Path, State },
+    http::{ header, StatusCode },
+    response::IntoResponse,
+    routing::{ get, post },
+    Json,
+    Router,
+};
+use rust_decimal::Decimal;
+use serde_json::{ json, Value };
+use tokio::net::TcpListener;
+
+use crate::{
+    conversion::Conversion,
+    engine::Engine,
+    graph::Cycle,
+    health,
+    execution::risk::KillSwitch,
+    markets::{ Opportunity, SharedMarkets },
+    metrics,
+    websocket::{ Control, Subscription },
+    Instrument,
+    Venue,
+};
+
+#[derive(Clone)]
+pub struct ServerState {
+    // venues that must be streaming fresh prices to be ready
+    pub min_ready_venues: usize,
+    pub markets: SharedMarkets,
+    pub kill_switch: KillSwitch,
+    // control of every venue task
+    pub subscriptions: BTreeMap<&'static str, Control>,
+}
+
+pub fn router(state: ServerState) -> Router {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+        // instruments contain a slash, e.g. /prices/SOL/USDT
+        .route("/prices/*instrument", get(get_prices))
+        .route("/best", get(get_best))
+        .route("/opportunities", get(get_opportunities))
+        .route("/cycles", get(get_cycles))
+        .route("/kill-switch", get(get_kill_switch).post(post_kill_switch))
+        // markets may contain a slash too, e.g. /subscriptions/kraken/ETH/USDT
+        .route(
+            "/subscriptions/:exchange_id/*market",
+            post(post_subscription).delete(delete_subscription)
+        )
+        .with_state(state)
+}
+
+pub async fn run_server(listener: TcpListener, state: ServerState) {
+    if let Err(err) = axum::serve(listener, router(state)).await {
+        log::error!("http server {err}");
+    }
+}
+
+async fn get_metrics() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render())
+}
+
+// the process is alive as long as it answers, whatever the venues state
+async fn get_healthz() -> impl IntoResponse {
+    Json(json!({"venues": health::VENUES.statuses()}))
+}
+
+async fn get_readyz(State(state): State<ServerState>) -> impl IntoResponse {
+    let streaming = health::VENUES.streaming();
+    let ready = streaming >= state.min_ready_venues;
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (
+        status,
+        Json(
+            json!({
+                "ready": ready,
+                "streaming": streaming,
+                "min_ready_venues": state.min_ready_venues,
+                "venues": health::VENUES.statuses(),
+            })
+        ),
+    )
+}
+
+// ordered from the lowest price
+async fn get_prices(
+    State(state): State<ServerState>,
+    Path(instrument): Path<String>
+) -> impl IntoResponse {
+    let Ok(instrument) = instrument.parse::<Instrument>() else {
+        return (StatusCode::BAD_REQUEST, Json(json!({"error": "invalid instrument"})));
+    };
+
+    let markets = state.markets.read().unwrap();
+    let Some(engine) = markets.engine(&instrument) else {
+        return (StatusCode::NOT_FOUND, Json(json!({"error": "unknown instrument"})));
+    };
+
+    let prices = engine
+        .iter()
+        .map(|(price, venues)| level(price, venues))
+        .collect::<Vec<_>>();
+
+    (StatusCode::OK, Json(json!({"instrument": instrument.to_string(), "prices": prices})))
+}
+
+async fn get_best(State(state): State<ServerState>) -> impl IntoResponse {
+    let markets = state.markets.read().unwrap();
+
+    let best = markets
+        .engines()
+        .map(|(instrument, engine)| best_json(instrument, engine))
+        .collect::<Vec<_>>();
+
+    Json(best)
+}
+
+// newest first
+async fn get_opportunities(State(state): State<ServerState>) -> impl IntoResponse {
+    let markets = state.markets.read().unwrap();
+
+    let opportunities = markets.opportunities().map(opportunity_json).collect::<Vec<_>>();
+
+    Json(opportunities)
+}
+
+// newest first
+async fn get_cycles(State(state): State<ServerState>) -> impl IntoResponse {
+    let markets = state.markets.read().unwrap();
+
+    let cycles = markets.cycles().map(cycle_json).collect::<Vec<_>>();
+
+    Json(cycles)
+}
+
+async fn get_kill_switch(State(state): State<ServerState>) -> impl IntoResponse {
+    Json(json!({"engaged": state.kill_switch.is_engaged()}))
+}
+
+// stops new orders, for good
+async fn post_kill_switch(State(state): State<ServerState>) -> impl IntoResponse {
+    state.kill_switch.engage("http");
+    Json(json!({"engaged": true}))
+}
+
+// subscribes the venue to the market on its live connections
+async fn post_subscription(
+    State(state): State<ServerState>,
+    Path((exchange_id, market)): Path<(String, String)>
+) -> impl IntoResponse {
+    change_subscription(&state, &exchange_id, &market, Subscription::Subscribe)
+}
+
+// unsubscribes the venue from the market, its prices left out from then on
+async fn delete_subscription(
+    State(state): State<ServerState>,
+    Path((exchange_id, market)): Path<(String, String)>
+) -> impl IntoResponse {
+    change_subscription(&state, &exchange_id, &market, Subscription::Unsubscribe)
+}
+
+fn change_subscription(
+    state: &ServerState,
+    exchange_id: &str,
+    market: &str,
+    change: fn(String) -> Subscription
+) -> (StatusCode, Json<Value>) {
+    let Some((exchange_id, control)) = state.subscriptions.get_key_value(exchange_id) else {
+        return (StatusCode::NOT_FOUND, Json(json!({"error": "unknown venue"})));
+    };
+
+    let market = control.market(market);
+    let subscription = change(market.clone());
+    let description = subscription.to_string();
+    if control.send(subscription.clone()).is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({"error": "venue stopped"})));
+    }
+
+    // the prices still on their way are left out by the engine too
+    let venue = Venue { exchange_id, market };
+    match subscription {
+        Subscription::Subscribe(_) => state.markets.write().unwrap().subscribe(&venue),
+        Subscription::Unsubscribe(_) => state.markets.write().unwrap().unsubscribe(venue),
+    }
+
+    (StatusCode::ACCEPTED, Json(json!({"venue": exchange_id, "change": description})))
+}
+
+pub fn best_json(instrument: &Instrument, engine: &Engine<Venue>) -> Value {
+    json!({
+        "instrument": instrument.to_string(),
+        "lowest": engine.lowest_price().map(|(price, venues)| level(price, venues)),
+        "highest": engine.highest_price().map(|(price, venues)| level(price, venues)),
+        "levels": engine.len(),
+    })
+}
+
+pub fn opportunity_json(opportunity: &Opportunity) -> Value {
+    json!({
+        "instrument": opportunity.instrument.to_string(),
+        "buy_venue": opportunity.buy_venue.to_string(),
+        "buy_price": opportunity.buy_price,
+        "sell_venue": opportunity.sell_venue.to_string(),
+        "sell_price": opportunity.sell_price,
+        "spread_bps": opportunity.spread_bps.round_dp(2).normalize(),
+        "executable_bps": opportunity.executable_bps.round_dp(2).normalize(),
+        "buy_conversion": opportunity.buy_conversion.as_ref().map(Conversion::to_string),
+        "sell_conversion": opportunity.sell_conversion.as_ref().map(Conversion::to_string),
+        "detected_at_ms": opportunity.detected_at
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default(),
+    })
+}
+
+pub fn cycle_json(cycle: &Cycle) -> Value {
+    let steps = cycle.steps
+        .iter()
+        .map(|step| {
+            json!({
+                "venue": step.venue.to_string(),
+                "side": format!("{:?}", step.side).to_lowercase(),
+                "price": step.price,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "path": cycle.path(),
+        "return_bps": cycle.return_bps.round_dp(2).normalize(),
+        "steps": steps,
+        "detected_at_ms": cycle.detected_at
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default(),
+    })
+}
+
+fn level<'a>(price: &Decimal, venues: impl Iterator<Item = &'a Venue>) -> Value {
+    let mut venues = venues.map(Venue::to_string).collect::<Vec<_>>();
+    venues.sort();
+
+    json!({"price": price, "venues": venues})
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ sync::{ Arc, RwLock }, time::Instant };
+
+    use axum::{ body::{ to_bytes, Body }, http::Request };
+    use rust_decimal_macros::dec;
+    use tower::ServiceExt;
+
+    use crate::{ exchange::kraken::Kraken, graph::Graph, markets::Markets };
+
+    use super::*;
+
+    fn state(min_ready_venues: usize) -> ServerState {
+        ServerState {
+            min_ready_venues,
+            markets: Arc::new(RwLock::new(Markets::new(dec!(10)))),
+            kill_switch: KillSwitch::default(),
+            subscriptions: BTreeMap::new(),
+        }
+    }
+
+    async fn get(min_ready_venues: usize, uri: &str) -> (StatusCode, String) {
+        request(state(min_ready_venues), uri).await
+    }
+
+    async fn request(state: ServerState, uri: &str) -> (StatusCode, String) {
+        let response = router(state)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap()).await
+            .unwrap();
+
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_get_metrics() {
+        metrics::message_received("server_test", Instant::now());
+
+        let (status, body) = get(0, "/metrics").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("arbitrage_messages_received_total{exchange=\"server_test\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_get_healthz() {
+        let (status, body) = get(usize::MAX, "/healthz").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(serde_json::from_str::<Value>(&body).unwrap()["venues"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_get_readyz() {
+        let (status, body) = get(0, "/readyz").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["ready"], true);
+
+        let (status, body) = get(usize::MAX, "/readyz").await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["ready"], false);
+    }
+
+    fn venue(exchange_id: &'static str) -> Venue {
+        Venue { exchange_id, market: "SOL/USDT".to_string() }
+    }
+
+    fn markets_state() -> ServerState {
+        let state = state(0);
+        {
+            let mut markets = state.markets.write().unwrap();
+            let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+            markets.update(sol.clone(), venue("binance"), dec!(100));
+            markets.update(sol.clone(), venue("kraken"), dec!(101));
+            markets.update(sol, venue("solana"), dec!(100));
+        }
+        state
+    }
+
+    #[tokio::test]
+    async fn test_get_prices() {
+        let (status, body) = request(markets_state(), "/prices/SOL/USDT").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"instrument": "SOL/USDT", "prices": [
+                {"price": "100", "venues": ["binance:SOL/USDT", "solana:SOL/USDT"]},
+                {"price": "101", "venues": ["kraken:SOL/USDT"]}
+            ]})
+        );
+
+        let (status, _) = request(markets_state(), "/prices/ETH/USDT").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = request(markets_state(), "/prices/SOLUSDT").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_best() {
+        let (status, body) = request(markets_state(), "/best").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!([{
+                "instrument": "SOL/USDT",
+                "lowest": {"price": "100", "venues": ["binance:SOL/USDT", "solana:SOL/USDT"]},
+                "highest": {"price": "101", "venues": ["kraken:SOL/USDT"]},
+                "levels": 2
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_opportunities() {
+        let (status, body) = request(markets_state(), "/opportunities").await;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let opportunities = serde_json::from_str::<Value>(&body).unwrap();
+        assert_eq!(opportunities.as_array().unwrap().len(), 1);
+        assert_eq!(opportunities[0]["buy_venue"], "binance:SOL/USDT");
+        assert_eq!(opportunities[0]["sell_venue"], "kraken:SOL/USDT");
+        assert_eq!(opportunities[0]["spread_bps"], "100");
+    }
+
+    #[tokio::test]
+    async fn test_get_cycles() {
+        let state = markets_state();
+        {
+            let mut graph = Graph::new(dec!(0), Default::default());
+            let binance = |market: &str| Venue {
+                exchange_id: "binance",
+                market: market.to_string(),
+            };
+            for (market, instrument, price) in [
+                ("SOLUSDT", "SOL/USDT", dec!(100)),
+                ("SOLBTC", "SOL/BTC", dec!(0.002)),
+                ("BTCUSDT", "BTC/USDT", dec!(50500)),
+            ] {
+                graph.update(binance(market), instrument.parse().unwrap(), price, price);
+            }
+            state.markets.write().unwrap().record_cycle(graph.cycle().unwrap());
+        }
+
+        let (status, body) = request(state, "/cycles").await;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let cycles = serde_json::from_str::<Value>(&body).unwrap();
+        assert_eq!(cycles[0]["path"], "BTC→USDT→SOL→BTC");
+        assert_eq!(cycles[0]["return_bps"], "100");
+        assert_eq!(cycles[0]["steps"][0], json!({
+            "venue": "binance:BTCUSDT",
+            "side": "sell",
+            "price": "50500"
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch() {
+        let state = state(0);
+
+        let (_, body) = request(state.clone(), "/kill-switch").await;
+        assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["engaged"], false);
+
+        let response = router(state.clone())
+            .oneshot(Request::post("/kill-switch").body(Body::empty()).unwrap()).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert!(state.kill_switch.is_engaged());
+        let (_, body) = request(state, "/kill-switch").await;
+        assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["engaged"], true);
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions() {
+        let mut state = state(0);
+        let (control, mut control_rx) = tokio::sync::mpsc::unbounded_channel();
+        state.subscriptions.insert("kraken", Control::new::<Kraken>(control));
+
+        let send = |request: axum::http::request::Builder| {
+            router(state.clone()).oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = send(Request::post("/subscriptions/kraken/eth/usdt")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(control_rx.recv().await, Some(Subscription::Subscribe("ETH/USDT".to_string())));
+
+        let response = send(Request::delete("/subscriptions/kraken/ETH/USDT")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            control_rx.recv().await,
+            Some(Subscription::Unsubscribe("ETH/USDT".to_string()))
+        );
+
+        let response = send(Request::post("/subscriptions/binance/ethusdt")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe() {
+        let mut state = markets_state();
+        let (control, _control_rx) = tokio::sync::mpsc::unbounded_channel();
+        state.subscriptions.insert("kraken", Control::new::<Kraken>(control));
+
+        let response = router(state.clone())
+            .oneshot(
+                Request::delete("/subscriptions/kraken/sol/usdt").body(Body::empty()).unwrap()
+            ).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // a price of the market still queued
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+        state.markets.write().unwrap().update(sol, venue("kraken"), dec!(102));
+
+        let (_, body) = request(state, "/best").await;
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!([{
+                "instrument": "SOL/USDT",
+                "lowest": {"price": "100", "venues": ["binance:SOL/USDT", "solana:SOL/USDT"]},
+                "highest": {"price": "100", "venues": ["binance:SOL/USDT", "solana:SOL/USDT"]},
+                "levels": 1
+            }])
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use std::{ collections::HashMap, time::{ Duration, Instant, SystemTime, UNIX_EPOCH } };
+
+use ratatui::{
+    crossterm::event::{ self, Event, KeyCode, KeyEventKind, KeyModifiers },
+    layout::{ Constraint, Layout, Rect },
+    style::{ Color, Style, Stylize },
+    text::Line,
+    widgets::{ Block, List, ListItem, Row, Table },
+    DefaultTerminal,
+    Frame,
+};
+use tokio::sync::watch;
+
+use crate::{
+    conversion,
+    health::{ self, VenueState, VenueStatus },
+    markets::{ Markets, SharedMarkets },
+    metrics,
+    output::PriceLine,
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs the dashboard until `q` is pressed, which shuts the process down, or until the
+/// process shuts down otherwise. Blocks the thread it runs on.
+pub fn run_tui(markets: SharedMarkets, shutdown: watch::Sender<bool>) -> std::io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = run(&mut terminal, &markets, &shutdown);
+    ratatui::restore();
+
+    // the engine stops along with the dashboard, also when it fails
+    shutdown.send_replace(true);
+
+    result
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    markets: &SharedMarkets,
+    shutdown: &watch::Sender<bool>
+) -> std::io::Result<()> {
+    let mut dashboard = Dashboard::default();
+
+    while !*shutdown.borrow() {
+        let statuses = health::VENUES.statuses();
+        dashboard.update_rates(&statuses);
+
+        terminal.draw(|frame| {
+            dashboard.render(frame, &statuses, &markets.read().unwrap());
+        })?;
+
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                break;
+            }
+            // raw mode turns ctrl+c into a key press instead of a signal
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                break;
+            }
+            KeyCode::Down => {
+                dashboard.scroll = dashboard.scroll.saturating_add(1);
+            }
+            KeyCode::Up => {
+                dashboard.scroll = dashboard.scroll.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Dashboard {
+    // messages received and when, at the last rate update
+    counts: HashMap<&'static str, (u64, Instant)>,
+    rates: HashMap<&'static str, f64>, // messages per second
+    scroll: usize, // opportunities scrolled past
+}
+
+impl Dashboard {
+    fn update_rates(&mut self, statuses: &[VenueStatus]) {
+        let now = Instant::now();
+
+        for status in statuses {
+            let count = metrics::messages_received(status.exchange_id);
+
+            let Some((previous, at)) = self.counts.get(status.exchange_id).copied() else {
+                self.counts.insert(status.exchange_id, (count, now));
+                continue;
+            };
+
+            let elapsed = now.duration_since(at);
+            if elapsed < RATE_INTERVAL {
+                continue;
+            }
+
+            self.rates.insert(
+                status.exchange_id,
+                (count.saturating_sub(previous) as f64) / elapsed.as_secs_f64()
+            );
+            self.counts.insert(status.exchange_id, (count, now));
+        }
+    }
+
+    fn render(&self, frame: &mut Frame, statuses: &[VenueStatus], markets: &Markets) {
+        let [venues_area, ladders_area, opportunities_area] = Layout::vertical([
+            Constraint::Length((statuses.len() as u16) + 3),
+            Constraint::Min(6),
+            Constraint::Percentage(30),
+        ]).areas(frame.area());
+
+        self.render_venues(frame, venues_area, statuses);
+        render_ladders(frame, ladders_area, markets);
+        self.render_opportunities(frame, opportunities_area, markets);
+    }
+
+    fn render_venues(&self, frame: &mut Frame, area: Rect, statuses: &[VenueStatus]) {
+        let now_ms = unix_millis(SystemTime::now());
+
+        let rows = statuses.iter().map(|status| {
+            let color = match status.state {
+                VenueState::Streaming => Color::Green,
+                VenueState::Subscribed | VenueState::Connecting => Color::Yellow,
+                VenueState::Stale | VenueState::Backoff => Color::Red,
+            };
+
+            Row::new([
+                status.exchange_id.to_string(),
+                format!("{:?}", status.state).to_lowercase(),
+                format!("{}s", now_ms.saturating_sub(status.since_ms) / 1000),
+                format!("{:.1}", self.rates.get(status.exchange_id).copied().unwrap_or_default()),
+                status.last_price_ms
+                    .map(|last_price_ms| format!("{}ms", now_ms.saturating_sub(last_price_ms)))
+                    .unwrap_or("-".to_string()),
+            ]).style(Style::new().fg(color))
+        });
+
+        let table = Table::new(rows, [
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(14),
+        ])
+            .header(Row::new(["venue", "state", "since", "msg/s", "last price"]).bold())
+            .block(Block::bordered().title("Venues"));
+
+        frame.render_widget(table, area);
+    }
+
+    fn render_opportunities(&self, frame: &mut Frame, area: Rect, markets: &Markets) {
+        let items = markets
+            .opportunities()
+            .skip(self.scroll)
+            .map(|opportunity| {
+                ListItem::new(
+                    format!(
+                        "{} {} buy {} @ {} sell {} @ {} {} bps",
+                        format_time(opportunity.detected_at),
+                        opportunity.instrument,
+                        conversion::label(
+                            &opportunity.buy_venue,
+                            opportunity.buy_conversion.as_ref()
+                        ),
+                        opportunity.buy_price.round_dp(4),
+                        conversion::label(
+                            &opportunity.sell_venue,
+                            opportunity.sell_conversion.as_ref()
+                        ),
+                        opportunity.sell_price.round_dp(4),
+                        opportunity.spread_bps.round_dp(2).normalize()
+                    )
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let list = List::new(items).block(
+            Block::bordered().title("Opportunities (up/down to scroll, q to quit)")
+        );
+
+        frame.render_widget(list, area);
+    }
+}
+
+// an instrument ladder per column, from the lowest price
+fn render_ladders(frame: &mut Frame, area: Rect, markets: &Markets) {
+    let lines = markets
+        .engines()
+        .map(|(instrument, engine)| PriceLine::new(instrument, engine, markets.conversions()))
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        frame.render_widget(Block::bordered().title("Waiting for prices..."), area);
+        return;
+    }
+
+    let areas = Layout::horizontal(vec![Constraint::Fill(1); lines.len()]).split(area);
+
+    for (line, area) in lines.iter().zip(areas.iter()) {
+        let spread_bps = line
+            .spread_bps()
+            .map(|spread_bps| format!("{spread_bps} bps"))
+            .unwrap_or("-".to_string());
+
+        let rows = line.levels
+            .iter()
+            .enumerate()
+            .map(|(idx, (price, venues))| {
+                Row::new([(idx + 1).to_string(), price.round_dp(4).to_string(), venues.join(" ")])
+            });
+
+        let table = Table::new(rows, [
+            Constraint::Length(3),
+            Constraint::Length(14),
+            Constraint::Fill(1),
+        ]).block(
+            Block::bordered()
+                .title(Line::from(line.instrument.to_string()).bold())
+                .title(Line::from(format!("spread {spread_bps}")).right_aligned())
+        );
+
+        frame.render_widget(table, *area);
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// utc time of the day
+fn format_time(time: SystemTime) -> String {
+    let secs = unix_millis(time) / 1000;
+    format!("{:02}:{:02}:{:02}", (secs / 3600) % 24, (secs / 60) % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{ backend::TestBackend, Terminal };
+    use rust_decimal_macros::dec;
+
+    use crate::{ Instrument, Venue };
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let sol = "SOL/USDT".parse::<Instrument>().unwrap();
+        let mut markets = Markets::new(dec!(10));
+        let binance = Venue { exchange_id: "binance", market: "SOLUSDT".to_string() };
+        let kraken = Venue { exchange_id: "kraken", market: "SOL/USDT".to_string() };
+        markets.update(sol.clone(), binance, dec!(100));
+        markets.update(sol, kraken, dec!(101));
+
+        let statuses = vec![VenueStatus {
+            exchange_id: "binance",
+            state: VenueState::Streaming,
+            since_ms: unix_millis(SystemTime::now()),
+            last_price_ms: Some(unix_millis(SystemTime::now())),
+            connections: 1,
+        }];
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal
+            .draw(|frame| Dashboard::default().render(frame, &statuses, &markets))
+            .unwrap();
+
+        let screen = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect::<String>();
+
+        assert!(screen.contains("binance"));
+        assert!(screen.contains("streaming"));
+        assert!(screen.contains("SOL/USDT"));
+        assert!(screen.contains("spread 100 bps"));
+        assert!(screen.contains("kraken:SOL/USDT"));
+        assert!(screen.contains("buy binance:SOLUSDT @ 100 sell kraken:SOL/USDT @ 101 100 bps"));
+    }
+
+    #[test]
+    fn test_format_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(3 * 3600 + 25 * 60 + 7);
+        assert_eq!(format_time(time), "03:25:07");
+    }
+}
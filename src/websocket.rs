@@ -1,48 +1,289 @@
-use futures::prelude::*;
+use futures::{ stream::FuturesUnordered, prelude::* };
 use stream::FusedStream;
 
-use std::time::Duration;
+use std::{ collections::BTreeSet, fmt, time::{ Duration, Instant } };
 
 use async_tungstenite::{ tokio::connect_async, tungstenite::Message };
-use tokio::{ select, time::{ self, sleep, sleep_until } };
+use tokio::{
+    select,
+    sync::mpsc::{ unbounded_channel, UnboundedReceiver, UnboundedSender },
+    time::{ self, sleep, sleep_until },
+};
 
-use crate::{ Sender, MarketPrice };
+use crate::{ config::ConfigError, health, metrics, Sender, MarketPrice };
 
 pub trait ExchangeWebSocketConfig {
     const EXCHANGE_ID: &'static str;
     const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
     const PING_INTERVAL: Duration = Duration::from_secs(30);
+    // without prices for longer the venue is reported stale, e.g. quiet on-chain pools
+    const STALE_AFTER: Duration = Duration::from_secs(60);
+    // more markets are sharded across as many connections as needed
+    const MAX_MARKETS_PER_CONNECTION: usize = usize::MAX;
+    // messages a connection sends per second at most, the first ping included
+    const MAX_MESSAGES_PER_SECOND: u32 = u32::MAX;
 
-    fn url() -> String;
-    fn get_subscribe_payload<'a>(markets: &[&'a str]) -> String;
-    fn parse_incoming_payload(payload: String) -> Result<MarketPrice, std::io::Error>;
+    // per connection state, e.g. subscription ids handed out by the server
+    type Session: Default + Clone;
+
+    fn urls() -> Result<Vec<String>, ConfigError>;
+    // the market as subscribed to and unsubscribed from, and as the prices name it when
+    // they name it alike, e.g. uppercased symbols
+    fn normalize_market(market: &str) -> String {
+        market.to_string()
+    }
+    // every connection starts from, read once with the venue configuration
+    fn session() -> Result<Self::Session, ConfigError> {
+        Ok(Self::Session::default())
+    }
+    fn get_subscribe_payloads(session: &mut Self::Session, markets: &[&str]) -> Vec<String>;
+    fn get_unsubscribe_payloads(session: &mut Self::Session, markets: &[&str]) -> Vec<String>;
+    // the prices of a frame, none for acknowledgements and the like
+    fn parse_incoming_payload(
+        session: &mut Self::Session,
+        payload: String
+    ) -> Result<Vec<MarketPrice>, std::io::Error>;
+}
+
+/// Change to the markets of a running venue task, applied on its live connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subscription {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+impl fmt::Display for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subscription::Subscribe(market) => write!(f, "subscribe {market}"),
+            Subscription::Unsubscribe(market) => write!(f, "unsubscribe {market}"),
+        }
+    }
+}
+
+impl Subscription {
+    fn normalized<T: ExchangeWebSocketConfig>(self) -> Self {
+        match self {
+            Subscription::Subscribe(market) => {
+                Subscription::Subscribe(T::normalize_market(&market))
+            }
+            Subscription::Unsubscribe(market) => {
+                Subscription::Unsubscribe(T::normalize_market(&market))
+            }
+        }
+    }
+}
+
+/// Sends the subscription changes of a running venue task, naming markets as it does.
+#[derive(Clone)]
+pub struct Control {
+    subscriptions: UnboundedSender<Subscription>,
+    normalize_market: fn(&str) -> String,
+}
+
+impl Control {
+    pub fn new<T: ExchangeWebSocketConfig>(subscriptions: UnboundedSender<Subscription>) -> Self {
+        Self { subscriptions, normalize_market: T::normalize_market }
+    }
+
+    /// The market as the venue names it.
+    pub fn market(&self, market: &str) -> String {
+        (self.normalize_market)(market)
+    }
+
+    /// Returns the change back once the venue task is gone.
+    pub fn send(&self, subscription: Subscription) -> Result<(), Subscription> {
+        self.subscriptions.send(subscription).map_err(|err| err.0)
+    }
+}
+
+pub async fn run_websocket<T: ExchangeWebSocketConfig>(
+    tx: Sender<Vec<MarketPrice>>,
+    markets: &[&str],
+    mut control: UnboundedReceiver<Subscription>
+) {
+    let urls = match T::urls() {
+        Ok(urls) if !urls.is_empty() => urls,
+        Ok(_) => {
+            log::error!("{} no endpoints configured", T::EXCHANGE_ID);
+            return;
+        }
+        Err(err) => {
+            log::error!("{} {err}", T::EXCHANGE_ID);
+            return;
+        }
+    };
+    let session = match T::session() {
+        Ok(session) => session,
+        Err(err) => {
+            log::error!("{} {err}", T::EXCHANGE_ID);
+            return;
+        }
+    };
+
+    let max_markets = T::MAX_MARKETS_PER_CONNECTION.max(1);
+
+    // the markets of every connection, and its control
+    let mut shards: Vec<(BTreeSet<String>, UnboundedSender<Subscription>)> = vec![];
+    let mut connections = FuturesUnordered::new();
+
+    // once each, as the venue names them
+    let markets = markets
+        .iter()
+        .map(|market| T::normalize_market(market))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    for markets in markets.chunks(max_markets) {
+        let markets = markets.iter().cloned().collect::<BTreeSet<_>>();
+        let (shard_tx, shard_rx) = unbounded_channel();
+        let shard = shards.len();
+        connections.push(
+            run_connection::<T>(&tx, &urls, &session, shard, markets.clone(), shard_rx)
+        );
+        shards.push((markets, shard_tx));
+    }
+
+    if shards.len() > 1 {
+        let (count, connections) = (markets.len(), shards.len());
+        log::info!("{} {count} markets over {connections} connections", T::EXCHANGE_ID);
+    }
+
+    loop {
+        select! {
+            _ = connections.next(), if !connections.is_empty() => {}
+
+            // the connections end within 500 millis once the prices are no longer received
+            _ = tx.closed() => {
+                while connections.next().await.is_some() {}
+                return;
+            }
+
+            Some(subscription) = control.recv() => {
+                let subscription = subscription.normalized::<T>();
+                match &subscription {
+                    Subscription::Subscribe(market) => {
+                        if shards.iter().any(|(markets, _)| markets.contains(market)) {
+                            continue;
+                        }
+
+                        // onto the connection with the fewest markets, or a new one
+                        let shard = shards
+                            .iter_mut()
+                            .filter(|(markets, _)| markets.len() < max_markets)
+                            .min_by_key(|(markets, _)| markets.len());
+
+                        if let Some((markets, shard_tx)) = shard {
+                            markets.insert(market.clone());
+                            let _ = shard_tx.send(subscription);
+                        } else {
+                            let markets = BTreeSet::from([market.clone()]);
+                            let (shard, (shard_tx, shard_rx)) = (shards.len(), unbounded_channel());
+                            connections.push(
+                                run_connection::<T>(
+                                    &tx,
+                                    &urls,
+                                    &session,
+                                    shard,
+                                    markets.clone(),
+                                    shard_rx
+                                )
+                            );
+                            shards.push((markets, shard_tx));
+                        }
+                    }
+                    Subscription::Unsubscribe(market) => {
+                        let shard = shards
+                            .iter_mut()
+                            .find(|(markets, _)| markets.contains(market));
+
+                        if let Some((markets, shard_tx)) = shard {
+                            markets.remove(market);
+                            let _ = shard_tx.send(subscription);
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
 
-pub async fn run_websocket<T: ExchangeWebSocketConfig>(tx: Sender<MarketPrice>, markets: &[&str]) {
+async fn run_connection<T: ExchangeWebSocketConfig>(
+    tx: &Sender<Vec<MarketPrice>>,
+    urls: &[String],
+    initial_session: &T::Session,
+    shard: usize,
+    // subscribed to again on every connection
+    mut markets: BTreeSet<String>,
+    mut control: UnboundedReceiver<Subscription>
+) {
+    // spacing the subscribe payloads after the ping to stay within the message rate
+    let message_interval = Duration::from_secs(1) / T::MAX_MESSAGES_PER_SECOND.max(1);
+
+    // fail over to the next endpoint whenever a connection fails or drops, the shards
+    // starting from different ones
+    let mut endpoints = urls.iter().enumerate().cycle().skip(shard % urls.len());
+    let mut reconnecting = false;
+
     while !tx.is_closed() {
         // sleeping to avoid max cpu usage in case of retry
         sleep(Duration::from_millis(100)).await;
 
-        log::debug!("{} connecting...", T::EXCHANGE_ID);
+        if reconnecting {
+            metrics::reconnected(T::EXCHANGE_ID);
+        }
+        reconnecting = true;
+
+        let Some((endpoint, url)) = endpoints.next() else {
+            return;
+        };
+
+        health::VENUES.connecting(T::EXCHANGE_ID, shard, T::STALE_AFTER, markets.len());
+
+        // the url is not logged as it may carry credentials
+        log::debug!("{} connecting to endpoint {endpoint}...", T::EXCHANGE_ID);
 
         let Ok(Ok((mut conn, _))) = time::timeout(
             T::CONNECT_TIMEOUT,
-            connect_async(T::url())
+            connect_async(url.as_str())
         ).await else {
+            log::debug!("{} cannot connect to endpoint {endpoint}", T::EXCHANGE_ID);
+            health::VENUES.backoff(T::EXCHANGE_ID, shard);
             continue;
         };
 
-        log::debug!("{} connected", T::EXCHANGE_ID);
+        log::debug!("{} connected to endpoint {endpoint}", T::EXCHANGE_ID);
+
+        let mut session = initial_session.clone();
 
         let mut ping_deadline = time::Instant::now() + T::PING_INTERVAL;
         let _ = conn.send(Message::Ping(vec![])).await;
         log::trace!("{} ping", T::EXCHANGE_ID);
 
-        if conn.send(Message::Text(T::get_subscribe_payload(markets))).await.is_err() {
+        let subscribing = markets.iter().map(String::as_str).collect::<Vec<_>>();
+        let payloads = if subscribing.is_empty() {
+            vec![]
+        } else {
+            T::get_subscribe_payloads(&mut session, &subscribing)
+        };
+
+        let mut subscribed = true;
+        for payload in payloads {
+            sleep(message_interval).await;
+            if conn.send(Message::Text(payload)).await.is_err() {
+                subscribed = false;
+                break;
+            }
+        }
+
+        if !subscribed {
+            health::VENUES.backoff(T::EXCHANGE_ID, shard);
             continue;
         }
 
         log::debug!("{} subscribed", T::EXCHANGE_ID);
+        health::VENUES.subscribed(T::EXCHANGE_ID, shard);
 
         while !tx.is_closed() && !conn.is_terminated() {
             select! {
@@ -54,15 +295,82 @@ pub async fn run_websocket<T: ExchangeWebSocketConfig>(tx: Sender<MarketPrice>,
                     log::trace!("{} ping", T::EXCHANGE_ID);
                 }
 
+                Some(subscription) = control.recv() => {
+                    let payloads = match &subscription {
+                        Subscription::Subscribe(market) => {
+                            if !markets.insert(market.clone()) {
+                                continue;
+                            }
+                            T::get_subscribe_payloads(&mut session, &[market])
+                        }
+                        Subscription::Unsubscribe(market) => {
+                            if !markets.remove(market) {
+                                continue;
+                            }
+                            T::get_unsubscribe_payloads(&mut session, &[market])
+                        }
+                    };
+
+                    log::info!("{} {subscription}", T::EXCHANGE_ID);
+                    health::VENUES.markets(T::EXCHANGE_ID, shard, markets.len());
+
+                    let mut sent = true;
+                    for payload in payloads {
+                        sleep(message_interval).await;
+                        if conn.send(Message::Text(payload)).await.is_err() {
+                            sent = false;
+                            break;
+                        }
+                    }
+
+                    // reconnecting subscribes to the markets again
+                    if !sent {
+                        break;
+                    }
+                }
+
                 res = conn.next() => {
                     if let Some(Ok(message)) = res {
                         log::trace!("{} {message:?}", T::EXCHANGE_ID);
 
+                        let received_at = Instant::now();
+                        metrics::message_received(T::EXCHANGE_ID, received_at);
+
                         match message {
                             Message::Text(payload) => {
-                                if let Ok(market_price) = T::parse_incoming_payload(payload) {
-                                    // always replace to the most up-to-date market price
-                                    tx.send_replace(market_price);
+                                match T::parse_incoming_payload(&mut session, payload) {
+                                    Ok(market_prices) if market_prices.is_empty() => {}
+                                    Ok(market_prices) => {
+                                        health::VENUES.price_received(T::EXCHANGE_ID, shard);
+
+                                        let event_times = market_prices
+                                            .iter()
+                                            .filter_map(|market_price| market_price.event_time);
+                                        for event_time in event_times {
+                                            metrics::event_received(T::EXCHANGE_ID, event_time);
+                                        }
+                                        let slots = market_prices
+                                            .iter()
+                                            .filter_map(|market_price| market_price.slot);
+                                        for slot in slots {
+                                            metrics::slot_received(T::EXCHANGE_ID, slot);
+                                        }
+
+                                        let market_prices = market_prices
+                                            .into_iter()
+                                            .map(|market_price| MarketPrice {
+                                                received_at: Some(received_at),
+                                                ..market_price
+                                            })
+                                            .collect();
+
+                                        // every frame of every connection in order, those
+                                        // of a frame together
+                                        if tx.send(market_prices).await.is_err() {
+                                            break;
+                                        }
+                                    }
+                                    Err(_) => metrics::parse_failed(T::EXCHANGE_ID),
                                 }
                             }
                             Message::Ping(value) => {
@@ -81,6 +389,8 @@ pub async fn run_websocket<T: ExchangeWebSocketConfig>(tx: Sender<MarketPrice>,
         if !conn.is_terminated() {
             let _ = conn.close(None).await;
         }
+
+        health::VENUES.backoff(T::EXCHANGE_ID, shard);
     }
 }
 
@@ -97,31 +407,100 @@ mod tests {
         TestExchange {}
         impl ExchangeWebSocketConfig for TestExchange {
             const EXCHANGE_ID: &'static str = "test";
-            fn url() -> String;
-            fn get_subscribe_payload<'a>(markets: &[&'a str]) -> String;
-            fn parse_incoming_payload(payload: String) -> Result<MarketPrice, std::io::Error>;
+            const MAX_MARKETS_PER_CONNECTION: usize = 1;
+            const MAX_MESSAGES_PER_SECOND: u32 = 10;
+            type Session = ();
+            fn urls() -> Result<Vec<String>, ConfigError>;
+            fn get_subscribe_payloads<'a>(session: &mut (), markets: &[&'a str]) -> Vec<String>;
+            fn get_unsubscribe_payloads<'a>(session: &mut (), markets: &[&'a str]) -> Vec<String>;
+            fn parse_incoming_payload(
+                session: &mut (),
+                payload: String
+            ) -> Result<Vec<MarketPrice>, std::io::Error>;
         }
     }
 
+    // mocked static methods share their expectations across tests
+    static LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
     #[tokio::test]
     async fn test_run_websocket() {
+        let _lock = LOCK.lock().await;
+
         env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
         let server = WsMockServer::start().await;
 
         let mut seq = Sequence::new();
 
-        let ctx = MockTestExchange::url_context();
-        ctx.expect().once().return_const(server.uri().await);
+        let uri = server.uri().await;
+        let ctx = MockTestExchange::urls_context();
+        ctx.expect()
+            .once()
+            .returning(move || Ok(vec![uri.clone()]));
 
-        let ctx = MockTestExchange::get_subscribe_payload_context();
-        ctx.expect().once().in_sequence(&mut seq).return_const("test_subscribe".to_string());
+        let ctx = MockTestExchange::get_subscribe_payloads_context();
+        ctx.expect()
+            .once()
+            .in_sequence(&mut seq)
+            .return_const(vec!["test_subscribe".to_string()]);
 
         let ctx = MockTestExchange::parse_incoming_payload_context();
         ctx.expect()
             .once()
             .in_sequence(&mut seq)
-            .returning(|_| Ok(MarketPrice::default()));
+            .returning(|_, _| {
+                let market_price = |market: &str| MarketPrice {
+                    market: market.to_string(),
+                    ..Default::default()
+                };
+                Ok(vec![market_price("btcusdt"), market_price("ethusdt")])
+            });
+
+        WsMock::new()
+            .matcher(StringExact::new("test_subscribe"))
+            .respond_with(Message::Text("test_response".to_string()))
+            .expect(1)
+            .mount(&server).await;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        join!(run_websocket::<MockTestExchange>(tx, &["btcusdt"], control_rx), async move {
+            let market_prices = time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap();
+
+            // every price of the frame
+            let markets = market_prices
+                .unwrap()
+                .iter()
+                .map(|market_price| market_price.market.clone())
+                .collect::<Vec<_>>();
+            assert_eq!(markets, ["btcusdt", "ethusdt"]);
+            drop(rx);
+        });
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_websocket_failover() {
+        let _lock = LOCK.lock().await;
+
+        let server = WsMockServer::start().await;
+
+        let uri = server.uri().await;
+        let ctx = MockTestExchange::urls_context();
+        ctx.expect()
+            .once()
+            .returning(move || Ok(vec!["ws://127.0.0.1:1".to_string(), uri.clone()]));
+
+        let ctx = MockTestExchange::get_subscribe_payloads_context();
+        ctx.expect().once().return_const(vec!["test_subscribe".to_string()]);
+
+        let ctx = MockTestExchange::parse_incoming_payload_context();
+        ctx.expect()
+            .once()
+            .returning(|_, _| Ok(vec![MarketPrice::default()]));
 
         WsMock::new()
             .matcher(StringExact::new("test_subscribe"))
@@ -129,13 +508,122 @@ mod tests {
             .expect(1)
             .mount(&server).await;
 
-        let (tx, rx) = tokio::sync::watch::channel(MarketPrice::default());
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
 
-        join!(run_websocket::<MockTestExchange>(tx, &["btcusdt"]), async move {
+        join!(run_websocket::<MockTestExchange>(tx, &["btcusdt"], control_rx), async move {
             sleep(Duration::from_secs(1)).await;
             drop(rx);
         });
 
         server.verify().await;
     }
+
+    #[tokio::test]
+    async fn test_run_websocket_shards() {
+        let _lock = LOCK.lock().await;
+
+        let servers = [WsMockServer::start().await, WsMockServer::start().await];
+
+        let uris = [servers[0].uri().await, servers[1].uri().await];
+        let ctx = MockTestExchange::urls_context();
+        ctx.expect()
+            .once()
+            .returning(move || Ok(uris.to_vec()));
+
+        // a connection per market, each to its own endpoint
+        let ctx = MockTestExchange::get_subscribe_payloads_context();
+        ctx.expect()
+            .times(2)
+            .returning(|_, markets| vec![format!("subscribe {}", markets.join(","))]);
+
+        let ctx = MockTestExchange::parse_incoming_payload_context();
+        ctx.expect()
+            .times(2)
+            .returning(|_, _| Ok(vec![MarketPrice::default()]));
+
+        for (server, payload) in servers.iter().zip(["subscribe btcusdt", "subscribe ethusdt"]) {
+            WsMock::new()
+                .matcher(StringExact::new(payload))
+                .respond_with(Message::Text("test_response".to_string()))
+                .expect(1)
+                .mount(server).await;
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // the frames of both connections, neither replacing the other
+        let markets = ["btcusdt", "ethusdt"];
+        join!(run_websocket::<MockTestExchange>(tx, &markets, control_rx), async move {
+            for _ in 0..2 {
+                time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap().unwrap();
+            }
+            drop(rx);
+        });
+
+        for server in &servers {
+            server.verify().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_websocket_control() {
+        let _lock = LOCK.lock().await;
+
+        let server = WsMockServer::start().await;
+
+        let uri = server.uri().await;
+        let ctx = MockTestExchange::urls_context();
+        ctx.expect()
+            .once()
+            .returning(move || Ok(vec![uri.clone()]));
+
+        let ctx = MockTestExchange::get_subscribe_payloads_context();
+        ctx.expect()
+            .times(2)
+            .returning(|_, markets| vec![format!("subscribe {}", markets.join(","))]);
+
+        let ctx = MockTestExchange::get_unsubscribe_payloads_context();
+        ctx.expect()
+            .once()
+            .returning(|_, markets| vec![format!("unsubscribe {}", markets.join(","))]);
+
+        for payload in ["subscribe btcusdt", "unsubscribe btcusdt", "subscribe ethusdt"] {
+            WsMock::new().matcher(StringExact::new(payload)).expect(1).mount(&server).await;
+        }
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let (control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // on the live connection, the market unsubscribed from making room for the other
+        join!(run_websocket::<MockTestExchange>(tx, &["btcusdt"], control_rx), async move {
+            sleep(Duration::from_millis(500)).await;
+            control_tx.send(Subscription::Unsubscribe("btcusdt".to_string())).unwrap();
+            control_tx.send(Subscription::Subscribe("ethusdt".to_string())).unwrap();
+            control_tx.send(Subscription::Subscribe("ethusdt".to_string())).unwrap();
+            sleep(Duration::from_secs(1)).await;
+            drop(rx);
+        });
+
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_websocket_config_error() {
+        let _lock = LOCK.lock().await;
+
+        let ctx = MockTestExchange::urls_context();
+        ctx.expect()
+            .once()
+            .returning(|| Err(ConfigError::Missing("TEST_URL")));
+
+        let ctx = MockTestExchange::get_subscribe_payloads_context();
+        ctx.expect().never();
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let (_control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        run_websocket::<MockTestExchange>(tx, &["btcusdt"], control_rx).await;
+    }
 }